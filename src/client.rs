@@ -0,0 +1,100 @@
+// src/client.rs
+//
+// Encoders and decoders for the u128 keys used by the Liquify buy list and order fill tree. The codecs have no
+// dependencies, so off-ledger services can build this module with `default-features = false, features = ["client"]`
+// without pulling in the blueprint or scrypto. The crate itself still links `std`, it is not a `no_std` crate.
+// With the blueprint enabled the decoded parts derive `ScryptoSbor` so the component can return them as is.
+
+pub mod buy_list_key {
+    // Flags byte: bit 0 is auto_unstake, bit 7 marks the current layout. Legacy keys always have zeros here.
+    pub const FLAG_AUTO_UNSTAKE: u8 = 0x01;
+    pub const FLAG_LAYOUT_V2: u8 = 0x80;
+    pub const MAX_RECEIPT_ID: u64 = (1u64 << 40) - 1;
+
+    #[cfg(feature = "blueprint")]
+    use scrypto::prelude::*;
+
+    #[derive(PartialEq, Eq, Debug, Clone, Copy)]
+    #[cfg_attr(feature = "blueprint", derive(ScryptoSbor))]
+    pub struct BuyListKeyParts {
+        pub discount_basis_points: u16,
+        pub position: u64,
+        pub auto_unstake: bool,
+        pub receipt_id: u64,
+        pub legacy_layout: bool,
+    }
+
+    pub fn encode(discount_basis_points: u16, auto_unstake: bool, position: u64, receipt_id: u64) -> u128 {
+        // Pack: discount (16 bits) | position (64 bits) | flags (8 bits) | receipt_id (40 bits) = 128 bits
+        assert!(receipt_id <= MAX_RECEIPT_ID, "Receipt ID does not fit in a buy list key");
+        let flags = FLAG_LAYOUT_V2 | if auto_unstake { FLAG_AUTO_UNSTAKE } else { 0 };
+
+        ((discount_basis_points as u128) << 112) |  // Top 16 bits
+        ((position as u128) << 48) |                // Next 64 bits
+        ((flags as u128) << 40) |                   // Next 8 bits
+        (receipt_id as u128)                        // Bottom 40 bits
+    }
+
+    pub fn decode(key: u128) -> BuyListKeyParts {
+        let discount_basis_points = (key >> 112) as u16;
+        let position = (key >> 48) as u64;
+        let flags = (key >> 40) as u8;
+
+        if flags & FLAG_LAYOUT_V2 != 0 {
+            BuyListKeyParts {
+                discount_basis_points,
+                position,
+                auto_unstake: flags & FLAG_AUTO_UNSTAKE != 0,
+                receipt_id: (key & MAX_RECEIPT_ID as u128) as u64,
+                legacy_layout: false,
+            }
+        } else {
+            // Legacy layout: discount (16 bits) | position (64 bits) | auto_unstake (16 bits) | receipt_id (32 bits)
+            BuyListKeyParts {
+                discount_basis_points,
+                position,
+                auto_unstake: ((key >> 32) & 0xFFFF) == 1,
+                receipt_id: (key & 0xFFFF_FFFF) as u64,
+                legacy_layout: true,
+            }
+        }
+    }
+
+    // Discount tiers are 2.5 basis points apart and keys store the floored value, so this rounds back up to the tier
+    pub fn tier_index(discount_basis_points: u16) -> usize {
+        (discount_basis_points as usize * 2).div_ceil(5)
+    }
+}
+
+pub mod matching_policy {
+    #[cfg(feature = "blueprint")]
+    use scrypto::prelude::*;
+
+    // How an unstake is split between the positions of one discount tier, shared by the blueprint and the router
+    #[derive(PartialEq, Eq, Debug, Clone, Copy)]
+    #[cfg_attr(feature = "blueprint", derive(ScryptoSbor, ManifestSbor))]
+    pub enum MatchingPolicy {
+        Fifo,
+        ProRata,
+    }
+}
+
+pub mod order_fill_key {
+    #[derive(PartialEq, Eq, Debug, Clone, Copy)]
+    pub struct OrderFillKeyParts {
+        pub receipt_id: u64,
+        pub fill_number: u64,
+    }
+
+    pub fn encode(receipt_id: u64, fill_number: u64) -> u128 {
+        // Pack: receipt_id (64 bits) | fill_number (64 bits) = 128 bits
+        ((receipt_id as u128) << 64) | (fill_number as u128)
+    }
+
+    pub fn decode(key: u128) -> OrderFillKeyParts {
+        OrderFillKeyParts {
+            receipt_id: (key >> 64) as u64,
+            fill_number: key as u64,
+        }
+    }
+}
@@ -1,11 +1,11 @@
 // src/interface.rs
 
 use scrypto::prelude::*;
-use crate::liquify::{liquify_module::Liquify, LiquidityData, ReceiptDetailData, AutomationReadyReceipt};
+use crate::liquify::{liquify_module::Liquify, LiquidityData, ReceiptDetailData, AutomationReadyReceipt, AccountingAudit, BuyListKeyParts, SellOrderData, UnstakeExecutionMode, FillFilter};
 
 
 #[blueprint]
-#[types(ComponentAddress, ResourceAddress, u32, LiquidityData, ReceiptDetailData, AutomationReadyReceipt)]
+#[types(ComponentAddress, ResourceAddress, u32, LiquidityData, ReceiptDetailData, AutomationReadyReceipt, SellOrderData)]
 mod interface_module {
 
     enable_method_auth! {
@@ -14,19 +14,39 @@ mod interface_module {
         },
         methods {
             add_liquidity => PUBLIC;
+            add_liquidity_annualized => PUBLIC;
+            add_liquidity_ladder => PUBLIC;
             increase_liquidity => PUBLIC;
+            increase_liquidity_keep_position => PUBLIC;
             remove_liquidity => PUBLIC;
             liquify_unstake => PUBLIC;
             liquify_unstake_off_ledger => PUBLIC;
+            liquify_unstake_with_mode => PUBLIC;
+            liquify_unstake_off_ledger_with_mode => PUBLIC;
+            liquify_claim_nft => PUBLIC;
             collect_fills => PUBLIC;
+            collect_fills_filtered => PUBLIC;
+            collect_fills_as_xrd => PUBLIC;
+            set_delivery_account => PUBLIC;
+            deliver_fills => PUBLIC;
+            withdraw_claimable => PUBLIC;
+            deposit_claimable => PUBLIC;
+            close_position => PUBLIC;
             update_auto_refill_status => PUBLIC;
             update_refill_threshold => PUBLIC;
+            set_discount_override => PUBLIC;
             cycle_liquidity => PUBLIC;
             get_claimable_xrd => PUBLIC;
             get_raw_buy_list_range => PUBLIC;
+            audit_accounting => PUBLIC;
+            decode_buy_list_key => PUBLIC;
             get_automation_ready_receipts => PUBLIC;
             get_receipt_detail => PUBLIC;
             get_active_liquidity_positions => PUBLIC;
+            create_sell_order => PUBLIC;
+            cancel_sell_order => PUBLIC;
+            collect_sell_order_proceeds => PUBLIC;
+            get_sell_order => PUBLIC;
             set_interface_target => restrict_to: [owner];
         }
     }
@@ -108,12 +128,62 @@ mod interface_module {
             receipt_bucket
         }
 
+        pub fn add_liquidity_annualized(
+            &mut self, 
+            xrd_bucket: Bucket, 
+            annualized_rate: Decimal, 
+            auto_unstake: bool,
+            auto_refill: bool,
+            refill_threshold: Decimal,
+            automation_fee: Decimal 
+        ) -> NonFungibleBucket {
+            
+            let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
+
+            liquify_component.add_liquidity_annualized(
+                xrd_bucket, 
+                annualized_rate, 
+                auto_unstake,
+                auto_refill,
+                refill_threshold,
+                automation_fee  
+            )
+        }
+
+        pub fn add_liquidity_ladder(
+            &mut self, 
+            xrd_bucket: Bucket, 
+            rungs: Vec<(Decimal, Decimal)>, 
+            auto_unstake: bool,
+            auto_refill: bool,
+            refill_threshold: Decimal,
+            automation_fee: Decimal 
+        ) -> NonFungibleBucket {
+            
+            let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
+
+            liquify_component.add_liquidity_ladder(
+                xrd_bucket, 
+                rungs, 
+                auto_unstake,
+                auto_refill,
+                refill_threshold,
+                automation_fee  
+            )
+        }
+
         pub fn increase_liquidity(&mut self, receipt_bucket: Bucket, xrd_bucket: Bucket) -> Bucket {
             let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
             
             liquify_component.increase_liquidity(receipt_bucket, xrd_bucket)
         }
 
+        pub fn increase_liquidity_keep_position(&mut self, receipt_bucket: Bucket, xrd_bucket: Bucket) -> Bucket {
+            let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
+            
+            liquify_component.increase_liquidity_keep_position(receipt_bucket, xrd_bucket)
+        }
+
         pub fn remove_liquidity(&mut self, liquidity_receipt_bucket: Bucket) -> (Bucket, Bucket) {
             let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
             
@@ -135,12 +205,76 @@ mod interface_module {
             (xrd_bucket, remaining_lsu.into())
         }
 
+        pub fn liquify_unstake_with_mode(&mut self, lsu_bucket: Bucket, max_iterations: u8, execution_mode: UnstakeExecutionMode, seller_badge: Option<Proof>) -> (Bucket, Bucket) {
+            let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
+            
+            let (xrd_bucket, remaining_lsu) = liquify_component.liquify_unstake_with_mode(lsu_bucket.as_fungible(), max_iterations, execution_mode, seller_badge);
+            (xrd_bucket, remaining_lsu.into())
+        }
+
+        pub fn liquify_unstake_off_ledger_with_mode(&mut self, lsu_bucket: Bucket, order_keys: Vec<u128>, execution_mode: UnstakeExecutionMode, seller_badge: Option<Proof>) -> (Bucket, Bucket) {
+            let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
+            
+            let (xrd_bucket, remaining_lsu) = liquify_component.liquify_unstake_off_ledger_with_mode(lsu_bucket.as_fungible(), order_keys, execution_mode, seller_badge);
+            
+            (xrd_bucket, remaining_lsu.into())
+        }
+
+        pub fn liquify_claim_nft(&mut self, nft_bucket: Bucket, max_iterations: u8) -> (Bucket, Bucket) {
+            let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
+            
+            let (xrd_bucket, remaining_nfts) = liquify_component.liquify_claim_nft(nft_bucket.as_non_fungible(), max_iterations);
+            (xrd_bucket, remaining_nfts.into())
+        }
+
         pub fn collect_fills(&mut self, receipt_bucket: Bucket, number_of_fills_to_collect: u64) -> (Vec<Bucket>, Bucket) {
             let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
             
             liquify_component.collect_fills(receipt_bucket, number_of_fills_to_collect)
         }
 
+        pub fn collect_fills_filtered(&mut self, receipt_bucket: Bucket, number_of_fills_to_collect: u64, filter: FillFilter) -> (Vec<Bucket>, Bucket) {
+            let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
+            
+            liquify_component.collect_fills_filtered(receipt_bucket, number_of_fills_to_collect, filter)
+        }
+
+        pub fn collect_fills_as_xrd(&mut self, receipt_bucket: Bucket, max_fills: u64) -> (Bucket, Bucket) {
+            let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
+            
+            liquify_component.collect_fills_as_xrd(receipt_bucket, max_fills)
+        }
+
+        pub fn set_delivery_account(&mut self, receipt_bucket: Bucket, delivery_account: Option<ComponentAddress>) -> Bucket {
+            let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
+            
+            liquify_component.set_delivery_account(receipt_bucket, delivery_account)
+        }
+
+        pub fn deliver_fills(&mut self, receipt_ids: Vec<NonFungibleLocalId>, max_fills: u64) -> Bucket {
+            let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
+            
+            liquify_component.deliver_fills(receipt_ids, max_fills).into()
+        }
+
+        pub fn withdraw_claimable(&mut self, receipt_proof: Proof) -> Bucket {
+            let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
+            
+            liquify_component.withdraw_claimable(receipt_proof)
+        }
+
+        pub fn deposit_claimable(&mut self, receipt_proof: Proof, xrd_bucket: Bucket) {
+            let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
+            
+            liquify_component.deposit_claimable(receipt_proof, xrd_bucket)
+        }
+
+        pub fn close_position(&mut self, receipt_bucket: Bucket) {
+            let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
+            
+            liquify_component.close_position(receipt_bucket)
+        }
+
         pub fn update_auto_refill_status(&mut self, receipt_bucket: Bucket, auto_refill: bool) -> Bucket {
             let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
             
@@ -153,6 +287,12 @@ mod interface_module {
             liquify_component.update_refill_threshold(receipt_bucket, refill_threshold)
         }
 
+        pub fn set_discount_override(&mut self, receipt_bucket: Bucket, validator: ComponentAddress, discount: Option<Decimal>) -> Bucket {
+            let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
+            
+            liquify_component.set_discount_override(receipt_bucket, validator, discount)
+        }
+
         pub fn cycle_liquidity(&mut self, receipt_ids: Vec<NonFungibleLocalId>) -> Bucket {
             let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
             
@@ -183,10 +323,46 @@ mod interface_module {
             liquify_component.get_raw_buy_list_range(start_index, count)
         }
 
+        pub fn audit_accounting(&self, start_index: u64, count: u64) -> AccountingAudit {
+            let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
+            
+            liquify_component.audit_accounting(start_index, count)
+        }
+
+        pub fn decode_buy_list_key(&self, key: u128) -> BuyListKeyParts {
+            let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
+            
+            liquify_component.decode_buy_list_key(key)
+        }
+
         pub fn get_active_liquidity_positions(&self, start_index: u64, count: u64) -> Vec<ReceiptDetailData> {
             let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
             
             liquify_component.get_active_liquidity_positions(start_index, count)
         }
+
+        pub fn create_sell_order(&mut self, lsu_bucket: Bucket, max_discount: Decimal, max_iterations: u8, seller_badge: Option<Proof>) -> (Bucket, Bucket) {
+            let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
+            
+            liquify_component.create_sell_order(lsu_bucket.as_fungible(), max_discount, max_iterations, seller_badge)
+        }
+
+        pub fn cancel_sell_order(&mut self, sell_order_receipt_bucket: Bucket) -> (Bucket, Bucket) {
+            let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
+            
+            liquify_component.cancel_sell_order(sell_order_receipt_bucket)
+        }
+
+        pub fn collect_sell_order_proceeds(&mut self, sell_order_receipt_bucket: Bucket) -> (Bucket, Bucket) {
+            let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
+            
+            liquify_component.collect_sell_order_proceeds(sell_order_receipt_bucket)
+        }
+
+        pub fn get_sell_order(&self, sell_order_id: NonFungibleLocalId) -> SellOrderData {
+            let liquify_component: Global<Liquify> = self.active_liquify_component.unwrap().into();
+            
+            liquify_component.get_sell_order(sell_order_id)
+        }
     }
 }
\ No newline at end of file
@@ -1,4 +1,13 @@
+#[cfg(feature = "blueprint")]
 mod liquify;
+#[cfg(feature = "blueprint")]
 mod interface;
+#[cfg(feature = "blueprint")]
+pub use liquify::{AccountingAudit, ReceiptDetailData, SubPosition};
 // mod liquify_old;
 
+#[cfg(any(feature = "client", feature = "blueprint"))]
+pub mod client;
+#[cfg(feature = "router")]
+pub mod router;
+
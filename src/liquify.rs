@@ -1,5 +1,18 @@
 use scrypto::prelude::*;
 use scrypto_avltree::AvlTree;
+use crate::client::{buy_list_key, order_fill_key};
+pub use crate::client::buy_list_key::BuyListKeyParts;
+pub use crate::client::matching_policy::MatchingPolicy;
+
+// Number of 5 minute epochs in a year, used to convert annualized rates into discounts
+pub const EPOCHS_PER_YEAR: u64 = 105_120;
+
+// Most discounts a single ladder receipt can be spread across, bounding the buy list keys one receipt holds
+pub const MAX_LADDER_RUNGS: usize = 10;
+
+// Sub-positions a receipt can build up from top ups and refills before new XRD is folded into an existing one,
+// leaving room for every rung of a ladder plus one refill per rung
+pub const MAX_SUB_POSITIONS: usize = 2 * MAX_LADDER_RUNGS;
 
 #[derive(ScryptoSbor, NonFungibleData, Debug)]
 pub struct UnstakeData {
@@ -24,6 +37,40 @@ pub struct ReceiptDetailData {
     pub total_stake_claim_value: Decimal,
     pub total_lsu_redemption_value: Decimal,
     pub automation_fee: Decimal,
+    pub discount_overrides: Vec<(ComponentAddress, Decimal)>,
+    pub annualized_rate: Option<Decimal>,
+    pub effective_discount: Decimal,
+    pub lsu_fills_unbonding: u64,
+    pub redemption_ready_epoch: u64,
+    pub mid_cycle: bool,
+    pub claimable_xrd_balance: Decimal,
+    pub sub_positions: Vec<SubPosition>,
+    pub ladder_weights: Vec<(Decimal, Decimal)>,
+}
+
+#[derive(ScryptoSbor, Debug, Clone)]
+pub struct EffectiveDiscountTier {
+    pub discount: Decimal,
+    pub effective_discount: Decimal,
+    pub xrd_liquidity: Decimal,
+}
+
+#[derive(ScryptoSbor, Debug, Clone)]
+pub struct AccountingAudit {
+    pub start_index: u64,
+    pub entries_checked: u64,
+    pub receipts_checked: u64,
+    pub next_index: Option<u64>,
+    pub xrd_liquidity_available_sum: Decimal,
+    pub auto_unstake_true_sum: Decimal,
+    pub auto_unstake_false_sum: Decimal,
+    pub xrd_liquidity_amount: Decimal,
+    pub total_xrd_locked: Decimal,
+    pub liquidity_index_auto_unstake_true_total: Decimal,
+    pub liquidity_index_auto_unstake_false_total: Decimal,
+    pub claimable_xrd_amount: Decimal,
+    pub total_xrd_claimable: Decimal,
+    pub discrepancies: Vec<String>,
 }
 
 #[derive(ScryptoSbor, Debug, Clone)]
@@ -47,6 +94,7 @@ pub struct LiquidityReceipt {
     refill_threshold: Decimal,
     #[mutable]
     automation_fee: Decimal,
+    annualized_rate: Option<Decimal>,
 }
 
 #[derive(ScryptoSbor, PartialEq, Debug, Clone)]
@@ -55,6 +103,84 @@ pub struct LiquidityData {
     pub xrd_liquidity_available: Decimal,
     pub fills_to_collect: u64,
     pub last_added_epoch: u32,
+    pub discount_override_validators: Vec<ComponentAddress>,
+    pub delivery_account: Option<ComponentAddress>,
+    pub lsu_fills_unbonding: u64,
+    pub redemption_ready_epoch: u64,
+    pub mid_cycle: bool,
+    pub cycle_fee_charged: Decimal,
+    pub claimable_xrd_balance: Decimal,
+    // Buy list key of the receipt's original position, `None` once all its liquidity has been used up
+    pub primary_buy_list_key: Option<u128>,
+    pub sub_positions: Vec<SubPosition>,
+    pub ladder_weights: Vec<(Decimal, Decimal)>,
+}
+
+// Liquidity queued under its own buy list key behind the receipt's original position. The receipt's
+// `xrd_liquidity_available` includes these amounts, whatever isn't in a sub-position sits at the original key.
+#[derive(ScryptoSbor, PartialEq, Debug, Clone)]
+pub struct SubPosition {
+    pub buy_list_key: u128,
+    pub discount: Decimal,
+    pub xrd_liquidity_available: Decimal,
+}
+
+impl LiquidityData {
+    pub fn sub_position_liquidity(&self) -> Decimal {
+        self.sub_positions.iter().fold(dec!(0), |sum, sub_position| sum + sub_position.xrd_liquidity_available)
+    }
+
+    pub fn is_sub_position_key(&self, key: u128) -> bool {
+        self.sub_positions.iter().any(|sub_position| sub_position.buy_list_key == key)
+    }
+
+    // Available liquidity queued under one of the receipt's buy list keys
+    pub fn key_liquidity_available(&self, key: u128) -> Decimal {
+        match self.sub_positions.iter().find(|sub_position| sub_position.buy_list_key == key) {
+            Some(sub_position) => sub_position.xrd_liquidity_available,
+            None => self.xrd_liquidity_available - self.sub_position_liquidity(),
+        }
+    }
+}
+
+// Splits an amount across ladder rungs in proportion to their weights, the last rung takes the rounding remainder
+pub fn split_by_weight(amount: Decimal, rungs: &[(Decimal, Decimal)]) -> Vec<(Decimal, Decimal)> {
+    let total_weight = rungs.iter().fold(dec!(0), |sum, (_, weight)| sum + *weight);
+    let mut allocated = dec!(0);
+
+    rungs.iter().enumerate().map(|(index, (discount, weight))| {
+        let share = if index == rungs.len() - 1 {
+            amount - allocated
+        } else {
+            amount * *weight / total_weight
+        };
+        allocated += share;
+        (*discount, share)
+    }).collect()
+}
+
+#[derive(ScryptoSbor, PartialEq, Eq, Hash, Debug, Clone)]
+pub struct DiscountOverrideKey {
+    pub receipt_id: NonFungibleGlobalId,
+    pub validator: ComponentAddress,
+}
+
+#[derive(NonFungibleData, ScryptoSbor, PartialEq, Debug, Clone)]
+pub struct SellOrderReceipt {
+    key_image_url: Url,
+    lsu_resource: ResourceAddress,
+    max_discount: Decimal,
+}
+
+#[derive(ScryptoSbor, PartialEq, Debug, Clone)]
+pub struct SellOrderData {
+    pub lsu_resource: ResourceAddress,
+    pub max_discount: Decimal,
+    pub lsu_remaining: Decimal,
+    pub lsu_sold: Decimal,
+    pub xrd_proceeds: Decimal,
+    pub sell_list_key: u128,
+    pub seller_receipts: Vec<NonFungibleGlobalId>,
 }
 
 #[derive(ScryptoSbor, ScryptoEvent)]
@@ -65,6 +191,7 @@ struct LiquidityAddedEvent {
     auto_unstake: bool,
     auto_refill: bool,
     refill_threshold: Decimal,
+    annualized_rate: Option<Decimal>,
 }
 
 #[derive(ScryptoSbor, ScryptoEvent)]
@@ -73,12 +200,20 @@ struct LiquidityIncreasedEvent {
     additional_xrd: Decimal,
 }
 
+#[derive(ScryptoSbor, ScryptoEvent)]
+struct LadderCreatedEvent {
+    receipt_id: NonFungibleLocalId,
+    xrd_amount: Decimal,
+    rungs: Vec<(Decimal, Decimal)>,
+}
+
 #[derive(ScryptoSbor, ScryptoEvent)]
 struct OrderFillEvent {
     receipt_id: NonFungibleLocalId,
     lsu_amount: Decimal,
     xrd_amount: Decimal,
     discount: Decimal,
+    wash_fill: bool,
 }
 
 #[derive(ScryptoSbor, ScryptoEvent)]
@@ -88,12 +223,34 @@ struct LiquifyUnstakeEvent {
     xrd_received: Decimal,
 }
 
+#[derive(ScryptoSbor, ScryptoEvent)]
+struct ClaimNftFillEvent {
+    receipt_id: NonFungibleLocalId,
+    claim_nft: NonFungibleGlobalId,
+    claim_amount: Decimal,
+    xrd_amount: Decimal,
+    discount: Decimal,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+struct LiquifyClaimNftEvent {
+    nft_resource: ResourceAddress,
+    claim_amount: Decimal,
+    xrd_received: Decimal,
+}
+
 #[derive(ScryptoSbor, ScryptoEvent)]
 struct LiquidityRemovedEvent {
     receipt_id: NonFungibleLocalId,
     xrd_amount: Decimal,
 }
 
+#[derive(ScryptoSbor, ScryptoEvent)]
+struct PositionClosedEvent {
+    receipt_id: NonFungibleLocalId,
+    xrd_liquidity_filled: Decimal,
+}
+
 #[derive(ScryptoSbor, ScryptoEvent)]
 struct CollectFillsEvent {
     receipt_id: NonFungibleLocalId,
@@ -102,11 +259,54 @@ struct CollectFillsEvent {
     stake_claim_nfts_collected: Vec<NonFungibleGlobalId>,
 }
 
+#[derive(ScryptoSbor, ScryptoEvent)]
+struct FillsClaimedEvent {
+    receipt_id: NonFungibleLocalId,
+    fills_claimed: u64,
+    xrd_claimed: Decimal,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+struct DeliveryAccountUpdatedEvent {
+    receipt_id: NonFungibleLocalId,
+    delivery_account: Option<ComponentAddress>,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+struct FillsDeliveredEvent {
+    receipt_id: NonFungibleLocalId,
+    delivery_account: ComponentAddress,
+    fills_delivered: u64,
+    keeper_fee: Decimal,
+    lsus_delivered: Vec<(Decimal, ResourceAddress)>,
+    stake_claim_nfts_delivered: Vec<NonFungibleGlobalId>,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+struct LsuFillsUnstakedEvent {
+    receipt_id: NonFungibleLocalId,
+    fills_unstaked: u64,
+    redemption_ready_epoch: u64,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+struct ClaimableXrdCreditedEvent {
+    receipt_id: NonFungibleLocalId,
+    xrd_amount: Decimal,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+struct ClaimableXrdWithdrawnEvent {
+    receipt_id: NonFungibleLocalId,
+    xrd_amount: Decimal,
+}
+
 #[derive(ScryptoSbor, ScryptoEvent)]
 struct LiquidityCycledEvent {
     receipt_id: NonFungibleLocalId,
     xrd_amount_cycled: Decimal,
     automation_fee: Decimal,
+    fills_remaining: u64,
 }
 
 #[derive(ScryptoSbor, ScryptoEvent)]
@@ -127,21 +327,72 @@ struct AutomationFeeUpdatedEvent {
     automation_fee: Decimal,
 }
 
+#[derive(ScryptoSbor, ScryptoEvent)]
+struct DiscountOverrideUpdatedEvent {
+    receipt_id: NonFungibleLocalId,
+    validator: ComponentAddress,
+    discount: Option<Decimal>,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+struct SellOrderCreatedEvent {
+    sell_order_id: NonFungibleLocalId,
+    lsu_resource: ResourceAddress,
+    lsu_amount: Decimal,
+    max_discount: Decimal,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+struct SellOrderFilledEvent {
+    sell_order_id: NonFungibleLocalId,
+    lsu_amount: Decimal,
+    xrd_amount: Decimal,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+struct SellOrderCancelledEvent {
+    sell_order_id: NonFungibleLocalId,
+    lsu_returned: Decimal,
+    xrd_returned: Decimal,
+}
+
 pub struct BuyListKey;
 
 impl BuyListKey {
-    pub fn new(discount_basis_points: u16, auto_unstake: bool, position: u64, receipt_id: u32) -> u128 {
-        // Pack: discount (16 bits) | position (64 bits) | auto_unstake (16 bits) | receipt_id (32 bits) = 128 bits
-        let auto_unstake_flag = if auto_unstake { 1u16 } else { 0u16 };
-        
-        ((discount_basis_points as u128) << 112) |  // Top 16 bits
-        ((position as u128) << 48) |                // Next 64 bits
-        ((auto_unstake_flag as u128) << 32) |       // Next 16 bits
-        (receipt_id as u128)                        // Bottom 32 bits
+    pub fn new(discount_basis_points: u16, auto_unstake: bool, position: u64, receipt_id: u64) -> u128 {
+        buy_list_key::encode(discount_basis_points, auto_unstake, position, receipt_id)
+    }
+
+    pub fn decode(key: u128) -> BuyListKeyParts {
+        buy_list_key::decode(key)
     }
     
     pub fn extract_auto_unstake(key: u128) -> bool {
-        ((key >> 32) & 0xFFFF) == 1
+        buy_list_key::decode(key).auto_unstake
+    }
+
+    // Discount tiers are 2.5 basis points apart and keys store the floored value, so round back up to the tier
+    pub fn discount(key: u128) -> Decimal {
+        let tier_index = buy_list_key::tier_index(buy_list_key::decode(key).discount_basis_points);
+        dec!(0.00025) * Decimal::from(tier_index as u64)
+    }
+}
+
+pub struct SellListKey;
+
+impl SellListKey {
+    pub fn new(max_discount_basis_points: u16, position: u64, sell_order_id: u64) -> u128 {
+        // Pack: inverted discount (16 bits) | position (64 bits) | sell_order_id (48 bits) = 128 bits
+        // The discount is inverted so sellers accepting the deepest discount are matched first
+        let inverted_discount = u16::MAX - max_discount_basis_points;
+
+        ((inverted_discount as u128) << 112) |      // Top 16 bits
+        ((position as u128) << 48) |                // Next 64 bits
+        ((sell_order_id as u128) & 0xFFFF_FFFF_FFFF) // Bottom 48 bits
+    }
+
+    pub fn extract_max_discount(key: u128) -> u16 {
+        u16::MAX - ((key >> 112) as u16)
     }
 }
 
@@ -149,11 +400,38 @@ pub struct OrderFillKey;
 
 impl OrderFillKey {
     pub fn new(receipt_id: u64, fill_number: u64) -> u128 {
-        // Pack: receipt_id (64 bits) | fill_number (64 bits) = 128 bits
-        ((receipt_id as u128) << 64) | (fill_number as u128)
+        order_fill_key::encode(receipt_id, fill_number)
     }
 }
 
+#[derive(ScryptoSbor, PartialEq, Debug, Clone, Copy)]
+pub enum UnstakeExecutionMode {
+    PartialAllowed,
+    FillOrKill,
+    MinFillRatio(Decimal),
+}
+
+#[derive(ScryptoSbor, PartialEq, Debug, Clone, Copy)]
+pub enum FillFilter {
+    All,
+    Resource(ResourceAddress),
+    ClaimableNow,
+    LSUOnly,
+}
+
+#[derive(ScryptoSbor, PartialEq, Debug, Clone, Copy)]
+pub enum DustPolicy {
+    SweepIntoFill,
+    MoveToClaimable,
+}
+
+#[derive(ScryptoSbor, PartialEq, Debug, Clone, Copy)]
+pub enum OrderKeyPolicy {
+    Unrestricted,
+    Strict(u64),
+    Fallback(u64),
+}
+
 #[derive(ScryptoSbor, PartialEq, Debug, Clone)]
 pub enum UnstakeNFTOrLSU {
     UnstakeNFT(UnstakeNFTData),    
@@ -173,19 +451,32 @@ pub struct LSUData {
 }
 
 #[blueprint]
-#[types(Decimal, ResourceAddress, LiquidityReceipt, LiquidityData, NonFungibleLocalId, NonFungibleGlobalId, ComponentAddress, i64, u64, Vault)]
+#[types(Decimal, ResourceAddress, LiquidityReceipt, LiquidityData, DiscountOverrideKey, SellOrderReceipt, SellOrderData, NonFungibleLocalId, NonFungibleGlobalId, ComponentAddress, i64, u64, Vault)]
 #[events(
     LiquifyUnstakeEvent,
     OrderFillEvent, 
+    ClaimNftFillEvent,
+    LiquifyClaimNftEvent,
     LiquidityAddedEvent,
     LiquidityIncreasedEvent,
+    LadderCreatedEvent,
     LiquidityRemovedEvent,
     CollectFillsEvent,
+    FillsClaimedEvent,
+    DeliveryAccountUpdatedEvent,
+    FillsDeliveredEvent,
+    LsuFillsUnstakedEvent,
+    ClaimableXrdCreditedEvent,
+    ClaimableXrdWithdrawnEvent,
+    PositionClosedEvent,
     LiquidityCycledEvent,
     AutoRefillStatusUpdatedEvent,
     RefillThresholdUpdatedEvent,
     AutomationFeeUpdatedEvent,
-
+    DiscountOverrideUpdatedEvent,
+    SellOrderCreatedEvent,
+    SellOrderFilledEvent,
+    SellOrderCancelledEvent,
 )]
 mod liquify_module {
     enable_method_auth! {
@@ -195,22 +486,43 @@ mod liquify_module {
         methods {
             
             add_liquidity => PUBLIC;
+            add_liquidity_annualized => PUBLIC;
+            add_liquidity_ladder => PUBLIC;
             increase_liquidity => PUBLIC;
+            increase_liquidity_keep_position => PUBLIC;
             remove_liquidity => PUBLIC;
             liquify_unstake => PUBLIC;
             liquify_unstake_off_ledger => PUBLIC;
+            liquify_unstake_with_mode => PUBLIC;
+            liquify_unstake_off_ledger_with_mode => PUBLIC;
+            liquify_claim_nft => PUBLIC;
             collect_fills => PUBLIC;
+            collect_fills_filtered => PUBLIC;
+            collect_fills_as_xrd => PUBLIC;
+            set_delivery_account => PUBLIC;
+            deliver_fills => PUBLIC;
+            withdraw_claimable => PUBLIC;
+            deposit_claimable => PUBLIC;
+            close_position => PUBLIC;
             update_auto_refill_status => PUBLIC;
             update_refill_threshold => PUBLIC;
             update_automation_fee => PUBLIC;
+            set_discount_override => PUBLIC;
             cycle_liquidity => PUBLIC;
             calculate_claimable_xrd_and_ordered_list => PUBLIC;
+            create_sell_order => PUBLIC;
+            cancel_sell_order => PUBLIC;
+            collect_sell_order_proceeds => PUBLIC;
 
             get_claimable_xrd => PUBLIC;
             get_raw_buy_list_range => PUBLIC;
+            audit_accounting => PUBLIC;
+            decode_buy_list_key => PUBLIC;
             get_automation_ready_receipts => PUBLIC;
             get_receipt_detail => PUBLIC;
             get_active_liquidity_positions => PUBLIC;
+            get_sell_order => PUBLIC;
+            get_effective_discount_tiers => PUBLIC;
 
             set_component_status => restrict_to: [owner];
             set_platform_fee => restrict_to: [owner];
@@ -220,6 +532,15 @@ mod liquify_module {
             set_max_fills_per_cycle => restrict_to: [owner];
             set_unstake_value_range => restrict_to: [owner];
             set_small_order_threshold => restrict_to: [owner];
+            set_max_sell_order_matches => restrict_to: [owner];
+            set_unbonding_epochs => restrict_to: [owner];
+            set_claim_nft_time_adjustment => restrict_to: [owner];
+            set_delivery_fee => restrict_to: [owner];
+            set_dust_threshold => restrict_to: [owner];
+            set_dust_policy => restrict_to: [owner];
+            set_order_key_policy => restrict_to: [owner];
+            set_self_match_prevention => restrict_to: [owner];
+            set_tier_matching_policy => restrict_to: [owner];
             collect_platform_fees => restrict_to: [owner];
         }
     }
@@ -233,6 +554,7 @@ mod liquify_module {
         order_fill_tree: AvlTree<u128, UnstakeNFTOrLSU>,
         component_vaults: KeyValueStore<ResourceAddress, Vault>,
         liquidity_data: KeyValueStore<NonFungibleGlobalId, LiquidityData>,
+        discount_overrides: KeyValueStore<DiscountOverrideKey, Decimal>,
         total_xrd_volume: Decimal,
         total_xrd_locked: Decimal,
         component_status: bool,
@@ -241,6 +563,7 @@ mod liquify_module {
         liquidity_index_auto_unstake_false: Vec<Decimal>,  // 201 elements
         liquidity_index_auto_unstake_true: Vec<Decimal>,   // 201 elements
         discounts: Vec<Decimal>,
+        tier_matching_policies: Vec<MatchingPolicy>,        // 201 elements
         platform_fee: Decimal,
         fee_vault: Vault,
         minimum_liquidity: Decimal,
@@ -251,8 +574,25 @@ mod liquify_module {
         automation_fee: Decimal,
         automated_liquidity: KeyValueStore<u64, NonFungibleGlobalId>,
         automated_liquidity_index: u64,
+        automated_liquidity_slots: KeyValueStore<NonFungibleGlobalId, u64>,
         max_fills_per_cycle: u64,
         small_order_threshold: Decimal,
+        sell_order_receipt: NonFungibleResourceManager,
+        sell_order_receipt_counter: u64,
+        sell_list: AvlTree<u128, NonFungibleGlobalId>,
+        sell_order_data: KeyValueStore<NonFungibleGlobalId, SellOrderData>,
+        sell_order_vaults: KeyValueStore<ResourceAddress, Vault>,
+        sell_order_xrd: Vault,
+        max_sell_order_matches: u64,
+        unbonding_epochs: u64,
+        claim_nft_time_adjustment: bool,
+        delivery_fee: Decimal,
+        claimable_xrd: Vault,
+        total_xrd_claimable: Decimal,
+        dust_threshold: Decimal,
+        dust_policy: DustPolicy,
+        order_key_policy: OrderKeyPolicy,
+        self_match_prevention: bool,
     }
 
     impl Liquify {
@@ -314,10 +654,31 @@ mod liquify_module {
                     non_fungible_data_updater_updater => rule!(deny_all);
                 })
                 .create_with_no_initial_supply();
+
+            let sell_order_receipt = ResourceBuilder::new_integer_non_fungible::<SellOrderReceipt>(OwnerRole::Fixed(
+                rule!(require_any_of(vec![global_caller(component_address), ResourceOrNonFungible::Resource(liquify_owner_badge.resource_address())]))))
+                .metadata(metadata!(
+                    init {
+                        "name" => "Liquify Sell Order Receipt".to_owned(), updatable;
+                        "description" => "Receipt for a resting LSU sell order on the Liquify platform".to_string(), updatable;
+                        "icon_url" => Url::of("https://www.liquifyxrd.app/assets/logo_white_on_black.jpg"), updatable;
+                        "tags" => tags.clone(), updatable;
+                    }
+                ))
+                .mint_roles(mint_roles!{
+                    minter => rule!(require(global_caller(component_address)));
+                    minter_updater => rule!(deny_all); 
+                })
+                .burn_roles(burn_roles! {
+                    burner => rule!(require(global_caller(component_address)));
+                    burner_updater => rule!(deny_all);
+                })
+                .create_with_no_initial_supply();
             
             let mut liquidity_index_auto_unstake_false: Vec<Decimal> = Vec::new();
             let mut liquidity_index_auto_unstake_true: Vec<Decimal> = Vec::new();
             let mut discounts: Vec<Decimal> = Vec::new();
+            let mut tier_matching_policies: Vec<MatchingPolicy> = Vec::new();
             let step: Decimal = dec!(0.00025);
 
             for i in 0..=200 {
@@ -325,6 +686,7 @@ mod liquify_module {
                 liquidity_index_auto_unstake_false.push(Decimal::ZERO);
                 liquidity_index_auto_unstake_true.push(Decimal::ZERO);
                 discounts.push(discount);
+                tier_matching_policies.push(MatchingPolicy::Fifo);
             }
 
             let liquify_component = Liquify {
@@ -337,9 +699,11 @@ mod liquify_module {
                 order_fill_tree: AvlTree::new(),
                 component_vaults: KeyValueStore::new_with_registered_type(),
                 liquidity_data: KeyValueStore::new_with_registered_type(),
+                discount_overrides: KeyValueStore::new_with_registered_type(),
                 liquidity_index_auto_unstake_false,
                 liquidity_index_auto_unstake_true,
                 discounts,
+                tier_matching_policies,
                 total_xrd_volume: Decimal::ZERO,
                 total_xrd_locked: Decimal::ZERO,
                 component_status: false, 
@@ -354,8 +718,25 @@ mod liquify_module {
                 automation_fee: dec!(5),
                 automated_liquidity: KeyValueStore::new_with_registered_type(),
                 automated_liquidity_index: 1,
+                automated_liquidity_slots: KeyValueStore::new_with_registered_type(),
                 max_fills_per_cycle: 50,
                 small_order_threshold: dec!(1000), 
+                sell_order_receipt,
+                sell_order_receipt_counter: 1,
+                sell_list: AvlTree::new(),
+                sell_order_data: KeyValueStore::new_with_registered_type(),
+                sell_order_vaults: KeyValueStore::new_with_registered_type(),
+                sell_order_xrd: Vault::new(XRD),
+                max_sell_order_matches: 10,
+                unbonding_epochs: 2016,
+                claim_nft_time_adjustment: true,
+                delivery_fee: dec!(1),
+                claimable_xrd: Vault::new(XRD),
+                total_xrd_claimable: dec!(0),
+                dust_threshold: dec!("0.000001"),
+                dust_policy: DustPolicy::MoveToClaimable,
+                order_key_policy: OrderKeyPolicy::Unrestricted,
+                self_match_prevention: true,
             }
             .instantiate()
             .prepare_to_globalize(
@@ -378,14 +759,28 @@ mod liquify_module {
             .enable_component_royalties(component_royalties! {
                 init {
                     add_liquidity => Free, updatable;
+                    add_liquidity_annualized => Free, updatable;
+                    add_liquidity_ladder => Free, updatable;
                     increase_liquidity => Free, updatable;
+                    increase_liquidity_keep_position => Free, updatable;
                     remove_liquidity => Free, updatable;
                     liquify_unstake => Free, updatable;
                     liquify_unstake_off_ledger => Free, updatable;
+                    liquify_unstake_with_mode => Free, updatable;
+                    liquify_unstake_off_ledger_with_mode => Free, updatable;
+                    liquify_claim_nft => Free, updatable;
                     collect_fills => Free, updatable;
+                    collect_fills_filtered => Free, updatable;
+                    collect_fills_as_xrd => Free, updatable;
+                    set_delivery_account => Free, updatable;
+                    deliver_fills => Free, updatable;
+                    withdraw_claimable => Free, updatable;
+                    deposit_claimable => Free, updatable;
+                    close_position => Free, updatable;
                     update_auto_refill_status => Free, updatable;
                     update_refill_threshold => Free, updatable;
                     update_automation_fee => Free, updatable;
+                    set_discount_override => Free, updatable;
                     cycle_liquidity => Free, updatable;
                     calculate_claimable_xrd_and_ordered_list => Free, updatable;
                     get_claimable_xrd => Free, updatable;
@@ -396,13 +791,28 @@ mod liquify_module {
                     set_minimum_liquidity => Free, updatable;
                     set_receipt_image_url => Free, updatable;
                     get_raw_buy_list_range => Free, updatable;
+                    audit_accounting => Free, updatable;
+                    decode_buy_list_key => Free, updatable;
                     get_active_liquidity_positions => Free, updatable;
                     set_minimum_refill_threshold => Free, updatable;
                     set_unstake_value_range => Free, updatable;
                     set_small_order_threshold => Free, updatable;
                     get_automation_ready_receipts => Free, updatable;
                     get_receipt_detail => Free, updatable;
-
+                    create_sell_order => Free, updatable;
+                    cancel_sell_order => Free, updatable;
+                    collect_sell_order_proceeds => Free, updatable;
+                    get_sell_order => Free, updatable;
+                    get_effective_discount_tiers => Free, updatable;
+                    set_max_sell_order_matches => Free, updatable;
+                    set_unbonding_epochs => Free, updatable;
+                    set_claim_nft_time_adjustment => Free, updatable;
+                    set_delivery_fee => Free, updatable;
+                    set_dust_threshold => Free, updatable;
+                    set_dust_policy => Free, updatable;
+                    set_order_key_policy => Free, updatable;
+                    set_self_match_prevention => Free, updatable;
+                    set_tier_matching_policy => Free, updatable;
                 }
             })
             .globalize();
@@ -439,14 +849,154 @@ mod liquify_module {
             automation_fee: Decimal
             ) -> NonFungibleBucket {
             
+            let (liquidity_receipt, buy_list_key) = self.create_position(xrd_bucket, discount, None, auto_unstake, auto_refill, refill_threshold, automation_fee);
+
+            // Fill any resting sell orders that accept this discount
+            self.match_sell_orders(buy_list_key, discount);
+
+            liquidity_receipt
+        }
+
+        /// Allows user to deposit XRD liquidity priced as an annualized rate.
+        /// 
+        /// This method works like `add_liquidity` but the liquidity provider bids an annualized rate of return
+        /// instead of a flat discount. The rate reflects the time value of the XRD that stays locked until the
+        /// unstake claim matures. When LSUs are matched, `process_unstake` converts the rate into an effective
+        /// discount over `unbonding_epochs`. The position is placed in the buy list at the supported discount
+        /// tier at or just below its effective discount, so its effective discount may be up to one tier step
+        /// deeper than the tier it is listed at.
+        /// 
+        /// # Arguments
+        /// * `xrd_bucket`: A `Bucket` containing XRD to be deposited as liquidity
+        /// * `annualized_rate`: A `Decimal` representing the yearly rate of return bid for locked XRD (e.g. 0.1 for 10%)
+        /// * `auto_unstake`: A `bool` indicating whether the user wants to automatically unstake any LSUs that are collected
+        /// * `auto_refill`: A `bool` indicating whether the user wants to automatically refill liquidity from collected fills
+        /// * `refill_threshold`: A `Decimal` representing the minimum XRD amount needed to trigger auto refill
+        ///
+        /// # Returns
+        /// * A `NonFungibleBucket` containing the new liquidity receipt NFT that has been minted to track the liquidity
+        pub fn add_liquidity_annualized(
+            &mut self, 
+            xrd_bucket: Bucket, 
+            annualized_rate: Decimal, 
+            auto_unstake: bool, 
+            auto_refill: bool, 
+            refill_threshold: Decimal,
+            automation_fee: Decimal
+            ) -> NonFungibleBucket {
+
+            assert!(annualized_rate > dec!(0), "Annualized rate must be positive");
+
+            let effective_discount = annualized_rate * Decimal::from(self.unbonding_epochs) / Decimal::from(EPOCHS_PER_YEAR);
+            let discount = (effective_discount / dec!(0.00025)).checked_floor().unwrap() * dec!(0.00025);
+            assert!(self.discounts.contains(&discount), "Annualized rate converts to an unsupported discount of {}", effective_discount);
+
+            let (liquidity_receipt, buy_list_key) = self.create_position(xrd_bucket, discount, Some(annualized_rate), auto_unstake, auto_refill, refill_threshold, automation_fee);
+
+            // Fill any resting sell orders that accept this discount
+            self.match_sell_orders(buy_list_key, discount);
+
+            liquidity_receipt
+        }
+
+        /// Allows user to deposit XRD liquidity spread across several discounts under one receipt.
+        /// 
+        /// The XRD is split between the rungs of the ladder in proportion to their weights. The first rung
+        /// becomes the receipt's position and every other rung is queued as a sub-position at its own discount,
+        /// so each rung keeps its place in its tier. Fills from all rungs are stored under the receipt like any
+        /// other fill. XRD added later by increasing or cycling the position is split across the rungs by the
+        /// original weights.
+        /// 
+        /// # Arguments
+        /// * `xrd_bucket`: A `Bucket` containing XRD to be deposited as liquidity
+        /// * `rungs`: A `Vec<(Decimal, Decimal)>` of distinct supported discounts and their weights
+        /// * `auto_unstake`: A `bool` indicating whether the user wants to automatically unstake any LSUs that are collected
+        /// * `auto_refill`: A `bool` indicating whether the user wants to automatically refill liquidity from collected fills
+        /// * `refill_threshold`: A `Decimal` representing the minimum XRD amount needed to trigger auto refill
+        /// * `automation_fee`: A `Decimal` fee paid to the automation caller on each cycle
+        ///
+        /// # Returns
+        /// * A `NonFungibleBucket` containing the new liquidity receipt NFT backed by every rung of the ladder
+        pub fn add_liquidity_ladder(
+            &mut self, 
+            mut xrd_bucket: Bucket, 
+            rungs: Vec<(Decimal, Decimal)>, 
+            auto_unstake: bool, 
+            auto_refill: bool, 
+            refill_threshold: Decimal,
+            automation_fee: Decimal
+            ) -> NonFungibleBucket {
+
+            assert!(xrd_bucket.resource_address() == XRD, "Bucket must contain XRD");
+            assert!(!rungs.is_empty() && rungs.len() <= MAX_LADDER_RUNGS, "A ladder must have between 1 and {} rungs", MAX_LADDER_RUNGS);
+            for (index, (discount, weight)) in rungs.iter().enumerate() {
+                assert!(self.discounts.contains(discount), "This discount % is not supported");
+                assert!(*weight > dec!(0), "Ladder weights must be positive");
+                assert!(rungs[..index].iter().all(|(other_discount, _)| other_discount != discount), "Ladder discounts must be distinct");
+            }
+
+            let xrd_amount = xrd_bucket.amount();
+            let rung_amounts = split_by_weight(xrd_amount, &rungs);
+            for (_, amount) in &rung_amounts {
+                assert!(*amount >= self.minimum_liquidity, "Each ladder rung must meet the minimum liquidity requirement");
+            }
+
+            // The first rung becomes the receipt's position
+            let first_rung_bucket = xrd_bucket.take(rung_amounts[0].1);
+            let (liquidity_receipt, first_rung_key) = self.create_position(first_rung_bucket, rung_amounts[0].0, None, auto_unstake, auto_refill, refill_threshold, automation_fee);
+
+            let local_id = liquidity_receipt.non_fungible_local_id();
+            let nft_data: LiquidityReceipt = self.liquidity_receipt.get_non_fungible_data(&local_id);
+            let global_id = NonFungibleGlobalId::new(self.liquidity_receipt.address(), local_id.clone());
+
+            let mut kvs_data = self.liquidity_data.get_mut(&global_id).unwrap();
+            kvs_data.ladder_weights = rungs.clone();
+            kvs_data.xrd_liquidity_available += xrd_bucket.amount();
+            drop(kvs_data);
+
+            // Every other rung is queued as a sub-position at its own discount
+            let mut rung_keys: Vec<(u128, Decimal)> = vec![(first_rung_key, rung_amounts[0].0)];
+            for (discount, amount) in rung_amounts.into_iter().skip(1) {
+                let buy_list_key = self.queue_buy_list_key(&global_id, &nft_data, discount, amount, true);
+                rung_keys.push((buy_list_key, discount));
+            }
+
+            self.total_xrd_locked += xrd_bucket.amount();
+            self.xrd_liquidity.put(xrd_bucket);
+
+            Runtime::emit_event(LadderCreatedEvent {
+                receipt_id: local_id,
+                xrd_amount,
+                rungs,
+            });
+
+            // Fill any resting sell orders once every rung is queued, so the first rung can't be used up
+            // before the others have a position to queue behind
+            for (buy_list_key, discount) in rung_keys {
+                self.match_sell_orders(buy_list_key, discount);
+            }
+
+            liquidity_receipt
+        }
+
+        fn create_position(
+            &mut self, 
+            xrd_bucket: Bucket, 
+            discount: Decimal, 
+            annualized_rate: Option<Decimal>,
+            auto_unstake: bool, 
+            auto_refill: bool, 
+            refill_threshold: Decimal,
+            automation_fee: Decimal
+            ) -> (NonFungibleBucket, u128) {
+            
             assert!(self.component_status == true, "Liquify is not accepting new liquidity at this time.");
             assert!(xrd_bucket.resource_address() == XRD, "Bucket must contain XRD");
             assert!(xrd_bucket.amount() >= self.minimum_liquidity, "This amount is below the minimum liquidity requirement XRD");
             assert!(self.discounts.contains(&discount), "This discount % is not supported");
             
-            // ADDED: Validate auto_refill requires auto_unstake
+            // Positions without auto_unstake are refilled by redeeming their LSU fills in cycle_liquidity
             if auto_refill {
-                assert!(auto_unstake, "Auto refill can only be enabled when auto unstake is enabled");
                 assert!(refill_threshold >= self.minimum_refill_threshold, "Refill threshold is below required minimum");
             }
 
@@ -460,8 +1010,7 @@ mod liquify_module {
             };
 
             // Create buy list key with new structure - now includes auto_unstake
-            let receipt_id_u32 = self.liquidity_receipt_counter as u32;
-            let buy_list_key = BuyListKey::new(discount_basis_points, auto_unstake, self.avl_position_counter, receipt_id_u32);
+            let buy_list_key = BuyListKey::new(discount_basis_points, auto_unstake, self.avl_position_counter, self.liquidity_receipt_counter);
             self.avl_position_counter += 1;
             
             let id = NonFungibleLocalId::Integer(IntegerNonFungibleLocalId::new(self.liquidity_receipt_counter));
@@ -474,6 +1023,7 @@ mod liquify_module {
                 auto_refill,
                 refill_threshold,
                 automation_fee,
+                annualized_rate,
             };
 
             let new_liquidity_receipt: NonFungibleBucket = self.liquidity_receipt.mint_non_fungible(&id, liquidity_receipt_data);
@@ -485,13 +1035,22 @@ mod liquify_module {
                 xrd_liquidity_available: xrd_bucket.amount(),
                 fills_to_collect: 0,
                 last_added_epoch: Runtime::current_epoch().number() as u32,  // Keep for info only
+                discount_override_validators: Vec::new(),
+                delivery_account: None,
+                lsu_fills_unbonding: 0,
+                redemption_ready_epoch: 0,
+                mid_cycle: false,
+                cycle_fee_charged: dec!(0),
+                claimable_xrd_balance: dec!(0),
+                primary_buy_list_key: Some(buy_list_key),
+                sub_positions: Vec::new(),
+                ladder_weights: Vec::new(),
             };
             self.liquidity_data.insert(global_id.clone(), liquidity_data);
             
             // Add to automated tracking if auto_refill is enabled
             if auto_refill {
-                self.automated_liquidity.insert(self.automated_liquidity_index, global_id.clone());
-                self.automated_liquidity_index += 1;
+                self.register_automation(&global_id);
             }
             
             self.liquidity_receipt_counter += 1;
@@ -519,11 +1078,13 @@ mod liquify_module {
                 auto_unstake,
                 auto_refill,
                 refill_threshold,
+                annualized_rate,
             });
 
             self.xrd_liquidity.put(xrd_bucket);
 
-            new_liquidity_receipt
+            // Resting sell orders are matched by the caller once the whole position is queued
+            (new_liquidity_receipt, buy_list_key)
         }
 
         /// Increases existing liquidity position with additional XRD.
@@ -548,64 +1109,18 @@ mod liquify_module {
             let local_id = receipt_bucket.as_non_fungible().non_fungible_local_id();
             let nft_data: LiquidityReceipt = self.liquidity_receipt.get_non_fungible_data(&local_id);
             let global_id = NonFungibleGlobalId::new(self.liquidity_receipt.address(), local_id.clone());
-            let mut kvs_data = self.liquidity_data.get_mut(&global_id).unwrap();
             
             // Check that current + new liquidity meets minimum requirement
             assert!(
-                kvs_data.xrd_liquidity_available + xrd_bucket.amount() >= self.minimum_liquidity, 
+                self.liquidity_data.get(&global_id).unwrap().xrd_liquidity_available + xrd_bucket.amount() >= self.minimum_liquidity, 
                 "Total liquidity after increase would be below the minimum liquidity requirement"
             );
             
             // Store the amount for the event before consuming the bucket
             let additional_xrd_amount = xrd_bucket.amount();
-            
-            // Get discount for the key
-            let discount_basis_points = match (nft_data.discount * dec!(10000)).checked_floor() {
-                Some(val) => match val.to_string().parse::<u32>() {
-                    Ok(points) => points as u16,  // Convert to u16
-                    Err(_) => panic!("Failed to parse discount basis points")
-                },
-                None => panic!("Failed to convert discount to basis points")
-            };
-
-            // Find and remove from old position
-            let mut key_to_remove = None;
-            self.buy_list.range_mut(0..u128::MAX).for_each(|(key, tree_global_id, _)| {
-                if tree_global_id == &global_id {
-                    key_to_remove = Some(*key);
-                    return scrypto_avltree::IterMutControl::Break;
-                }
-                scrypto_avltree::IterMutControl::Continue
-            });
-
-            if let Some(key) = key_to_remove {
-                self.buy_list.remove(&key);
-            }
-
-            // Update KVS data
-            kvs_data.xrd_liquidity_available += additional_xrd_amount;
-            let current_epoch = Runtime::current_epoch().number() as u32;
-            kvs_data.last_added_epoch = current_epoch;
-
-            // Create new key with updated position counter
-            let receipt_id_u32 = match local_id.clone() {
-                NonFungibleLocalId::Integer(i) => i.value() as u32,
-                _ => panic!("Invalid NFT ID type")
-            };
-            let new_buy_list_key = BuyListKey::new(discount_basis_points, nft_data.auto_unstake, self.avl_position_counter, receipt_id_u32);
-            self.avl_position_counter += 1;
-
-            // Reinsert at new position
-            self.buy_list.insert(new_buy_list_key, global_id.clone());
-            
-            // Update liquidity index
-            let index_usize = (nft_data.discount / dec!(0.00025)).checked_floor().unwrap().to_string().parse::<usize>().unwrap();
 
-            if nft_data.auto_unstake {
-                self.liquidity_index_auto_unstake_true[index_usize] += additional_xrd_amount;
-            } else {
-                self.liquidity_index_auto_unstake_false[index_usize] += additional_xrd_amount;
-            }
+            // Move to the back of the queue, splitting only the new XRD across a ladder's rungs by their weights
+            let new_buy_list_keys = self.requeue_liquidity(&global_id, &nft_data, additional_xrd_amount);
             
             self.total_xrd_locked += additional_xrd_amount;
             self.xrd_liquidity.put(xrd_bucket);
@@ -615,64 +1130,84 @@ mod liquify_module {
                 receipt_id: local_id,
                 additional_xrd: additional_xrd_amount,
             });
+
+            // Fill any resting sell orders that accept these discounts
+            for (new_buy_list_key, discount) in new_buy_list_keys {
+                self.match_sell_orders(new_buy_list_key, discount);
+            }
             
             receipt_bucket
         }
 
-        pub fn update_auto_refill_status(&mut self, receipt_bucket: Bucket, auto_refill: bool) -> Bucket {
+        /// Increases an existing liquidity position without giving up its place in the queue.
+        /// 
+        /// This method works like `increase_liquidity`, except the liquidity already in the position keeps its
+        /// buy list key. The additional XRD is queued as a sub-position under a new key at the back of the
+        /// position's discount tier, so it goes behind everyone else while the original amount keeps its
+        /// priority. Both are held by the same receipt and listed in `get_receipt_detail`. If the position has
+        /// no available liquidity left, the XRD simply becomes its new position. Ladders queue a sub-position
+        /// on each rung, splitting the XRD by the ladder's weights. Once a receipt holds `MAX_SUB_POSITIONS`
+        /// sub-positions, the newest one at the same discount is moved to the back together with the new XRD
+        /// instead of adding another key.
+        /// 
+        /// # Arguments
+        /// * `receipt_bucket`: A `Bucket` containing exactly one liquidity receipt NFT
+        /// * `xrd_bucket`: A `Bucket` containing XRD to be added to the existing position
+        ///
+        /// # Returns
+        /// * A `Bucket` containing the same liquidity receipt NFT that was passed in
+        pub fn increase_liquidity_keep_position(&mut self, receipt_bucket: Bucket, xrd_bucket: Bucket) -> Bucket {
             assert!(receipt_bucket.resource_address() == self.liquidity_receipt.address(), "Bucket must contain Liquify liquidity receipt");
             assert!(receipt_bucket.amount() == dec!(1), "Must provide exactly one liquidity receipt");
+            assert!(xrd_bucket.resource_address() == XRD, "Bucket must contain XRD");
             
             let local_id = receipt_bucket.as_non_fungible().non_fungible_local_id();
             let nft_data: LiquidityReceipt = self.liquidity_receipt.get_non_fungible_data(&local_id);
-            
-            if auto_refill {
-                assert!(nft_data.auto_unstake, "Cannot enable auto refill on a receipt that has auto unstake disabled");
-                assert!(nft_data.refill_threshold >= self.minimum_refill_threshold, "Current refill threshold is below required minimum");
-            }
-            
             let global_id = NonFungibleGlobalId::new(self.liquidity_receipt.address(), local_id.clone());
-            
-            // Handle automation tracking changes
-            if auto_refill && !nft_data.auto_refill {
-                // Enabling automation
-                self.automated_liquidity.insert(self.automated_liquidity_index, global_id);
-                self.automated_liquidity_index += 1;
-            } else if !auto_refill && nft_data.auto_refill {
-                // Disabling automation
-                let mut found_at_index = None;
-                
-                // Find the index to remove
-                for i in 1..self.automated_liquidity_index {
-                    if let Some(stored_global_id) = self.automated_liquidity.get(&i) {
-                        if *stored_global_id == global_id {
-                            found_at_index = Some(i);
-                            break;
-                        }
-                    }
-                }
-                
-                if let Some(index_to_remove) = found_at_index {
-                    let last_index = self.automated_liquidity_index - 1;
-                    
-                    if index_to_remove == last_index {
-                        // Removing the last entry - simple case
-                        self.automated_liquidity.remove(&index_to_remove);
-                    } else {
-                        // Not the last entry - need to move last to fill gap
-                        // Get the last entry first
-                        let last_entry = self.automated_liquidity.get(&last_index)
-                            .expect("Last entry should exist")
-                            .clone();
-                        
-                        // Now perform both operations
-                        self.automated_liquidity.remove(&index_to_remove);
-                        self.automated_liquidity.remove(&last_index);
-                        self.automated_liquidity.insert(index_to_remove, last_entry);
-                    }
-                    
-                    self.automated_liquidity_index -= 1;
-                }
+            let additional_xrd_amount = xrd_bucket.amount();
+
+            assert!(
+                self.liquidity_data.get(&global_id).unwrap().xrd_liquidity_available + additional_xrd_amount >= self.minimum_liquidity, 
+                "Total liquidity after increase would be below the minimum liquidity requirement"
+            );
+
+            // Queue behind the original position, or take its place if it has been used up
+            let new_buy_list_keys = self.queue_additional_liquidity(&global_id, &nft_data, additional_xrd_amount);
+
+            self.total_xrd_locked += additional_xrd_amount;
+            self.xrd_liquidity.put(xrd_bucket);
+
+            Runtime::emit_event(LiquidityIncreasedEvent {
+                receipt_id: local_id,
+                additional_xrd: additional_xrd_amount,
+            });
+
+            // Fill any resting sell orders that accept these discounts
+            for (new_buy_list_key, discount) in new_buy_list_keys {
+                self.match_sell_orders(new_buy_list_key, discount);
+            }
+
+            receipt_bucket
+        }
+
+        pub fn update_auto_refill_status(&mut self, receipt_bucket: Bucket, auto_refill: bool) -> Bucket {
+            assert!(receipt_bucket.resource_address() == self.liquidity_receipt.address(), "Bucket must contain Liquify liquidity receipt");
+            assert!(receipt_bucket.amount() == dec!(1), "Must provide exactly one liquidity receipt");
+            
+            let local_id = receipt_bucket.as_non_fungible().non_fungible_local_id();
+            let nft_data: LiquidityReceipt = self.liquidity_receipt.get_non_fungible_data(&local_id);
+            
+            if auto_refill {
+                assert!(nft_data.refill_threshold >= self.minimum_refill_threshold, "Current refill threshold is below required minimum");
+            }
+            
+            let global_id = NonFungibleGlobalId::new(self.liquidity_receipt.address(), local_id.clone());
+            
+            // Handle automation tracking changes
+            if auto_refill && !nft_data.auto_refill {
+                self.register_automation(&global_id);
+            } else if !auto_refill && nft_data.auto_refill {
+                self.deregister_automation(&global_id);
             }
             
             // Update NFT data
@@ -734,18 +1269,83 @@ mod liquify_module {
             receipt_bucket
         }
 
+        /// Sets or clears a validator-specific discount for a liquidity receipt.
+        /// 
+        /// This method lets a liquidity provider bid a different discount for LSUs of one validator while
+        /// keeping the receipt's base discount for every other validator. Overrides are stored in the
+        /// `discount_overrides` store and the validators that have one are listed on the receipt's
+        /// `LiquidityData`, so `process_unstake` only looks them up when needed. The position stays in the
+        /// buy list at its base discount: sellers reach it in base discount order, and the liquidity index
+        /// keeps counting it at the base tier. An override below the base discount therefore does not move
+        /// the position ahead of other liquidity, and an override above it can leave a seller of that
+        /// validator's LSU with a deeper discount than positions further down the book would have given.
+        /// 
+        /// # Arguments
+        /// * `receipt_bucket`: A `Bucket` containing exactly one liquidity receipt NFT
+        /// * `validator`: The `ComponentAddress` of the validator the override applies to
+        /// * `discount`: An `Option<Decimal>` with the discount to use, or `None` to remove the override
+        ///
+        /// # Returns
+        /// * A `Bucket` containing the same liquidity receipt NFT that was passed in
+        pub fn set_discount_override(&mut self, receipt_bucket: Bucket, validator: ComponentAddress, discount: Option<Decimal>) -> Bucket {
+            assert!(receipt_bucket.resource_address() == self.liquidity_receipt.address(), "Bucket must contain Liquify liquidity receipt");
+            assert!(receipt_bucket.amount() == dec!(1), "Must provide exactly one liquidity receipt");
+
+            let local_id = receipt_bucket.as_non_fungible().non_fungible_local_id();
+            let global_id = NonFungibleGlobalId::new(self.liquidity_receipt.address(), local_id.clone());
+            let override_key = DiscountOverrideKey {
+                receipt_id: global_id.clone(),
+                validator,
+            };
+            let mut kvs_data = self.liquidity_data.get_mut(&global_id).unwrap();
+
+            match discount {
+                Some(discount) => {
+                    assert!(self.discounts.contains(&discount), "This discount % is not supported");
+                    if !kvs_data.discount_override_validators.contains(&validator) {
+                        kvs_data.discount_override_validators.push(validator);
+                    }
+                    self.discount_overrides.insert(override_key, discount);
+                },
+                None => {
+                    kvs_data.discount_override_validators.retain(|address| *address != validator);
+                    self.discount_overrides.remove(&override_key);
+                }
+            }
+
+            Runtime::emit_event(DiscountOverrideUpdatedEvent {
+                receipt_id: local_id,
+                validator,
+                discount,
+            });
+
+            receipt_bucket
+        }
+
         /// Cycles liquidity for one or more receipts by claiming fills and re-adding as liquidity.
         /// 
-        /// This method processes liquidity receipts sequentially, cycling each one only if ALL its
-        /// fills can be processed within the remaining fill limit. Processing stops when the next receipt
-        /// would exceed the max_fills_to_process limit. Each receipt must have both auto_unstake and 
-        /// auto_refill enabled, and must meet its refill threshold. An automation fee is deducted for
-        /// each receipt and accumulated into a single bucket returned to the caller. The remaining XRD 
-        /// is added back to each position's available liquidity.
+        /// This method processes liquidity receipts sequentially until `max_fills_per_cycle` fills have been
+        /// processed. Each receipt must have auto_refill enabled and must meet its refill threshold. The
+        /// automation fee is deducted for each receipt and accumulated into a single bucket returned to the
+        /// caller, and the remaining XRD is queued as new liquidity at the back of the receipt's tiers, split by
+        /// the ladder's weights. Liquidity the receipt still has in the buy list keeps its keys, except that a
+        /// receipt already holding `MAX_SUB_POSITIONS` sub-positions has its newest sub-position at each rung moved
+        /// to the back with the refill. If the XRD claimed for a receipt doesn't cover its fee, the fee is waived
+        /// and all of it is added back. Resting
+        /// sell orders are matched against the refilled liquidity like they are on `add_liquidity`.
+        /// 
+        /// A receipt with more claimable fills than the remaining budget is cycled partially: the fills that
+        /// fit are claimed and refilled straight away, the rest stay in the tree and the receipt is marked
+        /// mid-cycle. A mid-cycle receipt is resumed by later calls without re-checking its refill threshold.
+        /// Each chunk is charged the share of the automation fee not yet charged in proportion to the fills
+        /// it claims, so a cycle split across several calls pays the fee once in total.
+        /// 
+        /// Receipts without auto_unstake are cycled in two phases. Once their LSU fills are worth the refill
+        /// threshold, the LSUs are unstaked in place and the resulting unstake NFTs replace the LSU fills.
+        /// A later call claims the matured NFTs and refills the position like any auto_unstake receipt.
         /// 
         /// # Arguments
         /// * `receipt_ids`: A `Vec<NonFungibleLocalId>` containing one or more receipt IDs to cycle
-        /// * `max_fills_to_process`: Maximum total fills to process across all receipts
         ///
         /// # Returns
         /// * A `FungibleBucket` containing the accumulated automation fees in XRD
@@ -765,32 +1365,47 @@ mod liquify_module {
                 let global_id = NonFungibleGlobalId::new(self.liquidity_receipt.address(), receipt_id.clone());
                 
                 // Skip if automation not enabled
-                if !nft_data.auto_refill || !nft_data.auto_unstake {
+                if !nft_data.auto_refill {
                     continue;
                 }
+
+                // Phase one for positions that keep LSUs: unstake the LSU fills so they can be refilled once matured
+                if !nft_data.auto_unstake {
+                    let (_, _, _, lsu_redemption_value) = self.calculate_claimable_xrd(&receipt_id);
+                    
+                    if lsu_redemption_value > dec!(0) && lsu_redemption_value >= nft_data.refill_threshold {
+                        let remaining_budget = self.max_fills_per_cycle - fills_processed_total;
+                        fills_processed_total += self.unstake_lsu_fills(&receipt_id, &global_id, remaining_budget);
+                        
+                        if fills_processed_total >= self.max_fills_per_cycle {
+                            break;
+                        }
+                    }
+                }
                 
                 // Get claimable XRD and ordered fill keys
                 let (total_claimable, total_fills, ordered_keys) = self.calculate_claimable_xrd_and_ordered_list(receipt_id.clone());
                 
-                // Skip if no fills
-                if total_fills == 0 {
+                let mid_cycle = self.liquidity_data.get(&global_id).unwrap().mid_cycle;
+                
+                // Skip if no fills, a partial cycle with nothing left to claim is finished
+                if total_fills == 0 || ordered_keys.is_empty() {
+                    if mid_cycle {
+                        let mut kvs_data = self.liquidity_data.get_mut(&global_id).unwrap();
+                        kvs_data.mid_cycle = false;
+                        kvs_data.cycle_fee_charged = dec!(0);
+                    }
                     continue;
                 }
                 
-                // Skip if doesn't meet threshold
-                if total_claimable < nft_data.refill_threshold {
+                // Skip if doesn't meet threshold, unless resuming a partial cycle
+                if !mid_cycle && total_claimable < nft_data.refill_threshold {
                     continue;
                 }
                 
                 let fills_available = ordered_keys.len() as u64;
                 let remaining_budget = self.max_fills_per_cycle - fills_processed_total;
                 
-                // If this is NOT the first receipt we're processing (index > 0 or fills_processed_total > 0)
-                // AND we can't complete all fills, skip this receipt
-                if fills_processed_total > 0 && fills_available > remaining_budget {
-                    continue;  // Don't start a receipt we can't finish
-                }
-                
                 // Process either all fills or up to our budget
                 let fills_to_process = if fills_available < remaining_budget {
                     fills_available
@@ -832,7 +1447,7 @@ mod liquify_module {
                             total_xrd.put(claimed_xrd);
                         },
                         UnstakeNFTOrLSU::LSU(_) => {
-                            panic!("Cannot cycle LSU fills - receipt must have auto_unstake enabled");
+                            panic!("LSU fills must be unstaked before they can be cycled");
                         }
                     }
                     
@@ -844,78 +1459,52 @@ mod liquify_module {
                 // Update KVS data
                 let mut kvs_data = self.liquidity_data.get_mut(&global_id).unwrap();
                 kvs_data.fills_to_collect = kvs_data.fills_to_collect.saturating_sub(fills_collected);
+                kvs_data.lsu_fills_unbonding = kvs_data.lsu_fills_unbonding.saturating_sub(fills_collected);
                 
-                // Skip if we didn't collect enough to cover fees
-                if total_xrd.amount() < self.automation_fee {
-                    // This shouldn't happen with proper ordering, but just in case
-                    self.xrd_liquidity.put(total_xrd.into());
-                    continue;
-                }
+                // Leave the remaining fills for the next call
+                let fills_remaining = fills_available - fills_collected;
+                kvs_data.mid_cycle = fills_remaining > 0;
                 
-                // Take automation fee
-                let fee_amount = nft_data.automation_fee;
+                // Take this chunk's share of the automation fee, waiving it when the claimed XRD can't cover
+                // it so the whole amount is still credited back to the position below
+                let fee_outstanding = std::cmp::max(nft_data.automation_fee - kvs_data.cycle_fee_charged, dec!(0));
+                let fee_share = fee_outstanding * Decimal::from(fills_collected) / Decimal::from(fills_available);
+                let fee_amount = if total_xrd.amount() >= fee_share {
+                    fee_share
+                } else {
+                    dec!(0)
+                };
+                // Only a fee actually charged counts towards this cycle, so a waived share is prorated over the
+                // chunks still to come
+                kvs_data.cycle_fee_charged = if fills_remaining > 0 {
+                    kvs_data.cycle_fee_charged + fee_amount
+                } else {
+                    dec!(0)
+                };
                 let automation_fee_bucket = total_xrd.take(fee_amount);
                 total_automation_fees.put(automation_fee_bucket);
+                drop(kvs_data);
                 
-                // Find and remove from current position in AVL tree
-                let mut key_to_remove = None;
-                for (key, tree_global_id, _) in self.buy_list.range(0..u128::MAX) {
-                    if tree_global_id == global_id {
-                        key_to_remove = Some(key);
-                        break;
-                    }
-                }
-                
-                if let Some(key) = key_to_remove {
-                    self.buy_list.remove(&key);
-                }
-                
-                // Add remaining XRD back to liquidity
+                // Queue the refilled XRD behind everyone else, split by the ladder's weights. Liquidity still in the
+                // buy list keeps its keys, so unfilled rungs don't lose their place
                 let xrd_to_add = total_xrd.amount();
-                
-                // Update KVS data
-                kvs_data.xrd_liquidity_available += xrd_to_add;
-                let current_epoch = Runtime::current_epoch().number() as u32;
-                kvs_data.last_added_epoch = current_epoch;
-                
-                // Create new key with new position
-                let discount_basis_points = match (nft_data.discount * dec!(10000)).checked_floor() {
-                    Some(val) => match val.to_string().parse::<u32>() {
-                        Ok(points) => points as u16,
-                        Err(_) => panic!("Failed to parse discount basis points")
-                    },
-                    None => panic!("Failed to convert discount to basis points")
-                };
-                
-                let receipt_id_u32 = match receipt_id.clone() {
-                    NonFungibleLocalId::Integer(i) => i.value() as u32,
-                    _ => panic!("Invalid NFT ID type")
-                };
-                
-                let new_buy_list_key = BuyListKey::new(discount_basis_points, nft_data.auto_unstake, self.avl_position_counter, receipt_id_u32);
-                self.avl_position_counter += 1;
-                
-                // Reinsert at new position
-                self.buy_list.insert(new_buy_list_key, global_id);
-                
-                // Update liquidity index
-                let index_usize = (nft_data.discount / dec!(0.00025)).checked_floor().unwrap().to_string().parse::<usize>().unwrap();
-                
-                if nft_data.auto_unstake {
-                    self.liquidity_index_auto_unstake_true[index_usize] += xrd_to_add;
-                } else {
-                    self.liquidity_index_auto_unstake_false[index_usize] += xrd_to_add;
-                }
+                let new_buy_list_keys = self.queue_additional_liquidity(&global_id, &nft_data, xrd_to_add);
                 
                 // Put XRD in vault
                 self.xrd_liquidity.as_fungible().put(total_xrd);
                 self.total_xrd_locked += xrd_to_add;
+
+                // Fill any resting sell orders that accept the refilled discounts, as adding liquidity would
+                for (new_buy_list_key, discount) in new_buy_list_keys {
+                    self.match_sell_orders(new_buy_list_key, discount);
+                }
                 
                 // Emit the cycle event
                 Runtime::emit_event(LiquidityCycledEvent {
                     receipt_id: receipt_id.clone(),
                     xrd_amount_cycled: xrd_to_add,
                     automation_fee: fee_amount,
+                    fills_remaining,
                 });
                 
                 fills_processed_total += fills_collected;
@@ -944,7 +1533,7 @@ mod liquify_module {
             for (key, value, _) in self.order_fill_tree.range(start_key..=end_key) {
                 total_fills += 1;
                 
-                // We know it's an UnstakeNFT because this is only called for auto_refill receipts
+                // LSU fills are not claimable until cycle_liquidity has unstaked them
                 match value {
                     UnstakeNFTOrLSU::UnstakeNFT(unstake_data) => {
                         let unstake_nft_resource = ResourceManager::from_address(unstake_data.resource_address);
@@ -956,9 +1545,7 @@ mod liquify_module {
                             claimable_fills.push((key, nft_data.claim_amount));
                         }
                     },
-                    UnstakeNFTOrLSU::LSU(_) => {
-                        panic!("Found LSU in auto_refill receipt - this should never happen!");
-                    }
+                    UnstakeNFTOrLSU::LSU(_) => {}
                 }
             }
             
@@ -969,6 +1556,74 @@ mod liquify_module {
             (total_claimable_now, total_fills, ordered_keys)
         }
 
+        /// Unstakes up to `max_fills` LSU fills of a receipt in place, replacing each LSU fill in the order
+        /// fill tree with the resulting unstake NFT under the same key. Returns the number of fills unstaked.
+        fn unstake_lsu_fills(&mut self, receipt_id: &NonFungibleLocalId, global_id: &NonFungibleGlobalId, max_fills: u64) -> u64 {
+            let receipt_id_u64 = match receipt_id {
+                NonFungibleLocalId::Integer(i) => i.value(),
+                _ => return 0,
+            };
+            
+            let start_key = OrderFillKey::new(receipt_id_u64, 1);
+            let end_key = OrderFillKey::new(receipt_id_u64, u64::MAX);
+            
+            let mut lsu_fills: Vec<(u128, LSUData)> = Vec::new();
+            for (key, value, _) in self.order_fill_tree.range(start_key..=end_key) {
+                if lsu_fills.len() as u64 >= max_fills {
+                    break;
+                }
+                if let UnstakeNFTOrLSU::LSU(lsu_data) = value {
+                    lsu_fills.push((key, lsu_data.clone()));
+                }
+            }
+            
+            let mut fills_unstaked = 0u64;
+            let mut redemption_ready_epoch = 0u64;
+            
+            for (avl_key, lsu_data) in lsu_fills {
+                let lsu_bucket = self.component_vaults.get_mut(&lsu_data.resource_address).unwrap()
+                    .take(lsu_data.amount)
+                    .as_fungible();
+                
+                let mut validator = self.get_validator_from_lsu(lsu_data.resource_address);
+                let unstake_nft = validator.unstake(lsu_bucket);
+                let nft_resource = unstake_nft.resource_address();
+                let nft_id = unstake_nft.non_fungible_local_id();
+                
+                let unstake_data: UnstakeData = NonFungibleResourceManager::from(nft_resource).get_non_fungible_data(&nft_id);
+                redemption_ready_epoch = std::cmp::max(redemption_ready_epoch, unstake_data.claim_epoch.number());
+                
+                if !self.component_vaults.get(&nft_resource).is_some() {
+                    self.component_vaults.insert(nft_resource, Vault::new(nft_resource));
+                }
+                self.component_vaults.get_mut(&nft_resource).unwrap().as_non_fungible().put(unstake_nft);
+                
+                // Keep the fill's position, only swap the LSU for its unstake NFT
+                self.order_fill_tree.remove(&avl_key);
+                self.order_fill_tree.insert(avl_key, UnstakeNFTOrLSU::UnstakeNFT(UnstakeNFTData {
+                    resource_address: nft_resource,
+                    id: nft_id,
+                }));
+                
+                fills_unstaked += 1;
+            }
+            
+            if fills_unstaked > 0 {
+                let mut kvs_data = self.liquidity_data.get_mut(global_id).unwrap();
+                kvs_data.lsu_fills_unbonding += fills_unstaked;
+                kvs_data.redemption_ready_epoch = std::cmp::max(kvs_data.redemption_ready_epoch, redemption_ready_epoch);
+                drop(kvs_data);
+                
+                Runtime::emit_event(LsuFillsUnstakedEvent {
+                    receipt_id: receipt_id.clone(),
+                    fills_unstaked,
+                    redemption_ready_epoch,
+                });
+            }
+            
+            fills_unstaked
+        }
+
 
 
         /// Removes liquidity and returns XRD to the provider.
@@ -990,7 +1645,7 @@ mod liquify_module {
             assert!(liquidity_receipt_bucket.resource_address() == self.liquidity_receipt.address(), "Bucket must contain Liquify liquidity receipt(s)");
 
             // First pass: Collect all data and validate
-            let mut removal_data: Vec<(NonFungibleLocalId, NonFungibleGlobalId, LiquidityReceipt, Decimal, Decimal, usize)> = Vec::new();
+            let mut removal_data: Vec<(NonFungibleLocalId, NonFungibleGlobalId, LiquidityReceipt, Decimal)> = Vec::new();
             let mut total_order_size = Decimal::ZERO;
             
             for local_id in liquidity_receipt_bucket.as_non_fungible().non_fungible_local_ids() {
@@ -1000,16 +1655,15 @@ mod liquify_module {
                 
                 let nft_data: LiquidityReceipt = self.liquidity_receipt.get_non_fungible_data(&local_id);
                 let order_size = kvs_data.xrd_liquidity_available;
-                let discount = nft_data.discount;
-                let index = (discount / dec!(0.00025)).checked_floor().unwrap().to_string().parse::<usize>().unwrap();
+                drop(kvs_data);
                 
-                removal_data.push((local_id.clone(), global_id, nft_data, order_size, discount, index));
+                removal_data.push((local_id.clone(), global_id, nft_data, order_size));
                 total_order_size += order_size;
             }
 
             // Second pass: Update NFT data for auto_refill receipts
             let mut automated_removals: Vec<NonFungibleGlobalId> = Vec::new();
-            for (local_id, global_id, nft_data, _, _, _) in &removal_data {
+            for (local_id, global_id, nft_data, _) in &removal_data {
                 if nft_data.auto_refill {
                     self.liquidity_receipt.update_non_fungible_data(local_id, "auto_refill", false);
                     automated_removals.push(global_id.clone());
@@ -1018,73 +1672,21 @@ mod liquify_module {
 
             // Third pass: Remove from automated liquidity tracking
             for global_id_to_remove in automated_removals {
-                let mut target_index = None;
-                let mut last_entry_data = None;
-                
-                // Find the target and get last entry data if needed
-                for i in 1..self.automated_liquidity_index {
-                    if let Some(stored_global_id) = self.automated_liquidity.get(&i) {
-                        if *stored_global_id == global_id_to_remove {
-                            target_index = Some(i);
-                        }
-                        // Store the last entry data
-                        if i == self.automated_liquidity_index - 1 {
-                            last_entry_data = Some(stored_global_id.clone());
-                        }
-                    }
-                }
-                
-                // Now do the removal and reshuffling
-                if let Some(index_to_remove) = target_index {
-                    let last_index = self.automated_liquidity_index - 1;
-                    
-                    if index_to_remove == last_index {
-                        // Removing the last entry - simple case
-                        self.automated_liquidity.remove(&index_to_remove);
-                    } else {
-                        // Not the last entry - need to move last to fill gap
-                        // Remove the target entry
-                        self.automated_liquidity.remove(&index_to_remove);
-                        
-                        if let Some(last_entry) = last_entry_data {
-                            // Remove from last position
-                            self.automated_liquidity.remove(&last_index);
-                            // Insert at the gap position
-                            self.automated_liquidity.insert(index_to_remove, last_entry);
-                        }
-                    }
-                    
-                    self.automated_liquidity_index -= 1;
-                }
-            }
-
-            // Fourth pass: Update liquidity index
-            for (_, _, nft_data, order_size, _, index) in &removal_data {
-                if nft_data.auto_unstake {
-                    self.liquidity_index_auto_unstake_true[*index] -= *order_size;
-                } else {
-                    self.liquidity_index_auto_unstake_false[*index] -= *order_size;
-                }
+                self.deregister_automation(&global_id_to_remove);
             }
 
-            // Fifth pass: Find all keys to remove from buy list
-            let mut keys_to_remove: Vec<u128> = Vec::new();
-            for (_, global_id, _, _, _, _) in &removal_data {
-                for (key, tree_global_id, _) in self.buy_list.range(0..u128::MAX) {
-                    if tree_global_id == global_id.clone() {
-                        keys_to_remove.push(key);
-                        break;
-                    }
-                }
+            // Fourth pass: Update liquidity index for every tier the receipt has liquidity in
+            for (_, global_id, nft_data, _) in &removal_data {
+                self.remove_receipt_from_liquidity_index(global_id, nft_data);
             }
 
-            // Remove from buy list
-            for key in keys_to_remove {
-                self.buy_list.remove(&key);
+            // Fifth pass: Remove every buy list key, including sub-positions
+            for (_, global_id, _, _) in &removal_data {
+                self.remove_buy_list_keys(global_id);
             }
 
             // Sixth pass: Update KVS data
-            for (_, global_id, _, _, _, _) in &removal_data {
+            for (_, global_id, _, _) in &removal_data {
                 let mut kvs_data = self.liquidity_data.get_mut(global_id).unwrap();
                 kvs_data.xrd_liquidity_available = dec!(0);
             }
@@ -1094,7 +1696,7 @@ mod liquify_module {
             self.total_xrd_locked -= total_order_size;
 
             // Emit events
-            for (local_id, _, _, order_size, _, _) in removal_data {
+            for (local_id, _, _, order_size) in removal_data {
                 Runtime::emit_event(LiquidityRemovedEvent {
                     receipt_id: local_id,
                     xrd_amount: order_size,
@@ -1121,6 +1723,29 @@ mod liquify_module {
         ///   - `Bucket`: XRD received from the liquidity providers (minus platform fee)
         ///   - `FungibleBucket`: Any remaining LSUs that couldn't be matched
         pub fn liquify_unstake(&mut self, lsu_bucket: FungibleBucket, max_iterations: u8) -> (Bucket, FungibleBucket) {
+            self.liquify_unstake_with_mode(lsu_bucket, max_iterations, UnstakeExecutionMode::PartialAllowed, None)
+        }
+
+        /// Processes LSU unstaking using on-ledger order matching with an execution mode.
+        /// 
+        /// This method behaves like `liquify_unstake` but lets the caller decide what happens when the LSUs
+        /// can't be completely matched. `PartialAllowed` returns the unmatched LSUs as `liquify_unstake` does,
+        /// `FillOrKill` reverts the transaction unless every LSU is matched, and `MinFillRatio` reverts unless
+        /// at least the given fraction of the LSUs is matched.
+        /// 
+        /// # Arguments
+        /// * `lsu_bucket`: A `FungibleBucket` containing native Radix validator LSUs
+        /// * `max_iterations`: A `u8` limiting the number of liquidity positions to check
+        /// * `execution_mode`: An `UnstakeExecutionMode` selecting how partial fills are treated
+        /// * `seller_badge`: An optional `Proof` of the seller's own liquidity receipts, which won't be matched
+        ///
+        /// # Returns
+        /// * A tuple containing:
+        ///   - `Bucket`: XRD received from the liquidity providers (minus platform fee)
+        ///   - `FungibleBucket`: Any remaining LSUs that couldn't be matched
+        pub fn liquify_unstake_with_mode(&mut self, lsu_bucket: FungibleBucket, max_iterations: u8, execution_mode: UnstakeExecutionMode, seller_badge: Option<Proof>) -> (Bucket, FungibleBucket) {
+            self.validate_execution_mode(execution_mode);
+            let seller_receipts = self.seller_receipt_ids(seller_badge);
             assert!(self.validate_lsu(lsu_bucket.resource_address()), "Bucket must contain a native Radix Validator LSU");
 
             // Pre-calculate total value and validate min/max
@@ -1145,26 +1770,9 @@ mod liquify_module {
             // Check if this is a small order
             let is_small_order = total_lsu_value < self.small_order_threshold;
 
-            let mut order_keys = Vec::new();
-            let mut iter_count = 0;
-            
-            self.buy_list.range_mut(0..u128::MAX).for_each(|(avl_key, _global_id, _)| {
-                if iter_count >= max_iterations as usize {
-                    return scrypto_avltree::IterMutControl::Break;
-                }
-                
-                // For small orders, only include keys with auto_unstake=true
-                if is_small_order && BuyListKey::extract_auto_unstake(*avl_key) {
-                    return scrypto_avltree::IterMutControl::Continue;
-                }
-                
-                order_keys.push(*avl_key);
-                iter_count += 1;
-                
-                scrypto_avltree::IterMutControl::Continue
-            });
+            let order_keys = self.collect_order_keys(is_small_order, max_iterations as usize);
             
-            self.process_unstake(lsu_bucket, order_keys)
+            self.process_unstake(lsu_bucket, order_keys, execution_mode, &seller_receipts, None)
         }
 
         /// Processes LSU unstaking using off-ledger computed order keys.
@@ -1183,6 +1791,28 @@ mod liquify_module {
         ///   - `Bucket`: XRD received from the liquidity providers (minus platform fee)
         ///   - `FungibleBucket`: Any remaining LSUs that couldn't be matched
         pub fn liquify_unstake_off_ledger(&mut self, lsu_bucket: FungibleBucket, order_keys: Vec<u128>) -> (Bucket, FungibleBucket) {
+            self.liquify_unstake_off_ledger_with_mode(lsu_bucket, order_keys, UnstakeExecutionMode::PartialAllowed, None)
+        }
+
+        /// Processes LSU unstaking using off-ledger computed order keys with an execution mode.
+        /// 
+        /// This method behaves like `liquify_unstake_off_ledger` but applies the given `UnstakeExecutionMode`
+        /// once matching is complete, reverting the transaction if too few LSUs were matched. The keys are
+        /// checked against buy list priority according to the component's `OrderKeyPolicy`.
+        /// 
+        /// # Arguments
+        /// * `lsu_bucket`: A `FungibleBucket` containing native Radix validator LSUs
+        /// * `order_keys`: A `Vec<u128>` of pre-computed AVL tree keys to match against
+        /// * `execution_mode`: An `UnstakeExecutionMode` selecting how partial fills are treated
+        /// * `seller_badge`: An optional `Proof` of the seller's own liquidity receipts, which won't be matched
+        ///
+        /// # Returns
+        /// * A tuple containing:
+        ///   - `Bucket`: XRD received from the liquidity providers (minus platform fee)
+        ///   - `FungibleBucket`: Any remaining LSUs that couldn't be matched
+        pub fn liquify_unstake_off_ledger_with_mode(&mut self, lsu_bucket: FungibleBucket, order_keys: Vec<u128>, execution_mode: UnstakeExecutionMode, seller_badge: Option<Proof>) -> (Bucket, FungibleBucket) {
+            self.validate_execution_mode(execution_mode);
+            let seller_receipts = self.seller_receipt_ids(seller_badge);
             assert!(self.validate_lsu(lsu_bucket.resource_address()), "Bucket must contain a native Radix Validator LSU");
             
             // Pre-calculate total value and validate min/max
@@ -1208,70 +1838,318 @@ mod liquify_module {
             let is_small_order = total_lsu_value < self.small_order_threshold;
             
             // Filter order keys if it's a small order
-            let filtered_keys = if is_small_order {
+            let filtered_keys: Vec<u128> = if is_small_order {
                 order_keys.into_iter()
                     .filter(|key| !BuyListKey::extract_auto_unstake(*key))
                     .collect()
             } else {
                 order_keys
             };
+
+            let filtered_keys = match self.order_key_policy {
+                OrderKeyPolicy::Unrestricted => filtered_keys,
+                OrderKeyPolicy::Strict(tolerance) => {
+                    assert!(
+                        self.order_keys_follow_book(&filtered_keys, is_small_order, tolerance),
+                        "Order keys do not follow buy list priority"
+                    );
+                    filtered_keys
+                },
+                OrderKeyPolicy::Fallback(tolerance) => {
+                    if self.order_keys_follow_book(&filtered_keys, is_small_order, tolerance) {
+                        filtered_keys
+                    } else {
+                        // Match the same number of positions the caller asked for, but in book order
+                        let max_keys = filtered_keys.len() + tolerance as usize;
+                        self.collect_order_keys(is_small_order, max_keys)
+                    }
+                },
+            };
             
-            self.process_unstake(lsu_bucket, filtered_keys)
+            self.process_unstake(lsu_bucket, filtered_keys, execution_mode, &seller_receipts, None)
         }
 
-        fn process_unstake(&mut self, mut lsu_bucket: FungibleBucket, order_keys: Vec<u128>) -> (Bucket, FungibleBucket) {
-            
-            let mut xrd_bucket: Bucket = Bucket::new(XRD);
-            let mut validator = self.get_validator_from_lsu(lsu_bucket.resource_address());
-            
-            // Store initial values for event
-            let lsu_resource = lsu_bucket.resource_address();
-            let initial_lsu_amount = lsu_bucket.amount();
-            
-            // Calculate redemption rate
-            let redemption_rate = validator.get_redemption_value(dec!(1));
-            
-            let mut remaining_lsus = lsu_bucket.amount();
-            let mut remaining_value = remaining_lsus * redemption_rate;
+        /// Sells unstake claim NFTs into the available liquidity.
+        /// 
+        /// This method lets holders of validator claim NFTs, received from `validator.unstake`, get XRD now
+        /// instead of waiting for the unbonding delay. Each NFT is valued at its `claim_amount` discounted by
+        /// the liquidity position's discount. When `claim_nft_time_adjustment` is enabled, the discount is
+        /// scaled by the epochs remaining until `claim_epoch` relative to `unbonding_epochs`, so NFTs that are
+        /// close to maturity are bought at a smaller discount. Only positions with auto_unstake enabled are
+        /// matched, as they already receive unstake NFTs for their fills. NFTs can't be split, so each one is
+        /// matched to the first position in buy list order with enough liquidity to buy it outright, checking
+        /// up to `max_iterations` positions. Matched NFTs are stored as fills for the liquidity providers.
+        /// 
+        /// # Arguments
+        /// * `nft_bucket`: A `NonFungibleBucket` containing native Radix validator claim NFTs
+        /// * `max_iterations`: A `u8` limiting the number of liquidity positions to check
+        ///
+        /// # Returns
+        /// * A tuple containing:
+        ///   - `Bucket`: XRD received from the liquidity providers (minus platform fee)
+        ///   - `NonFungibleBucket`: Any claim NFTs that couldn't be matched
+        pub fn liquify_claim_nft(&mut self, mut nft_bucket: NonFungibleBucket, max_iterations: u8) -> (Bucket, NonFungibleBucket) {
+            let nft_resource = nft_bucket.resource_address();
+            assert!(self.validate_unstake_nft(nft_resource), "Bucket must contain a native Radix Validator claim NFT");
+
+            let validator_address = self.get_validator_from_unstake_nft(&nft_resource);
+            let nft_manager = NonFungibleResourceManager::from(nft_resource);
+            let current_epoch = Runtime::current_epoch().number();
+
+            // Read the claim data once
+            let mut claims: Vec<(NonFungibleLocalId, Decimal, u64)> = Vec::new();
+            let mut total_claim_value = dec!(0);
+            for id in nft_bucket.non_fungible_local_ids() {
+                let unstake_data: UnstakeData = nft_manager.get_non_fungible_data(&id);
+                let epochs_remaining = unstake_data.claim_epoch.number().saturating_sub(current_epoch);
+                total_claim_value += unstake_data.claim_amount;
+                claims.push((id, unstake_data.claim_amount, epochs_remaining));
+            }
 
-            // Batch collections
-            let mut avl_removals = Vec::new();
-            let mut kvs_updates: Vec<(NonFungibleGlobalId, Decimal, Decimal, u64)> = Vec::new();
-            let mut index_updates: HashMap<(usize, bool), Decimal> = HashMap::new();
-            
-            // Separate fill operations by type to batch vault operations
-            let mut unstake_operations: Vec<(u128, FungibleBucket)> = Vec::new();
-            let mut lsu_operations: Vec<(u128, ResourceAddress, Decimal)> = Vec::new();
-            let mut vault_resources_needed: std::collections::HashSet<ResourceAddress> = std::collections::HashSet::new();
+            assert!(
+                total_claim_value >= self.minimum_unstake_value,
+                "Unstake value of {} XRD is below minimum requirement of {} XRD",
+                total_claim_value,
+                self.minimum_unstake_value
+            );
+            assert!(
+                total_claim_value <= self.maximum_unstake_value,
+                "Unstake value of {} XRD exceeds maximum limit of {} XRD",
+                total_claim_value,
+                self.maximum_unstake_value
+            );
 
-            for key in order_keys {
-                let global_id_option = self.buy_list.get(&key);
-                if global_id_option.is_none() {
+            // Snapshot the auto_unstake positions we are allowed to check
+            let mut candidates: Vec<(u128, NonFungibleGlobalId, LiquidityReceipt, Option<Decimal>, Decimal)> = Vec::new();
+            for (avl_key, global_id, _) in self.buy_list.range(0..u128::MAX) {
+                if candidates.len() >= max_iterations as usize {
+                    break;
+                }
+
+                if !BuyListKey::extract_auto_unstake(avl_key) {
                     continue;
                 }
-                
-                let global_id = global_id_option.unwrap().clone();
+
+                let nft_data: LiquidityReceipt = self.liquidity_receipt.get_non_fungible_data(global_id.local_id());
+                let kvs_data = self.liquidity_data.get(&global_id).unwrap();
+                let discount_override = if kvs_data.discount_override_validators.contains(&validator_address) {
+                    Some(*self.discount_overrides.get(&DiscountOverrideKey {
+                        receipt_id: global_id.clone(),
+                        validator: validator_address,
+                    }).unwrap())
+                } else {
+                    None
+                };
+                let xrd_available = kvs_data.key_liquidity_available(avl_key);
+                drop(kvs_data);
+
+                candidates.push((avl_key, global_id, nft_data, discount_override, xrd_available));
+            }
+
+            // Match each NFT to the first position that can buy it outright
+            let mut fills: Vec<(usize, NonFungibleLocalId, Decimal, Decimal, Decimal)> = Vec::new();
+            for (id, claim_amount, epochs_remaining) in claims {
+                let epochs_to_claim = if self.claim_nft_time_adjustment {
+                    std::cmp::min(epochs_remaining, self.unbonding_epochs)
+                } else {
+                    self.unbonding_epochs
+                };
+
+                for (index, candidate) in candidates.iter_mut().enumerate() {
+                    let discount = match candidate.3 {
+                        Some(discount_override) => self.time_scaled_discount(discount_override, None, epochs_to_claim),
+                        None => self.time_scaled_discount(BuyListKey::discount(candidate.0), candidate.2.annualized_rate, epochs_to_claim),
+                    };
+                    let mut price = claim_amount * (dec!(1) - discount);
+
+                    if price <= candidate.4 {
+                        candidate.4 -= price;
+                        if self.dust_policy == DustPolicy::SweepIntoFill && self.is_xrd_dust(candidate.4) {
+                            price += candidate.4;
+                            candidate.4 = dec!(0);
+                        }
+                        fills.push((index, id, claim_amount, price, discount));
+                        break;
+                    }
+                }
+            }
+
+            let mut xrd_bucket: Bucket = Bucket::new(XRD);
+            let mut index_updates: HashMap<(usize, bool), Decimal> = HashMap::new();
+
+            if !fills.is_empty() && !self.component_vaults.get(&nft_resource).is_some() {
+                self.component_vaults.insert(nft_resource, Vault::new(nft_resource));
+            }
+
+            for (candidate_index, id, claim_amount, price, discount) in fills {
+                let (avl_key, global_id, nft_data, _, _) = &candidates[candidate_index];
+                let local_id = global_id.local_id().clone();
+                let local_id_u64 = match &local_id {
+                    NonFungibleLocalId::Integer(i) => i.value(),
+                    _ => 0,
+                };
+
+                xrd_bucket.put(self.xrd_liquidity.take(price));
+
+                let claim_nft = nft_bucket.take_non_fungible(&id);
+                self.component_vaults.get_mut(&nft_resource).unwrap().as_non_fungible().put(claim_nft);
+
+                let order_fill_key = OrderFillKey::new(local_id_u64, self.order_fill_counter);
+                self.order_fill_counter += 1;
+                self.order_fill_tree.insert(order_fill_key, UnstakeNFTOrLSU::UnstakeNFT(UnstakeNFTData {
+                    resource_address: nft_resource,
+                    id: id.clone(),
+                }));
+
+                self.debit_buy_list_key(global_id, *avl_key, price);
+                let mut kvs_data = self.liquidity_data.get_mut(global_id).unwrap();
+                kvs_data.xrd_liquidity_filled += price;
+                kvs_data.fills_to_collect += 1;
+                drop(kvs_data);
+
+                let index = (BuyListKey::discount(*avl_key) / dec!(0.00025)).checked_floor().unwrap().to_string().parse::<usize>().unwrap();
+                *index_updates.entry((index, nft_data.auto_unstake)).or_insert(dec!(0)) += price;
+
+                Runtime::emit_event(ClaimNftFillEvent {
+                    receipt_id: local_id,
+                    claim_nft: NonFungibleGlobalId::new(nft_resource, id),
+                    claim_amount,
+                    xrd_amount: price,
+                    discount,
+                });
+            }
+
+            for ((index, auto_unstake), total_fill) in index_updates {
+                if auto_unstake {
+                    self.liquidity_index_auto_unstake_true[index] -= total_fill;
+                } else {
+                    self.liquidity_index_auto_unstake_false[index] -= total_fill;
+                }
+            }
+
+            // Used up positions have left the buy list, move any dust residue to the claimable balance
+            for (avl_key, global_id, nft_data, _, xrd_available) in &candidates {
+                if self.is_xrd_dust(*xrd_available) {
+                    self.move_available_to_claimable(global_id, nft_data, *avl_key);
+                }
+            }
+
+            // Update totals and fees
+            self.total_xrd_volume += xrd_bucket.amount();
+            self.total_xrd_locked -= xrd_bucket.amount();
+
+            let fee_bucket = xrd_bucket.take(xrd_bucket.amount() * self.platform_fee);
+            self.fee_vault.put(fee_bucket);
+
+            Runtime::emit_event(LiquifyClaimNftEvent {
+                nft_resource,
+                claim_amount: total_claim_value,
+                xrd_received: xrd_bucket.amount(),
+            });
+
+            (xrd_bucket, nft_bucket)
+        }
+
+        fn process_unstake(
+            &mut self,
+            mut lsu_bucket: FungibleBucket,
+            order_keys: Vec<u128>,
+            execution_mode: UnstakeExecutionMode,
+            seller_receipts: &std::collections::HashSet<NonFungibleGlobalId>,
+            max_discount: Option<Decimal>
+        ) -> (Bucket, FungibleBucket) {
+            
+            let mut xrd_bucket: Bucket = Bucket::new(XRD);
+            let mut validator = self.get_validator_from_lsu(lsu_bucket.resource_address());
+            let validator_address = validator.address();
+            
+            // Store initial values for event
+            let lsu_resource = lsu_bucket.resource_address();
+            let initial_lsu_amount = lsu_bucket.amount();
+            
+            // Calculate redemption rate
+            let redemption_rate = validator.get_redemption_value(dec!(1));
+            
+            let mut remaining_lsus = lsu_bucket.amount();
+            let mut remaining_value = remaining_lsus * redemption_rate;
+
+            // Batch collections
+            let mut kvs_updates: Vec<(NonFungibleGlobalId, u128, Decimal)> = Vec::new();
+            let mut index_updates: HashMap<(usize, bool), Decimal> = HashMap::new();
+            
+            // Separate fill operations by type to batch vault operations
+            let mut unstake_operations: Vec<(u128, FungibleBucket)> = Vec::new();
+            let mut lsu_operations: Vec<(u128, ResourceAddress, Decimal)> = Vec::new();
+            let mut vault_resources_needed: std::collections::HashSet<ResourceAddress> = std::collections::HashSet::new();
+            let mut dust_residues: Vec<(NonFungibleGlobalId, LiquidityReceipt, u128)> = Vec::new();
+            let mut lsu_allocations: HashMap<u128, Decimal> = HashMap::new();
+            let mut processed_keys: std::collections::HashSet<u128> = std::collections::HashSet::new();
+
+            for (key_index, key) in order_keys.iter().copied().enumerate() {
+                if !processed_keys.insert(key) {
+                    continue;
+                }
+
+                let global_id_option = self.buy_list.get(&key);
+                if global_id_option.is_none() {
+                    continue;
+                }
+                
+                let global_id = global_id_option.unwrap().clone();
                 let local_id = global_id.local_id();
+
+                // Sellers can't fill their own liquidity unless the owner has allowed it
+                let wash_fill = seller_receipts.contains(&global_id);
+                if wash_fill && self.self_match_prevention {
+                    continue;
+                }
+
+                // Split what is left across a pro-rata tier the first time the unstake reaches it
+                let tier_basis_points = (key >> 112) as u16;
+                if !lsu_allocations.contains_key(&key) && self.tier_matching_policy(tier_basis_points) == MatchingPolicy::ProRata {
+                    self.allocate_pro_rata(&order_keys[key_index..], tier_basis_points, remaining_lsus, seller_receipts, &mut lsu_allocations);
+                }
                 
                 // Read data once
                 let nft_data: LiquidityReceipt = self.liquidity_receipt.get_non_fungible_data(&local_id);
-                let discount = nft_data.discount;
                 let auto_unstake = nft_data.auto_unstake;
                 
                 let kvs_data = self.liquidity_data.get(&global_id).unwrap();
-                let xrd_available = kvs_data.xrd_liquidity_available;
-                let current_fills = kvs_data.fills_to_collect;
+                let xrd_available = kvs_data.key_liquidity_available(key);
+
+                // Price with the validator override if one is set; the buy list position stays at the base discount
+                let discount = self.applied_discount(key, &global_id, &nft_data, &kvs_data, validator_address);
+
+                // A limit order only sells into positions that charge no more than its limit
+                if max_discount.is_some_and(|limit| discount > limit) {
+                    continue;
+                }
 
-                // Calculate fill
-                let discounted_value = remaining_value * (dec!(1) - discount);
-                let (lsu_to_take, fill_amount, new_xrd_available) = if discounted_value <= xrd_available {
-                    (remaining_lsus, discounted_value, xrd_available - discounted_value)
+                // Calculate fill, limited to the position's share when its tier is matched pro-rata
+                let lsu_budget = match lsu_allocations.get(&key) {
+                    Some(allocation) if *allocation < remaining_lsus => *allocation,
+                    _ => remaining_lsus,
+                };
+                if lsu_budget <= dec!(0) {
+                    continue;
+                }
+                let budget_value = if lsu_budget == remaining_lsus { remaining_value } else { lsu_budget * redemption_rate };
+                let discounted_value = budget_value * (dec!(1) - discount);
+                let (lsu_to_take, mut fill_amount, mut new_xrd_available) = if discounted_value <= xrd_available {
+                    (lsu_budget, discounted_value, xrd_available - discounted_value)
                 } else {
                     let lsu_ratio = xrd_available / discounted_value;
-                    let lsu_take = remaining_lsus * lsu_ratio;
+                    let lsu_take = lsu_budget * lsu_ratio;
                     (lsu_take, xrd_available, dec!(0))
                 };
 
+                // Don't leave dust on the buy list, either pay it out with this fill or set it aside below
+                let is_dust = self.is_xrd_dust(new_xrd_available);
+                if is_dust && self.dust_policy == DustPolicy::SweepIntoFill {
+                    fill_amount += new_xrd_available;
+                    new_xrd_available = dec!(0);
+                }
+
                 // Take resources
                 let lsu_taken: FungibleBucket = lsu_bucket.take(lsu_to_take);
                 let xrd_funds = self.xrd_liquidity.take(fill_amount);
@@ -1286,15 +2164,15 @@ mod liquify_module {
                     _ => 0,
                 };
 
-                // Queue updates
-                if new_xrd_available == dec!(0) {
-                    avl_removals.push(key);
+                // Queue updates, a dust residue is moved to the receipt's claimable balance below
+                if is_dust && self.dust_policy == DustPolicy::MoveToClaimable {
+                    dust_residues.push((global_id.clone(), nft_data.clone(), key));
                 }
                 
-                kvs_updates.push((global_id.clone(), new_xrd_available, fill_amount, current_fills + 1));
+                kvs_updates.push((global_id.clone(), key, fill_amount));
                 
-                // Aggregate index updates
-                let index = (discount / dec!(0.00025)).checked_floor().unwrap().to_string().parse::<usize>().unwrap();
+                // Aggregate index updates, ladder rungs sit in different tiers
+                let index = (BuyListKey::discount(key) / dec!(0.00025)).checked_floor().unwrap().to_string().parse::<usize>().unwrap();
                 *index_updates.entry((index, auto_unstake)).or_insert(dec!(0)) += fill_amount;
 
                 // Create order fill key using new structure
@@ -1320,6 +2198,7 @@ mod liquify_module {
                     lsu_amount: lsu_to_take,
                     xrd_amount: fill_amount,
                     discount,
+                    wash_fill,
                 });
 
                 if remaining_lsus.is_zero() {
@@ -1327,16 +2206,30 @@ mod liquify_module {
                 }
             }
 
-            // Batch apply all non-vault updates first
-            for key in avl_removals {
-                self.buy_list.remove(&key);
+            // Enforce the execution mode before any state is updated
+            match execution_mode {
+                UnstakeExecutionMode::PartialAllowed => {},
+                UnstakeExecutionMode::FillOrKill => {
+                    assert!(remaining_lsus.is_zero(), "Fill-or-kill unstake could only match {} of {} LSU", initial_lsu_amount - remaining_lsus, initial_lsu_amount);
+                },
+                UnstakeExecutionMode::MinFillRatio(min_fill_ratio) => {
+                    let lsu_matched = initial_lsu_amount - remaining_lsus;
+                    assert!(
+                        lsu_matched >= initial_lsu_amount * min_fill_ratio,
+                        "Unstake matched {} of {} LSU, below the minimum fill ratio of {}",
+                        lsu_matched,
+                        initial_lsu_amount,
+                        min_fill_ratio
+                    );
+                }
             }
 
-            for (global_id, new_available, fill_amount, new_fills) in kvs_updates {
+            // Batch apply all non-vault updates first, emptied keys leave the buy list as they are debited
+            for (global_id, key, fill_amount) in kvs_updates {
+                self.debit_buy_list_key(&global_id, key, fill_amount);
                 let mut kvs_data = self.liquidity_data.get_mut(&global_id).unwrap();
                 kvs_data.xrd_liquidity_filled += fill_amount;
-                kvs_data.xrd_liquidity_available = new_available;
-                kvs_data.fills_to_collect = new_fills;
+                kvs_data.fills_to_collect += 1;
             }
 
             for ((index, auto_unstake), total_fill) in index_updates {
@@ -1347,6 +2240,10 @@ mod liquify_module {
                 }
             }
 
+            for (global_id, nft_data, key) in dust_residues {
+                self.move_available_to_claimable(&global_id, &nft_data, key);
+            }
+
             // Process LSU fills (already in vaults)
             for (order_fill_key, resource, amount) in lsu_operations {
                 let lsu_data = UnstakeNFTOrLSU::LSU(LSUData { resource_address: resource, amount });
@@ -1407,13 +2304,72 @@ mod liquify_module {
             (xrd_bucket, lsu_bucket)
         }
         
+        /// Closes a fully settled liquidity position and burns its receipt.
+        /// 
+        /// This method lets a liquidity provider clean up a receipt that has nothing left in it: no available
+        /// liquidity, no fills to collect, no claimable XRD and no partial cycle in progress. The receipt's
+        /// `liquidity_data` entry, which holds its delivery account, is removed together with its discount
+        /// overrides and any automation slot, and the NFT is burned.
+        /// 
+        /// # Arguments
+        /// * `receipt_bucket`: A `Bucket` containing exactly one liquidity receipt NFT
+        ///
+        /// # Returns
+        /// * None
+        pub fn close_position(&mut self, receipt_bucket: Bucket) {
+            assert!(receipt_bucket.resource_address() == self.liquidity_receipt.address(), "Bucket must contain Liquify liquidity receipt");
+            assert!(receipt_bucket.amount() == dec!(1), "Must provide exactly one liquidity receipt");
+
+            let local_id = receipt_bucket.as_non_fungible().non_fungible_local_id();
+            let global_id = NonFungibleGlobalId::new(self.liquidity_receipt.address(), local_id.clone());
+            let nft_data: LiquidityReceipt = self.liquidity_receipt.get_non_fungible_data(&local_id);
+
+            let kvs_data = self.liquidity_data.get(&global_id).unwrap();
+            assert!(kvs_data.xrd_liquidity_available == dec!(0), "Remove the available liquidity before closing the position");
+            assert!(kvs_data.fills_to_collect == 0, "Collect all fills before closing the position");
+            assert!(kvs_data.claimable_xrd_balance == dec!(0), "Withdraw the claimable XRD before closing the position");
+            assert!(!kvs_data.mid_cycle, "Cannot close a position in the middle of a cycle");
+            let xrd_liquidity_filled = kvs_data.xrd_liquidity_filled;
+            let override_validators = kvs_data.discount_override_validators.clone();
+            let delivery_account = kvs_data.delivery_account;
+            drop(kvs_data);
+
+            for validator in override_validators {
+                self.discount_overrides.remove(&DiscountOverrideKey {
+                    receipt_id: global_id.clone(),
+                    validator,
+                });
+            }
+
+            if nft_data.auto_refill {
+                self.deregister_automation(&global_id);
+            }
+
+            // The delivery account lives in the receipt's entry, so deliver_fills stops serving it once removed
+            if delivery_account.is_some() {
+                Runtime::emit_event(DeliveryAccountUpdatedEvent {
+                    receipt_id: local_id.clone(),
+                    delivery_account: None,
+                });
+            }
+
+            self.liquidity_data.remove(&global_id);
+            receipt_bucket.burn();
+
+            Runtime::emit_event(PositionClosedEvent {
+                receipt_id: local_id,
+                xrd_liquidity_filled,
+            });
+        }
+
         /// Collects fills for liquidity providers.
         /// 
         /// This method allows liquidity providers to collect LSUs or unstake NFTs from orders they've filled.
-        /// The number of fills to collect can be limited to manage transaction costs. Fills are returned in
-        /// the order they were created. For positions with auto_unstake enabled, unstake NFTs are returned.
-        /// For positions without auto_unstake, the original LSUs are returned. Multiple receipts can be
-        /// processed in one transaction.
+        /// The number of fills to collect can be limited to manage transaction costs. Fills are collected in
+        /// the order they were created and merged into one bucket per resource, so fills of the same LSU or
+        /// unstake NFT resource come back together. The per-fill detail is kept in `CollectFillsEvent`. For
+        /// positions with auto_unstake enabled, unstake NFTs are returned. For positions without auto_unstake,
+        /// the original LSUs are returned. Multiple receipts can be processed in one transaction.
         /// 
         /// # Arguments
         /// * `liquidity_receipt_bucket`: A `Bucket` containing one or more liquidity receipt NFTs
@@ -1421,9 +2377,30 @@ mod liquify_module {
         ///
         /// # Returns
         /// * A tuple containing:
-        ///   - `Vec<Bucket>`: A vector of buckets containing collected LSUs or unstake NFTs
+        ///   - `Vec<Bucket>`: A vector with one bucket per collected LSU or unstake NFT resource
         ///   - `Bucket`: The liquidity receipt NFTs (returned unchanged)
         pub fn collect_fills(&mut self, liquidity_receipt_bucket: Bucket, number_of_fills_to_collect: u64) -> (Vec<Bucket>, Bucket) {
+            self.collect_fills_filtered(liquidity_receipt_bucket, number_of_fills_to_collect, FillFilter::All)
+        }
+
+        /// Collects only the fills that match a filter.
+        /// 
+        /// This method works like `collect_fills` but skips fills that don't match `filter`, leaving them in
+        /// the order fill tree. `Resource` only collects fills of the given LSU or unstake NFT resource,
+        /// `ClaimableNow` only collects unstake NFTs that have reached their `claim_epoch`, and `LSUOnly` only
+        /// collects LSU fills. `ClaimableNow` uses the same rule as `calculate_claimable_xrd`, so the
+        /// `claimable_xrd` it reports is the XRD value of the NFTs such a call returns.
+        /// 
+        /// # Arguments
+        /// * `liquidity_receipt_bucket`: A `Bucket` containing one or more liquidity receipt NFTs
+        /// * `number_of_fills_to_collect`: A `u64` limiting total fills collected across all receipts
+        /// * `filter`: A `FillFilter` selecting which fills to collect
+        ///
+        /// # Returns
+        /// * A tuple containing:
+        ///   - `Vec<Bucket>`: A vector with one bucket per collected LSU or unstake NFT resource
+        ///   - `Bucket`: The liquidity receipt NFTs (returned unchanged)
+        pub fn collect_fills_filtered(&mut self, liquidity_receipt_bucket: Bucket, number_of_fills_to_collect: u64, filter: FillFilter) -> (Vec<Bucket>, Bucket) {
             
             assert!(
                 liquidity_receipt_bucket.resource_address() == self.liquidity_receipt.address(),
@@ -1433,6 +2410,7 @@ mod liquify_module {
             let mut bucket_vec: Vec<Bucket> = Vec::new();
             let mut collect_counter: u64 = 0;
             let mut all_updates = vec![];
+            let current_epoch = Runtime::current_epoch().number();
             
             // Track event data
             let mut event_data_per_receipt: HashMap<NonFungibleLocalId, (u64, Vec<(Decimal, ResourceAddress)>, Vec<NonFungibleGlobalId>)> = HashMap::new();
@@ -1472,6 +2450,10 @@ mod liquify_module {
                         break;
                     }
 
+                    if !self.fill_matches_filter(&value, filter, current_epoch) {
+                        continue;
+                    }
+
                     fills_to_remove.push((key, value.clone()));
                     fills_collected_for_this_order += 1;
                     collect_counter += 1;
@@ -1490,7 +2472,11 @@ mod liquify_module {
                             // Track for event
                             lsus_collected.push((lsu_amount, lsu_resource));
                             
-                            bucket_vec.push(lsu_bucket);
+                            // Merge into the bucket for this resource
+                            match bucket_vec.iter_mut().find(|bucket| bucket.resource_address() == lsu_resource) {
+                                Some(bucket) => bucket.put(lsu_bucket),
+                                None => bucket_vec.push(lsu_bucket),
+                            }
                         }
 
                         UnstakeNFTOrLSU::UnstakeNFT(unstake_nft_data) => {
@@ -1503,7 +2489,11 @@ mod liquify_module {
                             let nft_global_id = NonFungibleGlobalId::new(unstake_nft_data.resource_address, unstake_nft_id.clone());
                             stake_claim_nfts_collected.push(nft_global_id);
                             
-                            bucket_vec.push(unstake_nft_bucket);
+                            // Merge into the bucket for this resource
+                            match bucket_vec.iter_mut().find(|bucket| bucket.resource_address() == unstake_nft_data.resource_address) {
+                                Some(bucket) => bucket.put(unstake_nft_bucket),
+                                None => bucket_vec.push(unstake_nft_bucket),
+                            }
                         }
                     }
 
@@ -1511,7 +2501,7 @@ mod liquify_module {
                 }
 
                 let new_fills_to_collect = fills_to_collect - fills_collected_for_this_order;
-                all_updates.push((global_id, new_fills_to_collect));
+                all_updates.push((global_id, new_fills_to_collect, stake_claim_nfts_collected.len() as u64));
                 
                 // Store event data for this receipt
                 if fills_collected_for_this_order > 0 {
@@ -1523,9 +2513,10 @@ mod liquify_module {
             }
 
             // Apply all KVS updates
-            for (global_id, new_fills_to_collect) in all_updates {
+            for (global_id, new_fills_to_collect, nfts_collected) in all_updates {
                 let mut kvs_data = self.liquidity_data.get_mut(&global_id).unwrap();
                 kvs_data.fills_to_collect = new_fills_to_collect;
+                kvs_data.lsu_fills_unbonding = kvs_data.lsu_fills_unbonding.saturating_sub(nfts_collected);
             }
             
             // Emit events for each receipt that had fills collected
@@ -1537,10 +2528,895 @@ mod liquify_module {
                     stake_claim_nfts_collected: nfts,
                 });
             }
-
-            (bucket_vec, liquidity_receipt_bucket)
+
+            (bucket_vec, liquidity_receipt_bucket)
+        }
+                
+        fn fill_matches_filter(&self, fill: &UnstakeNFTOrLSU, filter: FillFilter, current_epoch: u64) -> bool {
+            match (filter, fill) {
+                (FillFilter::All, _) => true,
+                (FillFilter::Resource(resource), UnstakeNFTOrLSU::LSU(lsu_data)) => lsu_data.resource_address == resource,
+                (FillFilter::Resource(resource), UnstakeNFTOrLSU::UnstakeNFT(unstake_data)) => unstake_data.resource_address == resource,
+                (FillFilter::ClaimableNow, UnstakeNFTOrLSU::UnstakeNFT(unstake_data)) => {
+                    let unstake_nft_resource = NonFungibleResourceManager::from(unstake_data.resource_address);
+                    let nft_data: UnstakeData = unstake_nft_resource.get_non_fungible_data(&unstake_data.id);
+                    current_epoch >= nft_data.claim_epoch.number()
+                },
+                (FillFilter::ClaimableNow, UnstakeNFTOrLSU::LSU(_)) => false,
+                (FillFilter::LSUOnly, UnstakeNFTOrLSU::LSU(_)) => true,
+                (FillFilter::LSUOnly, UnstakeNFTOrLSU::UnstakeNFT(_)) => false,
+            }
+        }
+
+        /// Collects matured unstake NFT fills as claimed XRD.
+        /// 
+        /// This method walks the fills of one or more liquidity receipts and claims every unstake NFT that has
+        /// reached its `claim_epoch` through its validator, returning the XRD in a single bucket. Claim NFTs
+        /// from the same validator are claimed together. Unstake NFTs that are still unbonding and LSU fills
+        /// are left in the order fill tree to be collected later.
+        /// 
+        /// # Arguments
+        /// * `liquidity_receipt_bucket`: A `Bucket` containing one or more liquidity receipt NFTs
+        /// * `max_fills`: A `u64` limiting total fills claimed across all receipts
+        ///
+        /// # Returns
+        /// * A tuple containing:
+        ///   - `Bucket`: The XRD claimed from all matured unstake NFTs
+        ///   - `Bucket`: The liquidity receipt NFTs (returned unchanged)
+        pub fn collect_fills_as_xrd(&mut self, liquidity_receipt_bucket: Bucket, max_fills: u64) -> (Bucket, Bucket) {
+            assert!(
+                liquidity_receipt_bucket.resource_address() == self.liquidity_receipt.address(),
+                "Bucket must contain Liquify liquidity receipts NFT(s)"
+            );
+
+            let current_epoch = Runtime::current_epoch().number();
+            let mut claim_nft_buckets: Vec<NonFungibleBucket> = Vec::new();
+            let mut collect_counter: u64 = 0;
+
+            for receipt_id in liquidity_receipt_bucket.as_non_fungible().non_fungible_local_ids() {
+                if collect_counter >= max_fills {
+                    break;
+                }
+
+                let global_id = NonFungibleGlobalId::new(self.liquidity_receipt.address(), receipt_id.clone());
+                let receipt_id_u64 = match receipt_id.clone() {
+                    NonFungibleLocalId::Integer(i) => i.value(),
+                    _ => 0,
+                };
+
+                let start_key = OrderFillKey::new(receipt_id_u64, 1);
+                let end_key = OrderFillKey::new(receipt_id_u64, u64::MAX);
+
+                // Find the matured unstake NFTs, leaving immature NFTs and LSUs in place
+                let mut matured_fills: Vec<(u128, UnstakeNFTData, Decimal)> = Vec::new();
+                for (key, value, _) in self.order_fill_tree.range(start_key..=end_key) {
+                    if collect_counter >= max_fills {
+                        break;
+                    }
+
+                    if let UnstakeNFTOrLSU::UnstakeNFT(unstake_data) = value {
+                        let unstake_nft_resource = NonFungibleResourceManager::from(unstake_data.resource_address);
+                        let nft_data: UnstakeData = unstake_nft_resource.get_non_fungible_data(&unstake_data.id);
+
+                        if current_epoch >= nft_data.claim_epoch.number() {
+                            matured_fills.push((key, unstake_data.clone(), nft_data.claim_amount));
+                            collect_counter += 1;
+                        }
+                    }
+                }
+
+                if matured_fills.is_empty() {
+                    continue;
+                }
+
+                let fills_claimed = matured_fills.len() as u64;
+                let mut xrd_claimed = dec!(0);
+
+                for (avl_key, unstake_data, claim_amount) in matured_fills {
+                    let unstake_nft = self.component_vaults.get_mut(&unstake_data.resource_address).unwrap()
+                        .as_non_fungible()
+                        .take_non_fungible(&unstake_data.id);
+
+                    // Group claim NFTs per validator so each validator is called once
+                    match claim_nft_buckets.iter_mut().find(|bucket| bucket.resource_address() == unstake_data.resource_address) {
+                        Some(bucket) => bucket.put(unstake_nft),
+                        None => claim_nft_buckets.push(unstake_nft),
+                    }
+
+                    self.order_fill_tree.remove(&avl_key);
+                    xrd_claimed += claim_amount;
+                }
+
+                let mut kvs_data = self.liquidity_data.get_mut(&global_id).unwrap();
+                kvs_data.fills_to_collect = kvs_data.fills_to_collect.saturating_sub(fills_claimed);
+                kvs_data.lsu_fills_unbonding = kvs_data.lsu_fills_unbonding.saturating_sub(fills_claimed);
+
+                Runtime::emit_event(FillsClaimedEvent {
+                    receipt_id,
+                    fills_claimed,
+                    xrd_claimed,
+                });
+            }
+
+            let mut xrd_bucket: Bucket = Bucket::new(XRD);
+            for claim_nft_bucket in claim_nft_buckets {
+                let validator_address = self.get_validator_from_unstake_nft(&claim_nft_bucket.resource_address());
+                let mut validator: Global<Validator> = Global::from(validator_address);
+
+                let claimed_xrd = validator.claim_xrd(claim_nft_bucket);
+                xrd_bucket.put(claimed_xrd.into());
+            }
+
+            (xrd_bucket, liquidity_receipt_bucket)
+        }
+
+        /// Registers the account that `deliver_fills` sends a receipt's fills to.
+        /// 
+        /// This method lets a liquidity provider opt into having fills pushed to an account instead of
+        /// collecting them with `collect_fills`. Passing `None` stops deliveries.
+        /// 
+        /// # Arguments
+        /// * `receipt_bucket`: A `Bucket` containing exactly one liquidity receipt NFT
+        /// * `delivery_account`: An `Option<ComponentAddress>` with the account to deliver fills to
+        ///
+        /// # Returns
+        /// * A `Bucket` containing the same liquidity receipt NFT that was passed in
+        pub fn set_delivery_account(&mut self, receipt_bucket: Bucket, delivery_account: Option<ComponentAddress>) -> Bucket {
+            assert!(receipt_bucket.resource_address() == self.liquidity_receipt.address(), "Bucket must contain Liquify liquidity receipt");
+            assert!(receipt_bucket.amount() == dec!(1), "Must provide exactly one liquidity receipt");
+
+            if let Some(account) = delivery_account {
+                assert!(account.as_node_id().is_global_account(), "Delivery target must be an account");
+            }
+
+            let local_id = receipt_bucket.as_non_fungible().non_fungible_local_id();
+            let global_id = NonFungibleGlobalId::new(self.liquidity_receipt.address(), local_id.clone());
+            let mut kvs_data = self.liquidity_data.get_mut(&global_id).unwrap();
+            kvs_data.delivery_account = delivery_account;
+
+            Runtime::emit_event(DeliveryAccountUpdatedEvent {
+                receipt_id: local_id,
+                delivery_account,
+            });
+
+            receipt_bucket
+        }
+
+        /// Delivers fills to the accounts registered by their liquidity providers.
+        /// 
+        /// This permissionless method lets anyone push fills from the order fill tree to each receipt's
+        /// registered delivery account using `try_deposit_or_refund`, so liquidity providers receive their
+        /// LSUs or unstake NFTs without running their own bot. The caller earns `delivery_fee` XRD per
+        /// delivered fill, which is paid out of the receipt's claimable XRD balance so the position's bid is
+        /// left untouched. Liquidity providers fund that balance with `deposit_claimable`. A receipt whose
+        /// balance can't cover the fee is skipped, and a fill the account refuses stays in the tree.
+        /// 
+        /// # Arguments
+        /// * `receipt_ids`: A `Vec<NonFungibleLocalId>` containing the receipts to deliver fills for
+        /// * `max_fills`: A `u64` limiting total fills delivered across all receipts
+        ///
+        /// # Returns
+        /// * A `FungibleBucket` containing the accumulated delivery fees in XRD
+        pub fn deliver_fills(&mut self, receipt_ids: Vec<NonFungibleLocalId>, max_fills: u64) -> FungibleBucket {
+            let mut total_delivery_fees = FungibleBucket::new(XRD);
+            let mut delivered_total = 0u64;
+
+            for receipt_id in receipt_ids {
+                if delivered_total >= max_fills {
+                    break;
+                }
+
+                let global_id = NonFungibleGlobalId::new(self.liquidity_receipt.address(), receipt_id.clone());
+                let (delivery_account, fills_to_collect) = match self.liquidity_data.get(&global_id) {
+                    Some(kvs_data) => (kvs_data.delivery_account, kvs_data.fills_to_collect),
+                    None => continue,
+                };
+
+                let delivery_account = match delivery_account {
+                    Some(account) => account,
+                    None => continue,
+                };
+
+                if fills_to_collect == 0 {
+                    continue;
+                }
+
+                let receipt_id_u64 = match receipt_id.clone() {
+                    NonFungibleLocalId::Integer(i) => i.value(),
+                    _ => 0,
+                };
+
+                let start_key = OrderFillKey::new(receipt_id_u64, 1);
+                let end_key = OrderFillKey::new(receipt_id_u64, u64::MAX);
+
+                let mut fills_to_deliver = Vec::new();
+                for (key, value, _) in self.order_fill_tree.range(start_key..=end_key) {
+                    if delivered_total + fills_to_deliver.len() as u64 >= max_fills {
+                        break;
+                    }
+                    fills_to_deliver.push((key, value.clone()));
+                }
+
+                let account: Global<Account> = Global::from(delivery_account);
+                let mut fills_delivered = 0u64;
+                let mut keeper_fee = dec!(0);
+                let mut lsus_delivered = Vec::new();
+                let mut stake_claim_nfts_delivered = Vec::new();
+
+                for (avl_key, unstake_nft_or_lsu) in fills_to_deliver {
+                    let claimable_xrd_balance = self.liquidity_data.get(&global_id).unwrap().claimable_xrd_balance;
+                    if claimable_xrd_balance < self.delivery_fee {
+                        break;
+                    }
+
+                    let (fill_bucket, fill_resource): (Bucket, ResourceAddress) = match &unstake_nft_or_lsu {
+                        UnstakeNFTOrLSU::LSU(lsu_data) => {
+                            let mut lsu_vault = self.component_vaults.get_mut(&lsu_data.resource_address).unwrap();
+                            (lsu_vault.take(lsu_data.amount), lsu_data.resource_address)
+                        },
+                        UnstakeNFTOrLSU::UnstakeNFT(unstake_nft_data) => {
+                            let unstake_nft_vault = self.component_vaults.get_mut(&unstake_nft_data.resource_address).unwrap();
+                            (unstake_nft_vault.as_non_fungible().take_non_fungible(&unstake_nft_data.id).into(), unstake_nft_data.resource_address)
+                        }
+                    };
+
+                    // The account refused the deposit, keep the fill for later and move on
+                    if let Some(refunded_bucket) = account.try_deposit_or_refund(fill_bucket, None) {
+                        self.component_vaults.get_mut(&fill_resource).unwrap().put(refunded_bucket);
+                        break;
+                    }
+
+                    match unstake_nft_or_lsu {
+                        UnstakeNFTOrLSU::LSU(lsu_data) => lsus_delivered.push((lsu_data.amount, lsu_data.resource_address)),
+                        UnstakeNFTOrLSU::UnstakeNFT(unstake_nft_data) => stake_claim_nfts_delivered.push(
+                            NonFungibleGlobalId::new(unstake_nft_data.resource_address, unstake_nft_data.id)
+                        ),
+                    }
+
+                    self.order_fill_tree.remove(&avl_key);
+                    fills_delivered += 1;
+
+                    // Pay the keeper out of the receipt's claimable balance
+                    if self.delivery_fee > dec!(0) {
+                        total_delivery_fees.put(self.claimable_xrd.take(self.delivery_fee).as_fungible());
+                        keeper_fee += self.delivery_fee;
+                        self.total_xrd_claimable -= self.delivery_fee;
+                        self.liquidity_data.get_mut(&global_id).unwrap().claimable_xrd_balance -= self.delivery_fee;
+                    }
+                }
+
+                if fills_delivered == 0 {
+                    continue;
+                }
+
+                let mut kvs_data = self.liquidity_data.get_mut(&global_id).unwrap();
+                kvs_data.fills_to_collect = kvs_data.fills_to_collect.saturating_sub(fills_delivered);
+                kvs_data.lsu_fills_unbonding = kvs_data.lsu_fills_unbonding.saturating_sub(stake_claim_nfts_delivered.len() as u64);
+                delivered_total += fills_delivered;
+
+                Runtime::emit_event(FillsDeliveredEvent {
+                    receipt_id,
+                    delivery_account,
+                    fills_delivered,
+                    keeper_fee,
+                    lsus_delivered,
+                    stake_claim_nfts_delivered,
+                });
+            }
+
+            total_delivery_fees
+        }
+
+        /// Appends a receipt to the automated liquidity registry and records its slot.
+        fn register_automation(&mut self, global_id: &NonFungibleGlobalId) {
+            self.automated_liquidity.insert(self.automated_liquidity_index, global_id.clone());
+            self.automated_liquidity_slots.insert(global_id.clone(), self.automated_liquidity_index);
+            self.automated_liquidity_index += 1;
+        }
+
+        /// Removes a receipt from the automated liquidity registry in constant time by moving the last
+        /// entry into the freed slot.
+        fn deregister_automation(&mut self, global_id: &NonFungibleGlobalId) {
+            let index_to_remove = match self.automated_liquidity_slots.remove(global_id) {
+                Some(slot) => slot,
+                None => return,
+            };
+            let last_index = self.automated_liquidity_index - 1;
+
+            self.automated_liquidity.remove(&index_to_remove);
+            if index_to_remove != last_index {
+                let last_entry = self.automated_liquidity.remove(&last_index).expect("Last entry should exist");
+                self.automated_liquidity_slots.insert(last_entry.clone(), index_to_remove);
+                self.automated_liquidity.insert(index_to_remove, last_entry);
+            }
+
+            self.automated_liquidity_index -= 1;
+        }
+
+        /// Returns true for a non-zero amount of available liquidity below the dust threshold.
+        fn is_xrd_dust(&self, amount: Decimal) -> bool {
+            amount > dec!(0) && amount < self.dust_threshold
+        }
+
+        /// Moves the liquidity left under one of a receipt's buy list keys to its claimable XRD balance,
+        /// keeping the liquidity index and `total_xrd_locked` in step and removing the key from the buy list.
+        fn move_available_to_claimable(&mut self, global_id: &NonFungibleGlobalId, nft_data: &LiquidityReceipt, key: u128) {
+            let residue = self.liquidity_data.get(global_id).unwrap().key_liquidity_available(key);
+            if residue <= dec!(0) {
+                return;
+            }
+            self.debit_buy_list_key(global_id, key, residue);
+            self.liquidity_data.get_mut(global_id).unwrap().claimable_xrd_balance += residue;
+
+            self.update_liquidity_index(BuyListKey::discount(key), nft_data.auto_unstake, -residue);
+            self.total_xrd_locked -= residue;
+            self.total_xrd_claimable += residue;
+
+            let residue_bucket = self.xrd_liquidity.take(residue);
+            self.claimable_xrd.put(residue_bucket);
+
+            Runtime::emit_event(ClaimableXrdCreditedEvent {
+                receipt_id: global_id.local_id().clone(),
+                xrd_amount: residue,
+            });
+        }
+
+        /// Takes `amount` off the liquidity queued under one of a receipt's buy list keys, removing the key
+        /// from the buy list once it is empty. When the original position empties the oldest sub-position
+        /// takes its place. The caller keeps the liquidity index and totals in step.
+        fn debit_buy_list_key(&mut self, global_id: &NonFungibleGlobalId, key: u128, amount: Decimal) {
+            let mut kvs_data = self.liquidity_data.get_mut(global_id).unwrap();
+            kvs_data.xrd_liquidity_available -= amount;
+
+            let emptied = match kvs_data.sub_positions.iter().position(|sub_position| sub_position.buy_list_key == key) {
+                Some(index) => {
+                    kvs_data.sub_positions[index].xrd_liquidity_available -= amount;
+                    if kvs_data.sub_positions[index].xrd_liquidity_available <= dec!(0) {
+                        kvs_data.sub_positions.remove(index);
+                        true
+                    } else {
+                        false
+                    }
+                },
+                None => {
+                    let original_available = kvs_data.xrd_liquidity_available - kvs_data.sub_position_liquidity();
+                    if original_available <= dec!(0) {
+                        kvs_data.primary_buy_list_key = if kvs_data.sub_positions.is_empty() {
+                            None
+                        } else {
+                            Some(kvs_data.sub_positions.remove(0).buy_list_key)
+                        };
+                    }
+                    original_available <= dec!(0)
+                },
+            };
+            drop(kvs_data);
+
+            if emptied && self.buy_list.get(&key).is_some() {
+                self.buy_list.remove(&key);
+            }
+        }
+
+        /// The buy list key of a receipt's original position, as tracked in its liquidity data.
+        fn primary_buy_list_key(&self, global_id: &NonFungibleGlobalId) -> Option<u128> {
+            self.liquidity_data.get(global_id).unwrap().primary_buy_list_key
+        }
+
+        /// Removes all of a receipt's buy list keys and clears its sub-positions. The available liquidity
+        /// is left as it is for the caller to requeue or withdraw.
+        fn remove_buy_list_keys(&mut self, global_id: &NonFungibleGlobalId) {
+            let mut kvs_data = self.liquidity_data.get_mut(global_id).unwrap();
+            let primary_key = kvs_data.primary_buy_list_key.take();
+            let sub_position_keys: Vec<u128> = kvs_data
+                .sub_positions
+                .drain(..)
+                .map(|sub_position| sub_position.buy_list_key)
+                .collect();
+            drop(kvs_data);
+
+            for key in sub_position_keys.into_iter().chain(primary_key) {
+                self.buy_list.remove(&key);
+            }
+        }
+
+        /// The discounts a receipt's liquidity is spread across with their weights. Positions that are not
+        /// ladders have a single rung at the receipt's discount.
+        fn position_rungs(nft_data: &LiquidityReceipt, kvs_data: &LiquidityData) -> Vec<(Decimal, Decimal)> {
+            if kvs_data.ladder_weights.is_empty() {
+                vec![(nft_data.discount, dec!(1))]
+            } else {
+                kvs_data.ladder_weights.clone()
+            }
+        }
+
+        /// Adds `amount` to the liquidity index of the tier at `discount`. Negative amounts remove liquidity.
+        fn update_liquidity_index(&mut self, discount: Decimal, auto_unstake: bool, amount: Decimal) {
+            let index_usize = (discount / dec!(0.00025)).checked_floor().unwrap().to_string().parse::<usize>().unwrap();
+            if auto_unstake {
+                self.liquidity_index_auto_unstake_true[index_usize] += amount;
+            } else {
+                self.liquidity_index_auto_unstake_false[index_usize] += amount;
+            }
+        }
+
+        /// Takes all of a receipt's available liquidity out of the liquidity index, tier by tier.
+        fn remove_receipt_from_liquidity_index(&mut self, global_id: &NonFungibleGlobalId, nft_data: &LiquidityReceipt) {
+            let kvs_data = self.liquidity_data.get(global_id).unwrap();
+            let original_available = kvs_data.xrd_liquidity_available - kvs_data.sub_position_liquidity();
+            let sub_positions = kvs_data.sub_positions.clone();
+            drop(kvs_data);
+
+            let original_discount = match self.primary_buy_list_key(global_id) {
+                Some(key) => BuyListKey::discount(key),
+                None => nft_data.discount,
+            };
+            self.update_liquidity_index(original_discount, nft_data.auto_unstake, -original_available);
+            for sub_position in sub_positions {
+                self.update_liquidity_index(sub_position.discount, nft_data.auto_unstake, -sub_position.xrd_liquidity_available);
+            }
+        }
+
+        /// Inserts a new buy list key for a receipt at the back of the tier at `discount` and adds `amount` to
+        /// the liquidity index. With `as_sub_position` the key is tracked as a sub-position, otherwise it becomes
+        /// the receipt's original position. The caller has already added `amount` to the available liquidity.
+        fn queue_buy_list_key(
+            &mut self,
+            global_id: &NonFungibleGlobalId,
+            nft_data: &LiquidityReceipt,
+            discount: Decimal,
+            amount: Decimal,
+            as_sub_position: bool
+            ) -> u128 {
+
+            let discount_basis_points = match (discount * dec!(10000)).checked_floor() {
+                Some(val) => match val.to_string().parse::<u32>() {
+                    Ok(points) => points as u16,
+                    Err(_) => panic!("Failed to parse discount basis points")
+                },
+                None => panic!("Failed to convert discount to basis points")
+            };
+            let receipt_id_u64 = match global_id.local_id().clone() {
+                NonFungibleLocalId::Integer(i) => i.value(),
+                _ => panic!("Invalid NFT ID type")
+            };
+            let buy_list_key = BuyListKey::new(discount_basis_points, nft_data.auto_unstake, self.avl_position_counter, receipt_id_u64);
+            self.avl_position_counter += 1;
+
+            let mut kvs_data = self.liquidity_data.get_mut(global_id).unwrap();
+            if as_sub_position {
+                kvs_data.sub_positions.push(SubPosition {
+                    buy_list_key,
+                    discount,
+                    xrd_liquidity_available: amount,
+                });
+            } else {
+                kvs_data.primary_buy_list_key = Some(buy_list_key);
+            }
+            drop(kvs_data);
+            self.buy_list.insert(buy_list_key, global_id.clone());
+            self.update_liquidity_index(discount, nft_data.auto_unstake, amount);
+
+            buy_list_key
+        }
+
+        /// Adds `additional_xrd` to a receipt's available liquidity without moving the liquidity already queued.
+        /// The XRD is split across the position's rungs by weight and each part is queued as a sub-position at
+        /// the back of its tier, or becomes the receipt's position if it has none left. Once the receipt holds
+        /// `MAX_SUB_POSITIONS` sub-positions, a part is folded into the newest sub-position at its discount,
+        /// which moves to the back with it, so repeated top ups and refills can't grow the buy list without
+        /// bound. The caller keeps `total_xrd_locked` and the vault in step.
+        /// 
+        /// # Returns
+        /// * The new buy list keys with their discounts
+        fn queue_additional_liquidity(&mut self, global_id: &NonFungibleGlobalId, nft_data: &LiquidityReceipt, additional_xrd: Decimal) -> Vec<(u128, Decimal)> {
+            let mut kvs_data = self.liquidity_data.get_mut(global_id).unwrap();
+            let mut has_position = kvs_data.primary_buy_list_key.is_some();
+            let rungs = Self::position_rungs(nft_data, &kvs_data);
+            kvs_data.xrd_liquidity_available += additional_xrd;
+            kvs_data.last_added_epoch = Runtime::current_epoch().number() as u32;
+            drop(kvs_data);
+
+            let mut new_buy_list_keys: Vec<(u128, Decimal)> = Vec::new();
+            for (discount, amount) in split_by_weight(additional_xrd, &rungs) {
+                if amount <= dec!(0) {
+                    continue;
+                }
+
+                let mut xrd_to_queue = amount;
+                let kvs_data = self.liquidity_data.get(global_id).unwrap();
+                let folded_index = if kvs_data.sub_positions.len() >= MAX_SUB_POSITIONS {
+                    kvs_data.sub_positions.iter().rposition(|sub_position| sub_position.discount == discount)
+                } else {
+                    None
+                };
+                drop(kvs_data);
+
+                if let Some(index) = folded_index {
+                    let sub_position = self.liquidity_data.get_mut(global_id).unwrap().sub_positions.remove(index);
+                    self.buy_list.remove(&sub_position.buy_list_key);
+                    self.update_liquidity_index(discount, nft_data.auto_unstake, -sub_position.xrd_liquidity_available);
+                    xrd_to_queue += sub_position.xrd_liquidity_available;
+                }
+
+                let buy_list_key = self.queue_buy_list_key(global_id, nft_data, discount, xrd_to_queue, has_position);
+                has_position = true;
+                new_buy_list_keys.push((buy_list_key, discount));
+            }
+
+            new_buy_list_keys
+        }
+
+        /// Moves all of a receipt's available liquidity plus `additional_xrd` to the back of the queue. Each
+        /// discount keeps what it still holds and only `additional_xrd` is split across the position's rungs by
+        /// weight, so fills already taken from one rung of a ladder are not spread over the others. Liquidity
+        /// at the same discount is queued under one key. The caller keeps `total_xrd_locked` and the vault in step.
+        /// 
+        /// # Returns
+        /// * The new buy list keys with their discounts
+        fn requeue_liquidity(&mut self, global_id: &NonFungibleGlobalId, nft_data: &LiquidityReceipt, additional_xrd: Decimal) -> Vec<(u128, Decimal)> {
+            let kvs_data = self.liquidity_data.get(global_id).unwrap();
+            let original_discount = match kvs_data.primary_buy_list_key {
+                Some(key) => BuyListKey::discount(key),
+                None => nft_data.discount,
+            };
+            let mut discount_amounts: Vec<(Decimal, Decimal)> = vec![(original_discount, kvs_data.xrd_liquidity_available - kvs_data.sub_position_liquidity())];
+            for sub_position in kvs_data.sub_positions.iter() {
+                discount_amounts.push((sub_position.discount, sub_position.xrd_liquidity_available));
+            }
+            let rungs = Self::position_rungs(nft_data, &kvs_data);
+            drop(kvs_data);
+
+            // Fold the new XRD and any sub-positions at the same discount together
+            let mut xrd_to_queue: Vec<(Decimal, Decimal)> = Vec::new();
+            for (discount, amount) in discount_amounts.into_iter().chain(split_by_weight(additional_xrd, &rungs)) {
+                match xrd_to_queue.iter_mut().find(|(queued_discount, _)| *queued_discount == discount) {
+                    Some((_, queued_amount)) => *queued_amount += amount,
+                    None => xrd_to_queue.push((discount, amount)),
+                }
+            }
+
+            self.remove_receipt_from_liquidity_index(global_id, nft_data);
+            self.remove_buy_list_keys(global_id);
+
+            let mut kvs_data = self.liquidity_data.get_mut(global_id).unwrap();
+            kvs_data.xrd_liquidity_available += additional_xrd;
+            kvs_data.last_added_epoch = Runtime::current_epoch().number() as u32;
+            drop(kvs_data);
+
+            let mut new_buy_list_keys: Vec<(u128, Decimal)> = Vec::new();
+            for (discount, amount) in xrd_to_queue {
+                if amount <= dec!(0) {
+                    continue;
+                }
+                let as_sub_position = !new_buy_list_keys.is_empty();
+                let buy_list_key = self.queue_buy_list_key(global_id, nft_data, discount, amount, as_sub_position);
+                new_buy_list_keys.push((buy_list_key, discount));
+            }
+
+            new_buy_list_keys
+        }
+
+        /// Withdraws the claimable XRD balance of a liquidity receipt.
+        /// 
+        /// XRD that can't be kept as available liquidity, such as dust left over after a fill, is credited
+        /// to the receipt's claimable balance instead of being left in a shared vault. This method pays that
+        /// balance out to the holder of the receipt.
+        /// 
+        /// # Arguments
+        /// * `receipt_proof`: A `Proof` of the liquidity receipt NFT
+        ///
+        /// # Returns
+        /// * A `Bucket` containing the receipt's claimable XRD
+        pub fn withdraw_claimable(&mut self, receipt_proof: Proof) -> Bucket {
+            let checked_proof = receipt_proof.check_with_message(self.liquidity_receipt.address(), "Proof must be of a Liquify liquidity receipt");
+            let local_id = checked_proof.as_non_fungible().non_fungible_local_id();
+            let global_id = NonFungibleGlobalId::new(self.liquidity_receipt.address(), local_id.clone());
+
+            let mut kvs_data = self.liquidity_data.get_mut(&global_id).unwrap();
+            let xrd_amount = kvs_data.claimable_xrd_balance;
+            assert!(xrd_amount > dec!(0), "No claimable XRD for this receipt");
+            kvs_data.claimable_xrd_balance = dec!(0);
+            drop(kvs_data);
+
+            self.total_xrd_claimable -= xrd_amount;
+
+            Runtime::emit_event(ClaimableXrdWithdrawnEvent {
+                receipt_id: local_id,
+                xrd_amount,
+            });
+
+            self.claimable_xrd.take(xrd_amount)
+        }
+
+        /// Adds XRD to the claimable balance of a liquidity receipt.
+        /// 
+        /// The claimable balance pays the keeper fee of `deliver_fills`, so a liquidity provider that has
+        /// registered a delivery account funds it here instead of giving up available liquidity. Any part
+        /// that isn't spent on fees can be taken back with `withdraw_claimable`.
+        /// 
+        /// # Arguments
+        /// * `receipt_proof`: A `Proof` of the liquidity receipt NFT
+        /// * `xrd_bucket`: A `Bucket` containing the XRD to add
+        ///
+        /// # Returns
+        /// * None
+        pub fn deposit_claimable(&mut self, receipt_proof: Proof, xrd_bucket: Bucket) {
+            let checked_proof = receipt_proof.check_with_message(self.liquidity_receipt.address(), "Proof must be of a Liquify liquidity receipt");
+            assert!(xrd_bucket.resource_address() == XRD, "Bucket must contain XRD");
+            assert!(xrd_bucket.amount() > dec!(0), "Must deposit a positive amount of XRD");
+
+            let local_id = checked_proof.as_non_fungible().non_fungible_local_id();
+            let global_id = NonFungibleGlobalId::new(self.liquidity_receipt.address(), local_id.clone());
+            let xrd_amount = xrd_bucket.amount();
+
+            self.liquidity_data.get_mut(&global_id).unwrap().claimable_xrd_balance += xrd_amount;
+            self.total_xrd_claimable += xrd_amount;
+            self.claimable_xrd.put(xrd_bucket);
+
+            Runtime::emit_event(ClaimableXrdCreditedEvent {
+                receipt_id: local_id,
+                xrd_amount,
+            });
+        }
+
+        /// Places a resting LSU sell order with a maximum acceptable discount.
+        /// 
+        /// This method lets an LSU holder sell at a chosen minimum price instead of taking whatever discount
+        /// is currently available. The LSUs are first matched against the buy list for up to `max_iterations`
+        /// positions whose discount does not exceed `max_discount`. Any LSUs left over rest in the sell list
+        /// and are matched when `add_liquidity` or `increase_liquidity` brings in a compatible bid. Sell orders
+        /// accepting the deepest discount are matched first, then oldest first. A sell order receipt NFT is
+        /// minted for any resting remainder and is used to collect proceeds or cancel the order.
+        /// 
+        /// # Arguments
+        /// * `lsu_bucket`: A `FungibleBucket` containing native Radix validator LSUs
+        /// * `max_discount`: A `Decimal` representing the deepest discount the seller is willing to accept
+        /// * `max_iterations`: A `u8` limiting the number of liquidity positions checked for an immediate match
+        /// * `seller_badge`: An optional `Proof` of the seller's own liquidity receipts, which won't be matched now or later
+        ///
+        /// # Returns
+        /// * A tuple containing:
+        ///   - `Bucket`: XRD received from the immediate match (minus platform fee)
+        ///   - `Bucket`: The sell order receipt NFT, or an empty bucket if the order was filled immediately
+        pub fn create_sell_order(&mut self, lsu_bucket: FungibleBucket, max_discount: Decimal, max_iterations: u8, seller_badge: Option<Proof>) -> (Bucket, Bucket) {
+            assert!(self.component_status == true, "Liquify is not accepting new sell orders at this time.");
+            assert!(self.validate_lsu(lsu_bucket.resource_address()), "Bucket must contain a native Radix Validator LSU");
+            assert!(self.discounts.contains(&max_discount), "This discount % is not supported");
+
+            // Pre-calculate total value and validate min/max
+            let validator = self.get_validator_from_lsu(lsu_bucket.resource_address());
+            let redemption_rate = validator.get_redemption_value(dec!(1));
+            let total_lsu_value = lsu_bucket.amount() * redemption_rate;
+
+            assert!(
+                total_lsu_value >= self.minimum_unstake_value,
+                "Unstake value of {} XRD is below minimum requirement of {} XRD",
+                total_lsu_value,
+                self.minimum_unstake_value
+            );
+            assert!(
+                total_lsu_value <= self.maximum_unstake_value,
+                "Unstake value of {} XRD exceeds maximum limit of {} XRD",
+                total_lsu_value,
+                self.maximum_unstake_value
+            );
+
+            let max_discount_basis_points: u16 = match (max_discount * dec!(10000)).checked_floor() {
+                Some(val) => match val.to_string().parse::<u16>() {
+                    Ok(points) => points,
+                    Err(_) => panic!("Failed to parse discount basis points")
+                },
+                None => panic!("Failed to convert discount to basis points")
+            };
+
+            let seller_receipts = self.seller_receipt_ids(seller_badge);
+
+            // Take whatever the book already offers within the seller's limit, positions priced deeper
+            // than their tier by a validator override are skipped in `process_unstake`
+            let is_small_order = total_lsu_value < self.small_order_threshold;
+            let mut order_keys = Vec::new();
+
+            for (avl_key, _, _) in self.buy_list.range(0..u128::MAX) {
+                if order_keys.len() >= max_iterations as usize {
+                    break;
+                }
+
+                // The buy list is ordered by discount, so nothing further can be within the limit
+                if (avl_key >> 112) as u16 > max_discount_basis_points {
+                    break;
+                }
+
+                if is_small_order && BuyListKey::extract_auto_unstake(avl_key) {
+                    continue;
+                }
+
+                order_keys.push(avl_key);
+            }
+
+            let (xrd_bucket, remaining_lsu) = if order_keys.is_empty() {
+                (Bucket::new(XRD), lsu_bucket)
+            } else {
+                self.process_unstake(lsu_bucket, order_keys, UnstakeExecutionMode::PartialAllowed, &seller_receipts, Some(max_discount))
+            };
+
+            if remaining_lsu.is_empty() {
+                return (xrd_bucket, Bucket::new(self.sell_order_receipt.address()));
+            }
+
+            // Rest the remainder in the sell list
+            let lsu_resource = remaining_lsu.resource_address();
+            let lsu_amount = remaining_lsu.amount();
+            let id = NonFungibleLocalId::Integer(IntegerNonFungibleLocalId::new(self.sell_order_receipt_counter));
+            let sell_list_key = SellListKey::new(max_discount_basis_points, self.avl_position_counter, self.sell_order_receipt_counter);
+            self.avl_position_counter += 1;
+            self.sell_order_receipt_counter += 1;
+
+            let sell_order_receipt: NonFungibleBucket = self.sell_order_receipt.mint_non_fungible(&id, SellOrderReceipt {
+                key_image_url: self.receipt_image_url.clone(),
+                lsu_resource,
+                max_discount,
+            });
+
+            let global_id = NonFungibleGlobalId::new(self.sell_order_receipt.address(), id.clone());
+            self.sell_order_data.insert(global_id.clone(), SellOrderData {
+                lsu_resource,
+                max_discount,
+                lsu_remaining: lsu_amount,
+                lsu_sold: dec!(0),
+                xrd_proceeds: dec!(0),
+                sell_list_key,
+                seller_receipts: seller_receipts.into_iter().collect(),
+            });
+            self.sell_list.insert(sell_list_key, global_id);
+
+            if !self.sell_order_vaults.get(&lsu_resource).is_some() {
+                self.sell_order_vaults.insert(lsu_resource, Vault::new(lsu_resource));
+            }
+            self.sell_order_vaults.get_mut(&lsu_resource).unwrap().as_fungible().put(remaining_lsu);
+
+            Runtime::emit_event(SellOrderCreatedEvent {
+                sell_order_id: id,
+                lsu_resource,
+                lsu_amount,
+                max_discount,
+            });
+
+            (xrd_bucket, sell_order_receipt.into())
+        }
+
+        /// Cancels a resting sell order and closes it.
+        /// 
+        /// This method returns any LSUs that have not been sold together with all uncollected XRD proceeds.
+        /// The order is removed from the sell list and the sell order receipt NFT is burned.
+        /// 
+        /// # Arguments
+        /// * `sell_order_receipt_bucket`: A `Bucket` containing exactly one sell order receipt NFT
+        ///
+        /// # Returns
+        /// * A tuple containing:
+        ///   - `Bucket`: The uncollected XRD proceeds of the order
+        ///   - `Bucket`: The unsold LSUs of the order
+        pub fn cancel_sell_order(&mut self, sell_order_receipt_bucket: Bucket) -> (Bucket, Bucket) {
+            assert!(sell_order_receipt_bucket.resource_address() == self.sell_order_receipt.address(), "Bucket must contain a Liquify sell order receipt");
+            assert!(sell_order_receipt_bucket.amount() == dec!(1), "Must provide exactly one sell order receipt");
+
+            let local_id = sell_order_receipt_bucket.as_non_fungible().non_fungible_local_id();
+            let global_id = NonFungibleGlobalId::new(self.sell_order_receipt.address(), local_id.clone());
+            let order = self.sell_order_data.get(&global_id).unwrap().clone();
+
+            if order.lsu_remaining > dec!(0) {
+                self.sell_list.remove(&order.sell_list_key);
+            }
+
+            let lsu_bucket: Bucket = self.sell_order_vaults.get_mut(&order.lsu_resource).unwrap().take(order.lsu_remaining);
+            let xrd_bucket = self.sell_order_xrd.take(order.xrd_proceeds);
+
+            self.sell_order_data.remove(&global_id);
+            sell_order_receipt_bucket.burn();
+
+            Runtime::emit_event(SellOrderCancelledEvent {
+                sell_order_id: local_id,
+                lsu_returned: order.lsu_remaining,
+                xrd_returned: order.xrd_proceeds,
+            });
+
+            (xrd_bucket, lsu_bucket)
+        }
+
+        /// Collects the XRD proceeds of a sell order.
+        /// 
+        /// This method withdraws all XRD the order has received from fills so far. The order keeps resting
+        /// in the sell list if it still holds unsold LSUs.
+        /// 
+        /// # Arguments
+        /// * `sell_order_receipt_bucket`: A `Bucket` containing exactly one sell order receipt NFT
+        ///
+        /// # Returns
+        /// * A tuple containing:
+        ///   - `Bucket`: The collected XRD proceeds
+        ///   - `Bucket`: The sell order receipt NFT (returned unchanged)
+        pub fn collect_sell_order_proceeds(&mut self, sell_order_receipt_bucket: Bucket) -> (Bucket, Bucket) {
+            assert!(sell_order_receipt_bucket.resource_address() == self.sell_order_receipt.address(), "Bucket must contain a Liquify sell order receipt");
+            assert!(sell_order_receipt_bucket.amount() == dec!(1), "Must provide exactly one sell order receipt");
+
+            let local_id = sell_order_receipt_bucket.as_non_fungible().non_fungible_local_id();
+            let global_id = NonFungibleGlobalId::new(self.sell_order_receipt.address(), local_id);
+            let mut order = self.sell_order_data.get_mut(&global_id).unwrap();
+
+            let xrd_bucket = self.sell_order_xrd.take(order.xrd_proceeds);
+            order.xrd_proceeds = dec!(0);
+
+            (xrd_bucket, sell_order_receipt_bucket)
+        }
+
+        /// Matches resting sell orders against a single buy list position.
+        /// 
+        /// Sell orders are walked from the deepest accepted discount down and each one is sold into the
+        /// position through `process_unstake`, until the position is used up, the remaining sell orders no
+        /// longer accept the discount, or `max_sell_order_matches` is reached. An order is skipped when the
+        /// discount the position charges for its validator, including any override, exceeds its limit.
+        /// A position held by the seller is treated like any other self-match under `self_match_prevention`.
+        /// Like a direct unstake, an order worth less than `small_order_threshold` skips auto_unstake positions.
+        fn match_sell_orders(&mut self, buy_list_key: u128, discount: Decimal) {
+            let mut sell_keys: Vec<u128> = Vec::new();
+
+            for (sell_key, sell_order_id, _) in self.sell_list.range(0..u128::MAX) {
+                if sell_keys.len() as u64 >= self.max_sell_order_matches {
+                    break;
+                }
+
+                let max_discount = self.sell_order_data.get(&sell_order_id).unwrap().max_discount;
+                if max_discount < discount {
+                    break;
+                }
+
+                sell_keys.push(sell_key);
+            }
+
+            for sell_key in sell_keys {
+                // Stop once the bid has been completely filled
+                if self.buy_list.get(&buy_list_key).is_none() {
+                    break;
+                }
+
+                let sell_order_id = self.sell_list.get(&sell_key).unwrap().clone();
+                let (lsu_resource, lsu_remaining, max_discount, seller_receipts) = {
+                    let order = self.sell_order_data.get(&sell_order_id).unwrap();
+                    (order.lsu_resource, order.lsu_remaining, order.max_discount, order.seller_receipts.iter().cloned().collect::<std::collections::HashSet<NonFungibleGlobalId>>())
+                };
+
+                if BuyListKey::extract_auto_unstake(buy_list_key) {
+                    let order_value = self.get_validator_from_lsu(lsu_resource).get_redemption_value(lsu_remaining);
+                    if order_value < self.small_order_threshold {
+                        continue;
+                    }
+                }
+
+                let lsu_bucket = self.sell_order_vaults.get_mut(&lsu_resource).unwrap().as_fungible().take(lsu_remaining);
+                let (xrd_bucket, remaining_lsu) = self.process_unstake(lsu_bucket, vec![buy_list_key], UnstakeExecutionMode::PartialAllowed, &seller_receipts, Some(max_discount));
+
+                let lsu_sold = lsu_remaining - remaining_lsu.amount();
+                let xrd_received = xrd_bucket.amount();
+                self.sell_order_xrd.put(xrd_bucket);
+                self.sell_order_vaults.get_mut(&lsu_resource).unwrap().as_fungible().put(remaining_lsu);
+
+                let mut order = self.sell_order_data.get_mut(&sell_order_id).unwrap();
+                order.lsu_remaining -= lsu_sold;
+                order.lsu_sold += lsu_sold;
+                order.xrd_proceeds += xrd_received;
+
+                if order.lsu_remaining.is_zero() {
+                    self.sell_list.remove(&sell_key);
+                }
+
+                Runtime::emit_event(SellOrderFilledEvent {
+                    sell_order_id: sell_order_id.local_id().clone(),
+                    lsu_amount: lsu_sold,
+                    xrd_amount: xrd_received,
+                });
+            }
         }
-                
+
         /// Collects accumulated platform fees.
         /// 
         /// This method allows the component owner to withdraw all platform fees that have been collected
@@ -1635,6 +3511,154 @@ mod liquify_module {
             self.small_order_threshold = threshold;
         }
 
+        /// Sets how many resting sell orders a new bid may match.
+        /// 
+        /// This method allows the owner to bound the work `add_liquidity` and `increase_liquidity` perform
+        /// when matching resting sell orders, keeping those transactions within cost limits. Only the holder
+        /// of the owner badge can call this method.
+        /// 
+        /// # Arguments
+        /// * `max_matches`: A `u64` representing the maximum number of sell orders matched per bid
+        ///
+        /// # Returns
+        /// * None
+        pub fn set_max_sell_order_matches(&mut self, max_matches: u64) {
+            self.max_sell_order_matches = max_matches;
+        }
+
+        /// Sets the unbonding period used for claim NFT and annualized pricing.
+        /// 
+        /// This method allows the owner to keep the number of epochs between `validator.unstake` and the
+        /// claim epoch in line with the network configuration. It is used to scale the discount applied to
+        /// claim NFTs sold through `liquify_claim_nft` and to convert annualized rates into discounts. The
+        /// unbonding delay is part of the consensus manager's configuration, which neither the `Validator`
+        /// blueprint nor an LSU exposes to a component, so it can't be read when pricing LSUs that have not
+        /// been unstaked yet. Claim NFTs carry their own `claim_epoch` and are priced from that. Only the
+        /// holder of the owner badge can call this method.
+        /// 
+        /// # Arguments
+        /// * `epochs`: A `u64` representing the network unbonding period in epochs
+        ///
+        /// # Returns
+        /// * None
+        pub fn set_unbonding_epochs(&mut self, epochs: u64) {
+            assert!(epochs > 0, "Unbonding epochs must be greater than zero");
+            self.unbonding_epochs = epochs;
+        }
+
+        /// Enables or disables time-adjusted pricing of claim NFTs.
+        /// 
+        /// When enabled, `liquify_claim_nft` scales each position's discount by the epochs remaining until the
+        /// NFT's claim epoch relative to `unbonding_epochs`, so NFTs close to maturity are bought at a smaller
+        /// discount. When disabled, every claim NFT is priced as if it had a full unbonding period left. Only
+        /// the holder of the owner badge can call this method.
+        /// 
+        /// # Arguments
+        /// * `enabled`: A `bool` indicating whether claim NFT discounts are scaled by time to maturity
+        ///
+        /// # Returns
+        /// * None
+        pub fn set_claim_nft_time_adjustment(&mut self, enabled: bool) {
+            self.claim_nft_time_adjustment = enabled;
+        }
+
+        /// Sets the dust threshold for positions in the buy list.
+        /// 
+        /// This method allows the owner to set the smallest amount of available liquidity a position may
+        /// keep after a fill. A fill that would leave less is handled according to the dust policy and the
+        /// position leaves the buy list, so it no longer costs iterations. Only the holder of the owner badge
+        /// can call this method.
+        /// 
+        /// # Arguments
+        /// * `threshold`: A `Decimal` representing the dust threshold in XRD
+        ///
+        /// # Returns
+        /// * None
+        pub fn set_dust_threshold(&mut self, threshold: Decimal) {
+            assert!(threshold >= dec!(0), "Dust threshold cannot be negative");
+            self.dust_threshold = threshold;
+        }
+
+        /// Sets what happens to the dust left in a position after a fill.
+        /// 
+        /// With `SweepIntoFill` the remaining liquidity is added to the fill and paid to the seller. With
+        /// `MoveToClaimable` it is credited to the receipt's claimable XRD balance. Only the holder of the
+        /// owner badge can call this method.
+        /// 
+        /// # Arguments
+        /// * `policy`: The `DustPolicy` to apply
+        ///
+        /// # Returns
+        /// * None
+        pub fn set_dust_policy(&mut self, policy: DustPolicy) {
+            self.dust_policy = policy;
+        }
+
+        /// Sets how `liquify_unstake_off_ledger` treats order keys that don't follow buy list priority.
+        /// 
+        /// With `Unrestricted` any keys are accepted. With `Strict(tolerance)` the keys must be in ascending
+        /// buy list order and may skip at most `tolerance` eligible positions ahead of the last key, otherwise
+        /// the transaction reverts. `Fallback(tolerance)` applies the same check but matches on-ledger in book
+        /// order instead of reverting. Only the holder of the owner badge can call this method.
+        /// 
+        /// # Arguments
+        /// * `policy`: The `OrderKeyPolicy` to apply
+        ///
+        /// # Returns
+        /// * None
+        pub fn set_order_key_policy(&mut self, policy: OrderKeyPolicy) {
+            self.order_key_policy = policy;
+        }
+
+        /// Sets whether unstakes skip liquidity receipts presented by the seller.
+        /// 
+        /// When enabled, receipts in the `seller_badge` proof passed to the `_with_mode` unstake methods are
+        /// never matched. When disabled they are matched as usual but their fills are flagged with `wash_fill`
+        /// in `OrderFillEvent`. Only the holder of the owner badge can call this method.
+        /// 
+        /// # Arguments
+        /// * `enabled`: A `bool` turning self-match prevention on or off
+        ///
+        /// # Returns
+        /// * None
+        pub fn set_self_match_prevention(&mut self, enabled: bool) {
+            self.self_match_prevention = enabled;
+        }
+
+        /// Sets how unstakes are matched between positions at the same discount.
+        /// 
+        /// With `Fifo` the oldest position in the tier is filled first. With `ProRata` each unstake that reaches
+        /// the tier is split across its positions in proportion to their available liquidity, limited to the
+        /// positions the unstake iterates over. Only the holder of the owner badge can call this method.
+        /// 
+        /// # Arguments
+        /// * `discount`: A `Decimal` for one of the supported discount tiers
+        /// * `policy`: The `MatchingPolicy` to apply to the tier
+        ///
+        /// # Returns
+        /// * None
+        pub fn set_tier_matching_policy(&mut self, discount: Decimal, policy: MatchingPolicy) {
+            let index = self.discounts.iter().position(|tier| *tier == discount).expect("This discount % is not supported");
+            self.tier_matching_policies[index] = policy;
+        }
+
+        /// Sets the per-fill keeper fee paid by `deliver_fills`.
+        /// 
+        /// This method allows the owner to adjust the XRD paid to whoever delivers a fill to a liquidity
+        /// provider's registered account. The fee is taken from the receipt's claimable XRD balance, and
+        /// delivery stops once that balance can no longer cover it. Only the holder of the owner badge can
+        /// call this method.
+        /// 
+        /// # Arguments
+        /// * `fee`: A `Decimal` representing the XRD paid per delivered fill
+        ///
+        /// # Returns
+        /// * None
+        pub fn set_delivery_fee(&mut self, fee: Decimal) {
+            assert!(fee >= dec!(0), "Delivery fee cannot be negative");
+            self.delivery_fee = fee;
+        }
+
         /// Sets the receipt NFT image URL.
         /// 
         /// This method allows the owner to update the image URL used for newly minted liquidity receipt
@@ -1672,6 +3696,180 @@ mod liquify_module {
             ComponentAddress::try_from(metadata).unwrap()
         }
 
+        /// Returns the discount a position bids for a claim maturing in `epochs_to_claim` epochs.
+        /// Annualized positions accrue their rate over those epochs, flat discounts are scaled
+        /// against a full unbonding period.
+        fn time_scaled_discount(&self, discount: Decimal, annualized_rate: Option<Decimal>, epochs_to_claim: u64) -> Decimal {
+            match annualized_rate {
+                Some(rate) => rate * Decimal::from(epochs_to_claim) / Decimal::from(EPOCHS_PER_YEAR),
+                None if epochs_to_claim == self.unbonding_epochs => discount,
+                None => discount * Decimal::from(epochs_to_claim) / Decimal::from(self.unbonding_epochs),
+            }
+        }
+
+        /// Returns the discount `process_unstake` charges a buy list key when unstaking LSUs of the given validator:
+        /// the receipt's override for that validator if it has one, otherwise the key's tier scaled over a full
+        /// unbonding period.
+        fn applied_discount(
+            &self,
+            key: u128,
+            global_id: &NonFungibleGlobalId,
+            nft_data: &LiquidityReceipt,
+            kvs_data: &LiquidityData,
+            validator_address: ComponentAddress
+        ) -> Decimal {
+            if kvs_data.discount_override_validators.contains(&validator_address) {
+                *self.discount_overrides.get(&DiscountOverrideKey {
+                    receipt_id: global_id.clone(),
+                    validator: validator_address,
+                }).unwrap()
+            } else {
+                self.time_scaled_discount(BuyListKey::discount(key), nft_data.annualized_rate, self.unbonding_epochs)
+            }
+        }
+
+        fn validate_unstake_nft(&self, input_nft_address: ResourceAddress) -> bool {
+            let validator_address = self.get_validator_from_unstake_nft(&input_nft_address);
+            let validator: Global<Validator> = Global::from(validator_address);
+
+            let claim_nft_address: GlobalAddress = validator
+                .get_metadata("claim_nft")
+                .unwrap()
+                .unwrap_or_else(|| Runtime::panic(String::from("Not an unstake NFT!")));
+
+            input_nft_address == ResourceAddress::try_from(claim_nft_address).unwrap()
+        }
+
+        /// Returns the matching policy for the tier a buy list key's discount basis points belong to.
+        /// Tiers are 2.5 basis points apart and keys store the floored value, so this rounds back up.
+        fn tier_matching_policy(&self, discount_basis_points: u16) -> MatchingPolicy {
+            let index = buy_list_key::tier_index(discount_basis_points);
+            self.tier_matching_policies.get(index).copied().unwrap_or(MatchingPolicy::Fifo)
+        }
+
+        /// Splits `lsu_amount` across the positions at the front of `order_keys` that share the given tier,
+        /// in proportion to their available liquidity. The last position takes the rounding remainder.
+        fn allocate_pro_rata(
+            &self,
+            order_keys: &[u128],
+            tier_basis_points: u16,
+            lsu_amount: Decimal,
+            seller_receipts: &std::collections::HashSet<NonFungibleGlobalId>,
+            allocations: &mut HashMap<u128, Decimal>
+        ) {
+            let mut tier_positions: Vec<(u128, Decimal)> = Vec::new();
+            let mut tier_total = dec!(0);
+
+            for key in order_keys.iter().take_while(|key| (**key >> 112) as u16 == tier_basis_points) {
+                let global_id = match self.buy_list.get(key) {
+                    Some(global_id) => global_id.clone(),
+                    None => continue,
+                };
+                if self.self_match_prevention && seller_receipts.contains(&global_id) {
+                    continue;
+                }
+
+                let xrd_available = self.liquidity_data.get(&global_id).unwrap().key_liquidity_available(*key);
+                tier_positions.push((*key, xrd_available));
+                tier_total += xrd_available;
+            }
+
+            if tier_total <= dec!(0) {
+                return;
+            }
+
+            let last_index = tier_positions.len() - 1;
+            let mut allocated = dec!(0);
+            for (index, (key, xrd_available)) in tier_positions.into_iter().enumerate() {
+                let share = if index == last_index {
+                    lsu_amount - allocated
+                } else {
+                    lsu_amount * xrd_available / tier_total
+                };
+                allocated += share;
+                allocations.insert(key, share);
+            }
+        }
+
+        /// Returns the liquidity receipts in an optional seller proof.
+        fn seller_receipt_ids(&self, seller_badge: Option<Proof>) -> std::collections::HashSet<NonFungibleGlobalId> {
+            let mut seller_receipts = std::collections::HashSet::new();
+
+            if let Some(proof) = seller_badge {
+                let checked_proof = proof.check_with_message(self.liquidity_receipt.address(), "Seller badge must be a Liquify liquidity receipt proof");
+                for local_id in checked_proof.as_non_fungible().non_fungible_local_ids() {
+                    seller_receipts.insert(NonFungibleGlobalId::new(self.liquidity_receipt.address(), local_id));
+                }
+            }
+
+            seller_receipts
+        }
+
+        /// Collects up to `max_keys` buy list keys in book order for on-ledger matching.
+        /// Small orders skip auto_unstake positions.
+        fn collect_order_keys(&self, is_small_order: bool, max_keys: usize) -> Vec<u128> {
+            let mut order_keys = Vec::new();
+
+            for (avl_key, _, _) in self.buy_list.range(0..u128::MAX) {
+                if order_keys.len() >= max_keys {
+                    break;
+                }
+
+                if is_small_order && BuyListKey::extract_auto_unstake(avl_key) {
+                    continue;
+                }
+
+                order_keys.push(avl_key);
+            }
+
+            order_keys
+        }
+
+        /// Checks that off-ledger order keys are strictly ascending and skip no more than `tolerance`
+        /// eligible buy list entries before the last key. Keys that are no longer in the buy list are
+        /// ignored, as they may have been filled since the caller's snapshot.
+        fn order_keys_follow_book(&self, order_keys: &[u128], is_small_order: bool, tolerance: u64) -> bool {
+            if !order_keys.windows(2).all(|pair| pair[0] < pair[1]) {
+                return false;
+            }
+
+            let last_key = match order_keys.last() {
+                Some(key) => *key,
+                None => return true,
+            };
+
+            let mut supplied = order_keys.iter().peekable();
+            let mut skipped = 0u64;
+
+            for (avl_key, _, _) in self.buy_list.range(0..=last_key) {
+                while supplied.next_if(|key| **key < avl_key).is_some() {}
+
+                if supplied.next_if(|key| **key == avl_key).is_some() {
+                    continue;
+                }
+
+                if is_small_order && BuyListKey::extract_auto_unstake(avl_key) {
+                    continue;
+                }
+
+                skipped += 1;
+                if skipped > tolerance {
+                    return false;
+                }
+            }
+
+            true
+        }
+
+        fn validate_execution_mode(&self, execution_mode: UnstakeExecutionMode) {
+            if let UnstakeExecutionMode::MinFillRatio(min_fill_ratio) = execution_mode {
+                assert!(
+                    min_fill_ratio >= dec!(0) && min_fill_ratio <= dec!(1),
+                    "Minimum fill ratio must be between 0 and 1"
+                );
+            }
+        }
+
         fn validate_lsu(&self, input_lsu_address: ResourceAddress) -> bool {
             let validator = self.get_validator_from_lsu(input_lsu_address);
 
@@ -1693,18 +3891,57 @@ mod liquify_module {
             let (claimable_xrd, total_fills, total_stake_claim_value, total_lsu_redemption_value) = 
                 self.calculate_claimable_xrd(&receipt_id);
             
-            let (xrd_liquidity_available, xrd_liquidity_filled, fills_to_collect, last_added_epoch) = 
+            let (xrd_liquidity_available, xrd_liquidity_filled, fills_to_collect, last_added_epoch, lsu_fills_unbonding, redemption_ready_epoch, mid_cycle, claimable_xrd_balance) = 
                 match self.liquidity_data.get(&global_id) {
                     Some(kvs_data) => (
                         kvs_data.xrd_liquidity_available,
                         kvs_data.xrd_liquidity_filled,
                         kvs_data.fills_to_collect,
                         kvs_data.last_added_epoch,
+                        kvs_data.lsu_fills_unbonding,
+                        kvs_data.redemption_ready_epoch,
+                        kvs_data.mid_cycle,
+                        kvs_data.claimable_xrd_balance,
                     ),
-                    None => (dec!(0), dec!(0), 0, 0)
+                    None => (dec!(0), dec!(0), 0, 0, 0, 0, false, dec!(0))
                 };
 
             let liquidity_ahead = self.calculate_liquidity_ahead(&receipt_id);
+
+            // The original position first, then liquidity queued behind it by increase_liquidity_keep_position
+            let sub_positions: Vec<SubPosition> = match self.liquidity_data.get(&global_id) {
+                Some(kvs_data) => {
+                    let mut sub_positions = Vec::new();
+                    let original_available = kvs_data.xrd_liquidity_available - kvs_data.sub_position_liquidity();
+                    drop(kvs_data);
+                    if let Some(buy_list_key) = self.primary_buy_list_key(&global_id) {
+                        sub_positions.push(SubPosition {
+                            buy_list_key,
+                            discount: BuyListKey::discount(buy_list_key),
+                            xrd_liquidity_available: original_available,
+                        });
+                    }
+                    sub_positions.extend(self.liquidity_data.get(&global_id).unwrap().sub_positions.iter().cloned());
+                    sub_positions
+                },
+                None => Vec::new()
+            };
+
+            let ladder_weights: Vec<(Decimal, Decimal)> = match self.liquidity_data.get(&global_id) {
+                Some(kvs_data) => kvs_data.ladder_weights.clone(),
+                None => Vec::new()
+            };
+
+            let discount_overrides: Vec<(ComponentAddress, Decimal)> = match self.liquidity_data.get(&global_id) {
+                Some(kvs_data) => kvs_data.discount_override_validators.iter().map(|validator| {
+                    let override_key = DiscountOverrideKey {
+                        receipt_id: global_id.clone(),
+                        validator: *validator,
+                    };
+                    (*validator, *self.discount_overrides.get(&override_key).unwrap())
+                }).collect(),
+                None => Vec::new()
+            };
             
             ReceiptDetailData {
                 receipt_id,
@@ -1721,7 +3958,63 @@ mod liquify_module {
                 total_stake_claim_value,
                 total_lsu_redemption_value,
                 automation_fee: nft_data.automation_fee, 
+                discount_overrides,
+                annualized_rate: nft_data.annualized_rate,
+                effective_discount: self.time_scaled_discount(nft_data.discount, nft_data.annualized_rate, self.unbonding_epochs),
+                lsu_fills_unbonding,
+                redemption_ready_epoch,
+                mid_cycle,
+                claimable_xrd_balance,
+                sub_positions,
+                ladder_weights,
+            }
+        }
+
+        /// Retrieves the details of a sell order.
+        /// 
+        /// # Arguments
+        /// * `sell_order_id`: A `NonFungibleLocalId` identifying the sell order receipt
+        ///
+        /// # Returns
+        /// * A `SellOrderData` with the order's limit, remaining and sold LSUs and uncollected proceeds
+        pub fn get_sell_order(&self, sell_order_id: NonFungibleLocalId) -> SellOrderData {
+            let global_id = NonFungibleGlobalId::new(self.sell_order_receipt.address(), sell_order_id);
+            self.sell_order_data.get(&global_id).unwrap().clone()
+        }
+
+        /// Returns the effective discount of every discount tier.
+        /// 
+        /// For each supported tier this reports the deepest discount `process_unstake` charges any position
+        /// listed at the tier, together with the liquidity listed at the tier. Flat positions charge the tier
+        /// itself, annualized positions charge their rate over the owner-set `unbonding_epochs`, which can be up
+        /// to one tier step deeper. Validator overrides are not included as they depend on the LSU being sold.
+        /// 
+        /// # Returns
+        /// * A `Vec<EffectiveDiscountTier>` with one entry per supported discount tier
+        pub fn get_effective_discount_tiers(&self) -> Vec<EffectiveDiscountTier> {
+            let mut tiers: Vec<EffectiveDiscountTier> = self.discounts.iter().enumerate().map(|(index, discount)| {
+                EffectiveDiscountTier {
+                    discount: *discount,
+                    effective_discount: *discount,
+                    xrd_liquidity: self.liquidity_index_auto_unstake_false[index] + self.liquidity_index_auto_unstake_true[index],
+                }
+            }).collect();
+
+            for (avl_key, global_id, _) in self.buy_list.range(0..u128::MAX) {
+                let nft_data: LiquidityReceipt = self.liquidity_receipt.get_non_fungible_data(global_id.local_id());
+                if nft_data.annualized_rate.is_none() {
+                    continue;
+                }
+
+                let discount = BuyListKey::discount(avl_key);
+                let index = (discount / dec!(0.00025)).checked_floor().unwrap().to_string().parse::<usize>().unwrap();
+                let effective_discount = self.time_scaled_discount(discount, nft_data.annualized_rate, self.unbonding_epochs);
+                if effective_discount > tiers[index].effective_discount {
+                    tiers[index].effective_discount = effective_discount;
+                }
             }
+
+            tiers
         }
 
         pub fn get_automation_ready_receipts(&self, start_index: u64, batch_size: u64) -> Vec<AutomationReadyReceipt> {
@@ -1736,10 +4029,14 @@ mod liquify_module {
                     let receipt_id = global_id.local_id().clone();
                     let nft_data: LiquidityReceipt = self.liquidity_receipt.get_non_fungible_data(&receipt_id);
                     
-                    // Calculate claimable XRD (only need first value)
-                    let (claimable_xrd, fills_to_collect, _, _) = self.calculate_claimable_xrd(&receipt_id);
+                    // Calculate claimable XRD, plus LSU fills waiting to be unstaked for receipts that keep LSUs
+                    let (claimable_xrd, fills_to_collect, _, lsu_redemption_value) = self.calculate_claimable_xrd(&receipt_id);
+                    let lsu_fills_pending = !nft_data.auto_unstake 
+                        && lsu_redemption_value > dec!(0)
+                        && lsu_redemption_value >= nft_data.refill_threshold;
+                    let mid_cycle = self.liquidity_data.get(&*global_id).map(|kvs_data| kvs_data.mid_cycle).unwrap_or(false);
                     
-                    if claimable_xrd >= nft_data.refill_threshold {
+                    if claimable_xrd >= nft_data.refill_threshold || lsu_fills_pending || (mid_cycle && claimable_xrd > dec!(0)) {
                         
                         ready_receipts.push(AutomationReadyReceipt {
                             receipt_id,
@@ -1822,6 +4119,190 @@ mod liquify_module {
             self.calculate_claimable_xrd(&receipt_id)
         }
 
+        /// Audits the component's XRD accounting over a page of the buy list.
+        /// 
+        /// This method recomputes the liquidity held by each buy list entry in `[start_index, start_index + count)`
+        /// from `liquidity_data` and returns the page sums together with the global figures they must add up
+        /// to: the `xrd_liquidity` vault balance, `total_xrd_locked` and the totals of both liquidity index
+        /// vectors. Checks that can be made on a single page (entries without liquidity, flag mismatches,
+        /// duplicate receipts, negative index tiers, vault and index totals disagreeing with `total_xrd_locked`)
+        /// are reported as discrepancies. The same page of receipt IDs is checked from the receipt side, so
+        /// available liquidity that has no buy list key is reported too. Callers page until `next_index` is
+        /// `None` and compare the summed page totals with the global figures.
+        /// 
+        /// # Arguments
+        /// * `start_index`: The `u64` buy list index and receipt offset to start auditing from
+        /// * `count`: The `u64` maximum number of buy list entries and receipts to audit
+        ///
+        /// # Returns
+        /// * An `AccountingAudit` with the page sums, global figures and any discrepancies found
+        pub fn audit_accounting(&self, start_index: u64, count: u64) -> AccountingAudit {
+            let mut discrepancies: Vec<String> = Vec::new();
+            let mut seen_receipts: std::collections::HashSet<NonFungibleGlobalId> = std::collections::HashSet::new();
+            let mut xrd_liquidity_available_sum = dec!(0);
+            let mut auto_unstake_true_sum = dec!(0);
+            let mut auto_unstake_false_sum = dec!(0);
+            let mut entries_checked = 0u64;
+            let mut current_index = 0u64;
+            let mut next_index = None;
+
+            for (key, global_id, _) in self.buy_list.range(0..u128::MAX) {
+                if current_index < start_index {
+                    current_index += 1;
+                    continue;
+                }
+
+                if entries_checked >= count {
+                    next_index = Some(current_index);
+                    break;
+                }
+
+                current_index += 1;
+                entries_checked += 1;
+
+                let kvs_data = match self.liquidity_data.get(&global_id) {
+                    Some(kvs_data) => kvs_data,
+                    None => {
+                        discrepancies.push(format!("Buy list key {} has no liquidity data", key));
+                        continue;
+                    }
+                };
+
+                // Sub-positions have their own keys, a receipt should only have one other entry
+                if !kvs_data.is_sub_position_key(key) {
+                    if !seen_receipts.insert(global_id.clone()) {
+                        discrepancies.push(format!("Receipt {:?} has more than one buy list entry", global_id.local_id()));
+                    }
+                    if kvs_data.primary_buy_list_key != Some(key) {
+                        discrepancies.push(format!("Buy list key {} is not tracked by receipt {:?}", key, global_id.local_id()));
+                    }
+                }
+
+                if kvs_data.sub_position_liquidity() > kvs_data.xrd_liquidity_available {
+                    discrepancies.push(format!("Receipt {:?} has more liquidity in sub-positions than available", global_id.local_id()));
+                }
+
+                let xrd_liquidity_available = kvs_data.key_liquidity_available(key);
+                drop(kvs_data);
+
+                if xrd_liquidity_available <= dec!(0) {
+                    discrepancies.push(format!("Buy list key {} for receipt {:?} has {} XRD available", key, global_id.local_id(), xrd_liquidity_available));
+                }
+
+                let nft_data: LiquidityReceipt = self.liquidity_receipt.get_non_fungible_data(global_id.local_id());
+                if BuyListKey::extract_auto_unstake(key) != nft_data.auto_unstake {
+                    discrepancies.push(format!("Buy list key {} disagrees with receipt {:?} on auto_unstake", key, global_id.local_id()));
+                }
+
+                xrd_liquidity_available_sum += xrd_liquidity_available;
+                if nft_data.auto_unstake {
+                    auto_unstake_true_sum += xrd_liquidity_available;
+                } else {
+                    auto_unstake_false_sum += xrd_liquidity_available;
+                }
+            }
+
+            // Receipt IDs start at 1, closed receipts have no liquidity data left to check
+            let mut receipts_checked = 0u64;
+            let first_receipt_id = start_index.saturating_add(1);
+            let last_receipt_id = start_index.saturating_add(count).min(self.liquidity_receipt_counter - 1);
+            for receipt_id in first_receipt_id..=last_receipt_id {
+                let local_id = NonFungibleLocalId::Integer(IntegerNonFungibleLocalId::new(receipt_id));
+                let global_id = NonFungibleGlobalId::new(self.liquidity_receipt.address(), local_id.clone());
+                let kvs_data = match self.liquidity_data.get(&global_id) {
+                    Some(kvs_data) => kvs_data,
+                    None => continue,
+                };
+                receipts_checked += 1;
+
+                let original_available = kvs_data.xrd_liquidity_available - kvs_data.sub_position_liquidity();
+                match kvs_data.primary_buy_list_key {
+                    Some(key) => {
+                        if self.buy_list.get(&key).map(|tree_global_id| tree_global_id.clone()) != Some(global_id.clone()) {
+                            discrepancies.push(format!("Receipt {:?} tracks buy list key {} which isn't in the buy list", local_id, key));
+                        }
+                    },
+                    None => {
+                        if original_available > dec!(0) || !kvs_data.sub_positions.is_empty() {
+                            discrepancies.push(format!("Receipt {:?} has {} XRD available without a buy list key", local_id, kvs_data.xrd_liquidity_available));
+                        }
+                    },
+                }
+                for sub_position in kvs_data.sub_positions.iter() {
+                    if self.buy_list.get(&sub_position.buy_list_key).map(|tree_global_id| tree_global_id.clone()) != Some(global_id.clone()) {
+                        discrepancies.push(format!("Receipt {:?} tracks buy list key {} which isn't in the buy list", local_id, sub_position.buy_list_key));
+                    }
+                }
+            }
+
+            // Keep paging while either the buy list or the receipt IDs have more to check
+            if next_index.is_none() && last_receipt_id < self.liquidity_receipt_counter - 1 {
+                next_index = Some(start_index.saturating_add(count));
+            }
+
+            let liquidity_index_auto_unstake_true_total: Decimal = self.liquidity_index_auto_unstake_true.iter().fold(dec!(0), |sum, tier| sum + *tier);
+            let liquidity_index_auto_unstake_false_total: Decimal = self.liquidity_index_auto_unstake_false.iter().fold(dec!(0), |sum, tier| sum + *tier);
+
+            for (index, tier) in self.liquidity_index_auto_unstake_true.iter().enumerate() {
+                if *tier < dec!(0) {
+                    discrepancies.push(format!("Auto unstake liquidity index tier {} is negative: {}", index, tier));
+                }
+            }
+            for (index, tier) in self.liquidity_index_auto_unstake_false.iter().enumerate() {
+                if *tier < dec!(0) {
+                    discrepancies.push(format!("Liquidity index tier {} is negative: {}", index, tier));
+                }
+            }
+
+            let xrd_liquidity_amount = self.xrd_liquidity.amount();
+            if xrd_liquidity_amount != self.total_xrd_locked {
+                discrepancies.push(format!("XRD liquidity vault holds {} but total_xrd_locked is {}", xrd_liquidity_amount, self.total_xrd_locked));
+            }
+
+            let liquidity_index_total = liquidity_index_auto_unstake_true_total + liquidity_index_auto_unstake_false_total;
+            if liquidity_index_total != self.total_xrd_locked {
+                discrepancies.push(format!("Liquidity indexes sum to {} but total_xrd_locked is {}", liquidity_index_total, self.total_xrd_locked));
+            }
+
+            let claimable_xrd_amount = self.claimable_xrd.amount();
+            if claimable_xrd_amount != self.total_xrd_claimable {
+                discrepancies.push(format!("Claimable XRD vault holds {} but total_xrd_claimable is {}", claimable_xrd_amount, self.total_xrd_claimable));
+            }
+
+            AccountingAudit {
+                start_index,
+                entries_checked,
+                receipts_checked,
+                next_index,
+                xrd_liquidity_available_sum,
+                auto_unstake_true_sum,
+                auto_unstake_false_sum,
+                xrd_liquidity_amount,
+                total_xrd_locked: self.total_xrd_locked,
+                liquidity_index_auto_unstake_true_total,
+                liquidity_index_auto_unstake_false_total,
+                claimable_xrd_amount,
+                total_xrd_claimable: self.total_xrd_claimable,
+                discrepancies,
+            }
+        }
+
+        /// Decodes a buy list key into its discount, position, flags and receipt ID.
+        /// 
+        /// Both the current layout and the legacy layout with a 32-bit receipt ID are understood. Components
+        /// deployed before the current layout keep running their own code and their own keys, so there is no
+        /// on-ledger migration. Off-ledger services can decode keys from either deployment with the `client`
+        /// feature.
+        /// 
+        /// # Arguments
+        /// * `key`: The `u128` buy list key to decode
+        ///
+        /// # Returns
+        /// * A `BuyListKeyParts` with the decoded fields
+        pub fn decode_buy_list_key(&self, key: u128) -> BuyListKeyParts {
+            BuyListKey::decode(key)
+        }
+
         /// Gets a range of entries from the buy list order book.
         /// 
         /// This method returns a paginated view of the AVL tree buy list, useful for off-chain indexing
@@ -1865,8 +4346,16 @@ mod liquify_module {
             let mut results = Vec::new();
             let mut current_index = 0u64;
             
-            // Iterate through actual positions in the buy list
-            for (_, global_id, _) in self.buy_list.range(0..u128::MAX) {
+            // Iterate through actual positions in the buy list, sub-positions are listed with their receipt
+            for (key, global_id, _) in self.buy_list.range(0..u128::MAX) {
+                let is_sub_position = match self.liquidity_data.get(&global_id) {
+                    Some(kvs_data) => kvs_data.is_sub_position_key(key),
+                    None => false,
+                };
+                if is_sub_position {
+                    continue;
+                }
+
                 if current_index < start_index {
                     current_index += 1;
                     continue;
@@ -1891,20 +4380,14 @@ mod liquify_module {
             
             let mut liquidity_ahead = Decimal::ZERO;
             
-            // Find our position's key in the tree
-            let mut our_key = None;
-            for (key, tree_global_id, _) in self.buy_list.range(0..u128::MAX) {
-                if tree_global_id == global_id {
-                    our_key = Some(key);
-                    break;
-                }
-            }
+            // Find our original position's key in the tree
+            let our_key = self.primary_buy_list_key(&global_id);
             
             if let Some(our_key) = our_key {
                 // Sum all liquidity from start up to (but not including) our position
                 for (key, other_global_id, _) in self.buy_list.range(0..our_key) {
                     if let Some(kvs_data) = self.liquidity_data.get(&other_global_id) {
-                        liquidity_ahead += kvs_data.xrd_liquidity_available;
+                        liquidity_ahead += kvs_data.key_liquidity_available(key);
                     }
                 }
             }
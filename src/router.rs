@@ -0,0 +1,148 @@
+// src/router.rs
+//
+// Off-ledger routing for `liquify_unstake_off_ledger`. Given a snapshot of the buy list this works out which
+// order keys an unstake should be matched against and what it is expected to return, using the same matching
+// math as `process_unstake`.
+
+use scrypto::prelude::*;
+use crate::client::buy_list_key;
+pub use crate::client::matching_policy::MatchingPolicy;
+
+/// A buy list entry as seen off-ledger.
+///
+/// Build these from `get_raw_buy_list_range` for the keys and `get_receipt_detail` for the rest. `discount`
+/// is the receipt's discount override for the LSU's validator if it has one, otherwise its `effective_discount`,
+/// or for ladder receipts the `discount` of the key's `sub_positions` entry. `xrd_liquidity_available` is taken
+/// from the receipt's `sub_positions` entry for the key.
+#[derive(Debug, Clone)]
+pub struct BuyListEntry {
+    pub key: u128,
+    pub discount: Decimal,
+    pub xrd_liquidity_available: Decimal,
+}
+
+/// Component settings that affect matching, read from the Liquify component.
+///
+/// `tier_matching_policies` is the component's list of the same name, one policy per discount tier. Tiers
+/// missing from it are matched FIFO, so an empty list routes every tier FIFO.
+#[derive(Debug, Clone)]
+pub struct RouterConfig {
+    pub max_keys: usize,
+    pub platform_fee: Decimal,
+    pub small_order_threshold: Decimal,
+    pub dust_threshold: Decimal,
+    pub sweep_dust: bool,
+    pub tier_matching_policies: Vec<MatchingPolicy>,
+}
+
+impl RouterConfig {
+    fn tier_matching_policy(&self, discount_basis_points: u16) -> MatchingPolicy {
+        let index = buy_list_key::tier_index(discount_basis_points);
+        self.tier_matching_policies.get(index).copied().unwrap_or(MatchingPolicy::Fifo)
+    }
+}
+
+/// The order keys to pass to `liquify_unstake_off_ledger` and the expected outcome.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub order_keys: Vec<u128>,
+    pub lsu_matched: Decimal,
+    pub xrd_filled: Decimal,
+    pub xrd_expected: Decimal,
+}
+
+/// Routes an unstake of `lsu_amount` LSU through the buy list snapshot in book order.
+///
+/// Entries are matched in ascending key order, the same priority the buy list gives them on-ledger, until the
+/// LSUs run out or `max_keys` entries have been used. Small orders skip auto_unstake positions like the
+/// component does. When the unstake reaches a pro-rata tier, what is left of it is split across the tier's
+/// entries in the route in proportion to their available liquidity, so every one of them is part of the route.
+/// `xrd_expected` is what the seller receives after the platform fee.
+pub fn route_unstake(entries: &[BuyListEntry], lsu_amount: Decimal, redemption_rate: Decimal, config: &RouterConfig) -> Route {
+    let is_small_order = lsu_amount * redemption_rate < config.small_order_threshold;
+
+    // The entries the component will walk for these keys, in book order
+    let mut candidates: Vec<&BuyListEntry> = entries.iter()
+        .filter(|entry| entry.xrd_liquidity_available > dec!(0))
+        .filter(|entry| !(is_small_order && buy_list_key::decode(entry.key).auto_unstake))
+        .collect();
+    candidates.sort_by_key(|entry| entry.key);
+    candidates.truncate(config.max_keys);
+
+    let mut order_keys = Vec::new();
+    let mut remaining_lsus = lsu_amount;
+    let mut remaining_value = remaining_lsus * redemption_rate;
+    let mut xrd_filled = dec!(0);
+    let mut lsu_allocations: HashMap<u128, Decimal> = HashMap::new();
+
+    for (index, entry) in candidates.iter().enumerate() {
+        if remaining_lsus.is_zero() {
+            break;
+        }
+
+        // Split what is left across a pro-rata tier the first time the unstake reaches it
+        let tier_basis_points = buy_list_key::decode(entry.key).discount_basis_points;
+        if !lsu_allocations.contains_key(&entry.key) && config.tier_matching_policy(tier_basis_points) == MatchingPolicy::ProRata {
+            allocate_pro_rata(&candidates[index..], tier_basis_points, remaining_lsus, &mut lsu_allocations);
+        }
+
+        let lsu_budget = match lsu_allocations.get(&entry.key) {
+            Some(allocation) if *allocation < remaining_lsus => *allocation,
+            _ => remaining_lsus,
+        };
+        if lsu_budget <= dec!(0) {
+            continue;
+        }
+
+        let budget_value = if lsu_budget == remaining_lsus { remaining_value } else { lsu_budget * redemption_rate };
+        let discounted_value = budget_value * (dec!(1) - entry.discount);
+        let (lsu_to_take, mut fill_amount, new_xrd_available) = if discounted_value <= entry.xrd_liquidity_available {
+            (lsu_budget, discounted_value, entry.xrd_liquidity_available - discounted_value)
+        } else {
+            let lsu_ratio = entry.xrd_liquidity_available / discounted_value;
+            (lsu_budget * lsu_ratio, entry.xrd_liquidity_available, dec!(0))
+        };
+
+        if config.sweep_dust && new_xrd_available > dec!(0) && new_xrd_available < config.dust_threshold {
+            fill_amount += new_xrd_available;
+        }
+
+        order_keys.push(entry.key);
+        xrd_filled += fill_amount;
+        remaining_lsus -= lsu_to_take;
+        remaining_value = remaining_lsus * redemption_rate;
+    }
+
+    Route {
+        order_keys,
+        lsu_matched: lsu_amount - remaining_lsus,
+        xrd_filled,
+        xrd_expected: xrd_filled - xrd_filled * config.platform_fee,
+    }
+}
+
+// Splits `lsu_amount` across the entries at the front of `candidates` that share the given tier, in proportion
+// to their available liquidity, with the last entry taking the rounding remainder like the component does
+fn allocate_pro_rata(candidates: &[&BuyListEntry], tier_basis_points: u16, lsu_amount: Decimal, allocations: &mut HashMap<u128, Decimal>) {
+    let tier_entries: Vec<&BuyListEntry> = candidates.iter()
+        .take_while(|entry| buy_list_key::decode(entry.key).discount_basis_points == tier_basis_points)
+        .copied()
+        .collect();
+    let tier_total = tier_entries.iter().fold(dec!(0), |sum, entry| sum + entry.xrd_liquidity_available);
+
+    if tier_total <= dec!(0) {
+        return;
+    }
+
+    let last_index = tier_entries.len() - 1;
+    let mut allocated = dec!(0);
+    for (index, entry) in tier_entries.into_iter().enumerate() {
+        let share = if index == last_index {
+            lsu_amount - allocated
+        } else {
+            lsu_amount * entry.xrd_liquidity_available / tier_total
+        };
+        allocated += share;
+        allocations.insert(entry.key, share);
+    }
+}
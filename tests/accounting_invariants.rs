@@ -0,0 +1,675 @@
+use scrypto_test::prelude::*;
+
+mod common;
+
+#[derive(ScryptoSbor, Debug, Clone)]
+pub struct SubPosition {
+    pub buy_list_key: u128,
+    pub discount: Decimal,
+    pub xrd_liquidity_available: Decimal,
+}
+
+#[derive(ScryptoSbor, Debug, Clone)]
+pub struct ReceiptDetailData {
+    pub receipt_id: NonFungibleLocalId,
+    pub discount: Decimal,
+    pub auto_unstake: bool,
+    pub auto_refill: bool,
+    pub refill_threshold: Decimal,
+    pub xrd_liquidity_available: Decimal,
+    pub xrd_liquidity_filled: Decimal,
+    pub liquidity_ahead: Decimal,
+    pub fills_to_collect: u64,
+    pub last_added_epoch: u32,
+    pub claimable_xrd: Decimal,
+    pub total_stake_claim_value: Decimal,
+    pub total_lsu_redemption_value: Decimal,
+    pub automation_fee: Decimal,
+    pub discount_overrides: Vec<(ComponentAddress, Decimal)>,
+    pub annualized_rate: Option<Decimal>,
+    pub effective_discount: Decimal,
+    pub lsu_fills_unbonding: u64,
+    pub redemption_ready_epoch: u64,
+    pub mid_cycle: bool,
+    pub claimable_xrd_balance: Decimal,
+    pub sub_positions: Vec<SubPosition>,
+    pub ladder_weights: Vec<(Decimal, Decimal)>,
+}
+
+#[derive(Clone)]
+pub struct Account {
+    public_key: Secp256k1PublicKey,
+    account_address: ComponentAddress,
+} 
+
+pub struct TestEnvironment {
+    pub ledger: LedgerSimulator<NoExtension, InMemorySubstateDatabase>,
+    pub admin_account: Account,
+    pub user_account1: Account,
+    pub user_account2: Account,
+    pub package_address: PackageAddress,
+    pub liquify_component: ComponentAddress,
+    pub owner_badge: ResourceAddress,
+    pub liquidity_receipt: ResourceAddress,
+    pub lsu_resource_address: ResourceAddress,
+}
+
+impl TestEnvironment {
+    pub fn instantiate_test() -> Self {
+        let mut ledger = LedgerSimulatorBuilder::new()
+            .without_kernel_trace()
+            .build();
+
+        // Create accounts
+        let (admin_public_key, _admin_private_key, admin_account_address) = ledger.new_allocated_account();
+        let admin_account = Account { public_key: admin_public_key, account_address: admin_account_address };
+
+        let (user_public_key1, _user_private_key1, user_account_address1) = ledger.new_allocated_account();
+        let user_account1 = Account { public_key: user_public_key1, account_address: user_account_address1 };
+
+        let (user_public_key2, _user_private_key2, user_account_address2) = ledger.new_allocated_account();
+        let  user_account2 = Account { public_key: user_public_key2, account_address: user_account_address2 };
+
+        let package_address = ledger.compile_and_publish(this_package!());
+
+        // Instantiate Liquify component
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_function(
+                package_address,
+                "Liquify",
+                "instantiate_liquify",
+                manifest_args!(),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+
+        let liquify_component = receipt.expect_commit(true).new_component_addresses()[0];
+        let owner_badge = receipt.expect_commit(true).new_resource_addresses()[0];
+        let liquidity_receipt = receipt.expect_commit(true).new_resource_addresses()[1];
+
+        // Enable the component
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address, 
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component, 
+                "set_component_status", 
+                manifest_args!(true),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+
+        // Setup LSUs
+        let key = Secp256k1PrivateKey::from_u64(1u64).unwrap().public_key();
+        let validator_address = ledger.get_active_validator_with_key(&key);
+        let lsu_resource_address = ledger
+            .get_active_validator_info_by_key(&key)
+            .stake_unit_resource;
+
+        // Give user1 LSUs for unstaking
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet() 
+            .withdraw_from_account(user_account_address1, XRD, dec!(5000))
+            .take_all_from_worktop(XRD, "xrd")
+            .call_method_with_name_lookup(validator_address, "stake", |lookup| {
+                (lookup.bucket("xrd"),)
+            })
+            .call_method(
+                user_account_address1,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&user_public_key1)],
+        );
+        receipt.expect_commit_success();
+
+        // Set minimum liquidity to 100 for easier testing
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address, 
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component, 
+                "set_minimum_liquidity", 
+                manifest_args!(dec!("100")),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+
+        // Set minimum refill threshold to 100
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address, 
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component, 
+                "set_minimum_refill_threshold", 
+                manifest_args!(dec!("100")),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+       
+        Self {
+            ledger,
+            admin_account,
+            user_account1,
+            user_account2,
+            package_address,
+            liquify_component,
+            owner_badge,
+            liquidity_receipt,
+            lsu_resource_address,
+        }
+    }
+
+    pub fn execute_manifest(
+        &mut self,
+        manifest: TransactionManifestV1, 
+        account: Account,
+    ) -> TransactionReceipt {
+        let receipt = self.ledger.execute_manifest(
+            manifest, 
+            vec![NonFungibleGlobalId::from_public_key(&account.public_key)]
+        );
+
+        if receipt.is_commit_success() {
+            common::assert_accounting_invariants(&mut self.ledger, self.liquify_component);
+        }
+
+        receipt
+    }
+}
+
+fn add_liquidity(ledger: &mut TestEnvironment, automation_fee: Decimal) {
+    let user_account2 = ledger.user_account2.account_address;
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account2, XRD, dec!(1000))
+        .take_all_from_worktop(XRD, "xrd")
+        .call_method_with_name_lookup(ledger.liquify_component, "add_liquidity", |lookup| {(
+            lookup.bucket("xrd"),
+            dec!("0.01"),
+            true,
+            true,
+            dec!("200"),
+            automation_fee,
+        )})
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+    
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+}
+
+fn unstake(ledger: &mut TestEnvironment, lsu_amount: Decimal) {
+    let user_account1 = ledger.user_account1.account_address;
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account1, ledger.lsu_resource_address, lsu_amount)
+        .take_all_from_worktop(ledger.lsu_resource_address, "lsu")
+        .call_method_with_name_lookup(ledger.liquify_component, "liquify_unstake", |lookup| {
+            (lookup.bucket("lsu"), 10u8)
+        })
+        .call_method(
+            user_account1,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+        
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success();
+}
+
+fn cycle(ledger: &mut TestEnvironment, receipt_id: u64) -> Decimal {
+    let user_account1 = ledger.user_account1.account_address;
+    let xrd_before = ledger.ledger.get_component_balance(user_account1, XRD);
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(
+            ledger.liquify_component,
+            "cycle_liquidity",
+            manifest_args!(vec![NonFungibleLocalId::integer(receipt_id)]),
+        )
+        .call_method(
+            user_account1,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+    
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success();
+
+    ledger.ledger.get_component_balance(user_account1, XRD) - xrd_before
+}
+
+fn increase_liquidity_keep_position(ledger: &mut TestEnvironment, receipt_id: u64, xrd_amount: Decimal) {
+    let user_account2 = ledger.user_account2.account_address;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(
+            user_account2,
+            ledger.liquidity_receipt,
+            vec![NonFungibleLocalId::integer(receipt_id)],
+        )
+        .take_all_from_worktop(ledger.liquidity_receipt, "receipt")
+        .withdraw_from_account(user_account2, XRD, xrd_amount)
+        .take_all_from_worktop(XRD, "xrd")
+        .call_method_with_name_lookup(ledger.liquify_component, "increase_liquidity_keep_position", |lookup| {
+            (lookup.bucket("receipt"), lookup.bucket("xrd"))
+        })
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+}
+
+// Withdraws every position and the platform fees, after which no XRD may be left without an owner
+fn get_receipt_detail(ledger: &mut TestEnvironment, receipt_id: u64) -> ReceiptDetailData {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(ledger.liquify_component, "get_receipt_detail", manifest_args!(NonFungibleLocalId::integer(receipt_id)))
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success().output(1)
+}
+
+fn assert_no_orphaned_xrd(ledger: &mut TestEnvironment, receipt_ids: Vec<u64>) {
+    let user_account2 = ledger.user_account2.account_address;
+    let admin_account = ledger.admin_account.account_address;
+
+    for receipt_id in receipt_ids {
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_non_fungibles_from_account(
+                user_account2,
+                ledger.liquidity_receipt,
+                vec![NonFungibleLocalId::integer(receipt_id)],
+            )
+            .take_all_from_worktop(ledger.liquidity_receipt, "receipt")
+            .call_method_with_name_lookup(ledger.liquify_component, "remove_liquidity", |lookup| {
+                (lookup.bucket("receipt"),)
+            })
+            .call_method(
+                user_account2,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+
+        let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+        receipt.expect_commit_success();
+    }
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_amount(admin_account, ledger.owner_badge, 1)
+        .call_method(ledger.liquify_component, "collect_platform_fees", manifest_args!())
+        .call_method(
+            admin_account,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.admin_account.clone());
+    receipt.expect_commit_success();
+
+    let component_xrd = ledger.ledger.get_component_balance(ledger.liquify_component, XRD);
+    assert_eq!(component_xrd, dec!(0), "XRD left in the component without an owner");
+}
+
+#[test]
+fn test_cycle_accounts_for_all_xrd() {
+    let mut ledger = TestEnvironment::instantiate_test();
+
+    add_liquidity(&mut ledger, dec!(5));
+    unstake(&mut ledger, dec!(1010));
+    ledger.ledger.advance_to_round(Round::of(300));
+
+    let automation_fee = cycle(&mut ledger, 1);
+    assert_eq!(automation_fee, dec!(5));
+
+    assert_no_orphaned_xrd(&mut ledger, vec![1]);
+}
+
+#[test]
+fn test_cycle_fee_shortfall_credits_position() {
+    let mut ledger = TestEnvironment::instantiate_test();
+
+    // The automation fee is larger than anything the position can claim
+    add_liquidity(&mut ledger, dec!(2000));
+    unstake(&mut ledger, dec!(1010));
+    ledger.ledger.advance_to_round(Round::of(300));
+
+    let automation_fee = cycle(&mut ledger, 1);
+    assert_eq!(automation_fee, dec!(0), "Fee should be waived when the claimed XRD can't cover it");
+
+    assert_no_orphaned_xrd(&mut ledger, vec![1]);
+}
+
+#[test]
+fn test_chunked_cycle_charges_automation_fee_once() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let admin_account = ledger.admin_account.account_address;
+
+    // Let small unstakes reach the automated position and cycle at most two fills per call
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_amount(admin_account, ledger.owner_badge, 1)
+        .call_method(ledger.liquify_component, "set_small_order_threshold", manifest_args!(dec!(1)))
+        .call_method(ledger.liquify_component, "set_max_fills_per_cycle", manifest_args!(2u64))
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.admin_account.clone());
+    receipt.expect_commit_success();
+
+    add_liquidity(&mut ledger, dec!(5));
+    for _ in 0..3 {
+        unstake(&mut ledger, dec!(100));
+    }
+    ledger.ledger.advance_to_round(Round::of(300));
+
+    // Two of three fills are cycled first, the last one on the next call
+    let first_fee = cycle(&mut ledger, 1);
+    assert!(get_receipt_detail(&mut ledger, 1).mid_cycle);
+    let second_fee = cycle(&mut ledger, 1);
+    assert!(!get_receipt_detail(&mut ledger, 1).mid_cycle);
+
+    assert!(first_fee > dec!(0) && first_fee < dec!(5), "A partial chunk should only pay part of the fee");
+    assert_eq!(first_fee + second_fee, dec!(5), "The whole cycle should pay the automation fee once");
+
+    // Nothing is left to cycle, so nothing more is charged
+    assert_eq!(cycle(&mut ledger, 1), dec!(0));
+
+    assert_no_orphaned_xrd(&mut ledger, vec![1]);
+}
+
+#[test]
+fn test_partial_fill_cycle_accounts_for_all_xrd() {
+    let mut ledger = TestEnvironment::instantiate_test();
+
+    add_liquidity(&mut ledger, dec!(5));
+    add_liquidity(&mut ledger, dec!(5));
+    unstake(&mut ledger, dec!(1500));
+    ledger.ledger.advance_to_round(Round::of(300));
+
+    cycle(&mut ledger, 1);
+    cycle(&mut ledger, 2);
+
+    assert_no_orphaned_xrd(&mut ledger, vec![1, 2]);
+}
+
+#[test]
+fn test_keep_position_sub_positions_account_for_all_xrd() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account2 = ledger.user_account2.account_address;
+
+    add_liquidity(&mut ledger, dec!(5));
+    add_liquidity(&mut ledger, dec!(5));
+
+    // Top up the first receipt without losing its place ahead of the second
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(
+            user_account2,
+            ledger.liquidity_receipt,
+            vec![NonFungibleLocalId::integer(1)],
+        )
+        .take_all_from_worktop(ledger.liquidity_receipt, "receipt")
+        .withdraw_from_account(user_account2, XRD, dec!(500))
+        .take_all_from_worktop(XRD, "xrd")
+        .call_method_with_name_lookup(ledger.liquify_component, "increase_liquidity_keep_position", |lookup| {
+            (lookup.bucket("receipt"), lookup.bucket("xrd"))
+        })
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+
+    let detail = get_receipt_detail(&mut ledger, 1);
+    assert_eq!(detail.liquidity_ahead, dec!(0), "The original position should keep its place");
+    assert_eq!(detail.xrd_liquidity_available, dec!(1500));
+    assert_eq!(detail.sub_positions.len(), 2);
+    assert_eq!(detail.sub_positions[0].xrd_liquidity_available, dec!(1000));
+    assert_eq!(detail.sub_positions[1].xrd_liquidity_available, dec!(500));
+    assert!(detail.sub_positions[0].buy_list_key < detail.sub_positions[1].buy_list_key);
+
+    // Uses up the original position, then moves on to the second receipt ahead of the top up
+    unstake(&mut ledger, dec!(1200));
+
+    let detail = get_receipt_detail(&mut ledger, 1);
+    assert_eq!(detail.xrd_liquidity_available, dec!(500), "The top up should not have been filled");
+    assert_eq!(detail.sub_positions.len(), 1);
+    assert_eq!(detail.sub_positions[0].xrd_liquidity_available, dec!(500));
+
+    let detail = get_receipt_detail(&mut ledger, 2);
+    assert!(detail.xrd_liquidity_filled > dec!(0), "The second receipt should be filled before the top up");
+
+    assert_no_orphaned_xrd(&mut ledger, vec![1, 2]);
+}
+
+#[test]
+fn test_ladder_fills_and_refills_by_weight() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account2 = ledger.user_account2.account_address;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account2, XRD, dec!(3000))
+        .take_all_from_worktop(XRD, "xrd")
+        .call_method_with_name_lookup(ledger.liquify_component, "add_liquidity_ladder", |lookup| {(
+            lookup.bucket("xrd"),
+            vec![(dec!("0.01"), dec!(1)), (dec!("0.02"), dec!(2))],
+            true,
+            true,
+            dec!("200"),
+            dec!(5),
+        )})
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+
+    let detail = get_receipt_detail(&mut ledger, 1);
+    assert_eq!(detail.xrd_liquidity_available, dec!(3000));
+    assert_eq!(detail.ladder_weights.len(), 2);
+    assert_eq!(detail.sub_positions.len(), 2);
+    assert_eq!(detail.sub_positions[0].discount, dec!("0.01"));
+    assert_eq!(detail.sub_positions[0].xrd_liquidity_available, dec!(1000));
+    assert_eq!(detail.sub_positions[1].discount, dec!("0.02"));
+    assert_eq!(detail.sub_positions[1].xrd_liquidity_available, dec!(2000));
+
+    // Fills the cheaper rung first, all under the same receipt
+    unstake(&mut ledger, dec!(505));
+
+    let detail = get_receipt_detail(&mut ledger, 1);
+    assert_eq!(detail.xrd_liquidity_filled, dec!("499.95"));
+    assert_eq!(detail.sub_positions[0].xrd_liquidity_available, dec!("500.05"));
+    assert_eq!(detail.sub_positions[1].xrd_liquidity_available, dec!(2000));
+    let rungs_before = detail.sub_positions.clone();
+
+    ledger.ledger.advance_to_round(Round::of(300));
+    cycle(&mut ledger, 1);
+
+    // The rungs keep their keys and amounts, only the refilled XRD is queued behind them by the original weights
+    let detail = get_receipt_detail(&mut ledger, 1);
+    assert_eq!(detail.sub_positions.len(), 4);
+    for (rung, rung_before) in detail.sub_positions.iter().zip(rungs_before.iter()) {
+        assert_eq!(rung.buy_list_key, rung_before.buy_list_key);
+        assert_eq!(rung.xrd_liquidity_available, rung_before.xrd_liquidity_available);
+    }
+    assert_eq!(detail.sub_positions[2].discount, dec!("0.01"));
+    assert_eq!(detail.sub_positions[3].discount, dec!("0.02"));
+    assert!(detail.sub_positions[2].buy_list_key > rungs_before[0].buy_list_key);
+    assert!(detail.sub_positions[3].buy_list_key > rungs_before[1].buy_list_key);
+
+    let refill = detail.sub_positions[2].xrd_liquidity_available + detail.sub_positions[3].xrd_liquidity_available;
+    assert_eq!(detail.xrd_liquidity_available, dec!("2500.05") + refill);
+    let weight_error = detail.sub_positions[3].xrd_liquidity_available - detail.sub_positions[2].xrd_liquidity_available * dec!(2);
+    assert!(weight_error.checked_abs().unwrap() < dec!("0.000001"), "The refill should be split 1:2");
+
+    assert_no_orphaned_xrd(&mut ledger, vec![1]);
+}
+
+#[test]
+fn test_ladder_increase_liquidity_splits_only_new_xrd() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account2 = ledger.user_account2.account_address;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account2, XRD, dec!(3000))
+        .take_all_from_worktop(XRD, "xrd")
+        .call_method_with_name_lookup(ledger.liquify_component, "add_liquidity_ladder", |lookup| {(
+            lookup.bucket("xrd"),
+            vec![(dec!("0.01"), dec!(1)), (dec!("0.02"), dec!(2))],
+            true,
+            true,
+            dec!("200"),
+            dec!(5),
+        )})
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+
+    // Takes half of the cheaper rung
+    unstake(&mut ledger, dec!(505));
+
+    let detail = get_receipt_detail(&mut ledger, 1);
+    assert_eq!(detail.sub_positions[0].xrd_liquidity_available, dec!("500.05"));
+    assert_eq!(detail.sub_positions[1].xrd_liquidity_available, dec!(2000));
+    let rungs_before = detail.sub_positions.clone();
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(
+            user_account2,
+            ledger.liquidity_receipt,
+            vec![NonFungibleLocalId::integer(1)],
+        )
+        .take_all_from_worktop(ledger.liquidity_receipt, "receipt")
+        .withdraw_from_account(user_account2, XRD, dec!(300))
+        .take_all_from_worktop(XRD, "xrd")
+        .call_method_with_name_lookup(ledger.liquify_component, "increase_liquidity", |lookup| {
+            (lookup.bucket("receipt"), lookup.bucket("xrd"))
+        })
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+
+    // Each rung keeps what it held and gets its share of the new XRD, moved behind its old key
+    let detail = get_receipt_detail(&mut ledger, 1);
+    assert_eq!(detail.xrd_liquidity_available, dec!("2800.05"));
+    assert_eq!(detail.sub_positions.len(), 2);
+    assert_eq!(detail.sub_positions[0].discount, dec!("0.01"));
+    assert_eq!(detail.sub_positions[0].xrd_liquidity_available, dec!("600.05"));
+    assert_eq!(detail.sub_positions[1].discount, dec!("0.02"));
+    assert_eq!(detail.sub_positions[1].xrd_liquidity_available, dec!(2200));
+    assert!(detail.sub_positions[0].buy_list_key > rungs_before[0].buy_list_key);
+    assert!(detail.sub_positions[1].buy_list_key > rungs_before[1].buy_list_key);
+
+    assert_no_orphaned_xrd(&mut ledger, vec![1]);
+}
+
+#[test]
+fn test_keep_position_sub_positions_are_capped() {
+    let mut ledger = TestEnvironment::instantiate_test();
+
+    add_liquidity(&mut ledger, dec!(5));
+
+    // 20 sub-positions fit, the two top ups after that are folded into the newest one
+    for _ in 0..22 {
+        increase_liquidity_keep_position(&mut ledger, 1, dec!(10));
+    }
+
+    // The original position is listed first
+    let detail = get_receipt_detail(&mut ledger, 1);
+    assert_eq!(detail.xrd_liquidity_available, dec!(1220));
+    assert_eq!(detail.sub_positions.len(), 21);
+    assert_eq!(detail.sub_positions[0].xrd_liquidity_available, dec!(1000));
+    assert!(detail.sub_positions[1..20].iter().all(|sub_position| sub_position.xrd_liquidity_available == dec!(10)));
+    assert_eq!(detail.sub_positions[20].xrd_liquidity_available, dec!(30));
+    assert!(detail.sub_positions.windows(2).all(|pair| pair[0].buy_list_key < pair[1].buy_list_key));
+
+    assert_no_orphaned_xrd(&mut ledger, vec![1]);
+}
@@ -0,0 +1,371 @@
+use scrypto_test::prelude::*;
+
+mod common;
+
+#[derive(Clone)]
+pub struct Account {
+    public_key: Secp256k1PublicKey,
+    account_address: ComponentAddress,
+}
+
+pub struct TestEnvironment {
+    pub ledger: LedgerSimulator<NoExtension, InMemorySubstateDatabase>,
+    pub admin_account: Account,
+    pub user_account1: Account,
+    pub user_account2: Account,
+    pub package_address: PackageAddress,
+    pub liquify_component: ComponentAddress,
+    pub owner_badge: ResourceAddress,
+    pub liquidity_receipt: ResourceAddress,
+    pub lsu_resource_address: ResourceAddress,
+    pub claim_nft_address: ResourceAddress,
+    pub validator_address: ComponentAddress,
+}
+
+impl TestEnvironment {
+    pub fn instantiate_test() -> Self {
+        let mut ledger = LedgerSimulatorBuilder::new()
+            .without_kernel_trace()
+            .build();
+
+        // Create accounts
+        let (admin_public_key, _admin_private_key, admin_account_address) = ledger.new_allocated_account();
+        let admin_account = Account { public_key: admin_public_key, account_address: admin_account_address };
+
+        let (user_public_key1, _user_private_key1, user_account_address1) = ledger.new_allocated_account();
+        let user_account1 = Account { public_key: user_public_key1, account_address: user_account_address1 };
+
+        let (user_public_key2, _user_private_key2, user_account_address2) = ledger.new_allocated_account();
+        let  user_account2 = Account { public_key: user_public_key2, account_address: user_account_address2 };
+
+        let package_address = ledger.compile_and_publish(this_package!());
+
+        // Instantiate Liquify component
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_function(
+                package_address,
+                "Liquify",
+                "instantiate_liquify",
+                manifest_args!(),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+
+        let liquify_component = receipt.expect_commit(true).new_component_addresses()[0];
+        let owner_badge = receipt.expect_commit(true).new_resource_addresses()[0];
+        let liquidity_receipt = receipt.expect_commit(true).new_resource_addresses()[1];
+
+        // Enable the component
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address,
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component,
+                "set_component_status",
+                manifest_args!(true),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+
+        // Setup LSUs and claim NFTs
+        let key = Secp256k1PrivateKey::from_u64(1u64).unwrap().public_key();
+        let validator_address = ledger.get_active_validator_with_key(&key);
+        let validator_info = ledger.get_active_validator_info_by_key(&key);
+        let lsu_resource_address = validator_info.stake_unit_resource;
+        let claim_nft_address = validator_info.claim_nft;
+
+        // Give user1 LSUs to unstake into claim NFTs
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(user_account_address1, XRD, dec!(5000))
+            .take_all_from_worktop(XRD, "xrd")
+            .call_method_with_name_lookup(validator_address, "stake", |lookup| {
+                (lookup.bucket("xrd"),)
+            })
+            .call_method(
+                user_account_address1,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&user_public_key1)],
+        );
+        receipt.expect_commit_success();
+
+        // Set minimum liquidity to 100 for easier testing
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address,
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component,
+                "set_minimum_liquidity",
+                manifest_args!(dec!("100")),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+
+        Self {
+            ledger,
+            admin_account,
+            user_account1,
+            user_account2,
+            package_address,
+            liquify_component,
+            owner_badge,
+            liquidity_receipt,
+            lsu_resource_address,
+            claim_nft_address,
+            validator_address,
+        }
+    }
+
+    pub fn execute_manifest(
+        &mut self,
+        manifest: TransactionManifestV1,
+        account: Account,
+    ) -> TransactionReceipt {
+        let receipt = self.ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&account.public_key)]
+        );
+
+        if receipt.is_commit_success() {
+            common::assert_accounting_invariants(&mut self.ledger, self.liquify_component);
+        }
+
+        receipt
+    }
+}
+
+fn add_liquidity(ledger: &mut TestEnvironment, xrd_amount: Decimal, discount: Decimal, auto_unstake: bool) {
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account2, XRD, xrd_amount)
+        .take_all_from_worktop(XRD, "xrd")
+        .call_method_with_name_lookup(liquify_component, "add_liquidity", |lookup| {(
+            lookup.bucket("xrd"),
+            discount,
+            auto_unstake,
+            false,             // auto_refill
+            dec!("0"),         // refill_threshold
+            dec!("5"),         // automation_fee
+        )})
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+}
+
+fn set_claim_nft_time_adjustment(ledger: &mut TestEnvironment, enabled: bool) {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_amount(ledger.admin_account.account_address, ledger.owner_badge, 1)
+        .call_method(ledger.liquify_component, "set_claim_nft_time_adjustment", manifest_args!(enabled))
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.admin_account.clone());
+    receipt.expect_commit_success();
+}
+
+// Unstakes user1's LSUs at the validator and returns the id and data of the claim NFT received
+fn unstake_at_validator(ledger: &mut TestEnvironment, lsu_amount: Decimal) -> (NonFungibleLocalId, UnstakeData) {
+    let user_account1 = ledger.user_account1.account_address;
+    let lsu_resource_address = ledger.lsu_resource_address;
+    let claim_nft_address = ledger.claim_nft_address;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account1, lsu_resource_address, lsu_amount)
+        .take_all_from_worktop(lsu_resource_address, "lsu")
+        .call_method_with_name_lookup(ledger.validator_address, "unstake", |lookup| {
+            (lookup.bucket("lsu"),)
+        })
+        .call_method(
+            user_account1,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success();
+
+    let vault_id = ledger.ledger.get_component_vaults(user_account1, claim_nft_address)[0];
+    let (_, mut ids) = ledger.ledger.inspect_non_fungible_vault(vault_id).unwrap();
+    let id = ids.next().unwrap();
+    let unstake_data: UnstakeData = ledger.ledger.get_non_fungible_data(claim_nft_address, id.clone());
+
+    (id, unstake_data)
+}
+
+fn liquify_claim_nft(ledger: &mut TestEnvironment) -> TransactionReceipt {
+    let user_account1 = ledger.user_account1.account_address;
+    let liquify_component = ledger.liquify_component;
+    let claim_nft_address = ledger.claim_nft_address;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account1, claim_nft_address, dec!(1))
+        .take_all_from_worktop(claim_nft_address, "claim_nft")
+        .call_method_with_name_lookup(liquify_component, "liquify_claim_nft", |lookup| {
+            (lookup.bucket("claim_nft"), 10u8)
+        })
+        .call_method(
+            user_account1,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    ledger.execute_manifest(manifest, ledger.user_account1.clone())
+}
+
+// Mirrors the blueprint's pricing: the discount is scaled by the epochs left until the claim epoch
+fn expected_xrd(claim_amount: Decimal, discount: Decimal, epochs_to_claim: u64) -> Decimal {
+    let scaled_discount = if epochs_to_claim == 2016 {
+        discount
+    } else {
+        discount * Decimal::from(epochs_to_claim) / Decimal::from(2016u64)
+    };
+    let price = claim_amount * (dec!(1) - scaled_discount);
+    price - price * dec!("0.0005")
+}
+
+#[test]
+fn test_claim_nft_valued_at_full_discount_without_time_adjustment() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account1 = ledger.user_account1.account_address;
+
+    add_liquidity(&mut ledger, dec!(1000), dec!("0.01"), true);
+    set_claim_nft_time_adjustment(&mut ledger, false);
+
+    let (_, unstake_data) = unstake_at_validator(&mut ledger, dec!(100));
+
+    let xrd_before = ledger.ledger.get_component_balance(user_account1, XRD);
+    liquify_claim_nft(&mut ledger).expect_commit_success();
+    let xrd_received = ledger.ledger.get_component_balance(user_account1, XRD) - xrd_before;
+
+    assert_eq!(xrd_received, expected_xrd(unstake_data.claim_amount, dec!("0.01"), 2016));
+    assert_eq!(ledger.ledger.get_component_balance(user_account1, ledger.claim_nft_address), dec!(0));
+}
+
+#[test]
+fn test_claim_nft_time_adjustment_scales_discount() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account1 = ledger.user_account1.account_address;
+
+    add_liquidity(&mut ledger, dec!(1000), dec!("0.01"), true);
+
+    let (_, unstake_data) = unstake_at_validator(&mut ledger, dec!(100));
+    let current_epoch = ledger.ledger.get_current_epoch().number();
+    let epochs_remaining = unstake_data.claim_epoch.number().saturating_sub(current_epoch);
+    assert!(epochs_remaining < 2016, "The claim should mature before a full unbonding period");
+
+    let xrd_before = ledger.ledger.get_component_balance(user_account1, XRD);
+    liquify_claim_nft(&mut ledger).expect_commit_success();
+    let xrd_received = ledger.ledger.get_component_balance(user_account1, XRD) - xrd_before;
+
+    // An NFT close to maturity is bought at a fraction of the position's discount
+    assert_eq!(xrd_received, expected_xrd(unstake_data.claim_amount, dec!("0.01"), epochs_remaining));
+    assert!(xrd_received > expected_xrd(unstake_data.claim_amount, dec!("0.01"), 2016));
+}
+
+#[test]
+fn test_claim_nft_stored_as_unstake_nft_fill() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+    let liquidity_receipt = ledger.liquidity_receipt;
+    let claim_nft_address = ledger.claim_nft_address;
+
+    // A better priced position that keeps LSUs is skipped, claim NFTs only go to auto_unstake positions
+    add_liquidity(&mut ledger, dec!(1000), dec!("0.005"), false);
+    add_liquidity(&mut ledger, dec!(1000), dec!("0.01"), true);
+
+    let (claim_id, _) = unstake_at_validator(&mut ledger, dec!(100));
+    liquify_claim_nft(&mut ledger).expect_commit_success();
+
+    // The auto_unstake receipt collects the very same claim NFT
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(user_account2, liquidity_receipt, vec![NonFungibleLocalId::integer(2)])
+        .take_all_from_worktop(liquidity_receipt, "receipt")
+        .call_method_with_name_lookup(liquify_component, "collect_fills", |lookup| {
+            (lookup.bucket("receipt"), 10u64)
+        })
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+
+    let vault_id = ledger.ledger.get_component_vaults(user_account2, claim_nft_address)[0];
+    let (amount, mut ids) = ledger.ledger.inspect_non_fungible_vault(vault_id).unwrap();
+    assert_eq!(amount, dec!(1));
+    assert_eq!(ids.next().unwrap(), claim_id);
+
+    // The position that keeps LSUs has nothing to collect
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(user_account2, liquidity_receipt, vec![NonFungibleLocalId::integer(1)])
+        .take_all_from_worktop(liquidity_receipt, "receipt")
+        .call_method_with_name_lookup(liquify_component, "collect_fills", |lookup| {
+            (lookup.bucket("receipt"), 10u64)
+        })
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+    assert_eq!(ledger.ledger.get_component_balance(user_account2, ledger.lsu_resource_address), dec!(0));
+}
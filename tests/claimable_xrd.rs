@@ -0,0 +1,420 @@
+use scrypto_test::prelude::*;
+
+mod common;
+
+#[derive(ScryptoSbor, Debug, Clone)]
+pub struct SubPosition {
+    pub buy_list_key: u128,
+    pub discount: Decimal,
+    pub xrd_liquidity_available: Decimal,
+}
+
+#[derive(ScryptoSbor, Debug, Clone)]
+pub struct ReceiptDetailData {
+    pub receipt_id: NonFungibleLocalId,
+    pub discount: Decimal,
+    pub auto_unstake: bool,
+    pub auto_refill: bool,
+    pub refill_threshold: Decimal,
+    pub xrd_liquidity_available: Decimal,
+    pub xrd_liquidity_filled: Decimal,
+    pub liquidity_ahead: Decimal,
+    pub fills_to_collect: u64,
+    pub last_added_epoch: u32,
+    pub claimable_xrd: Decimal,
+    pub total_stake_claim_value: Decimal,
+    pub total_lsu_redemption_value: Decimal,
+    pub automation_fee: Decimal,
+    pub discount_overrides: Vec<(ComponentAddress, Decimal)>,
+    pub annualized_rate: Option<Decimal>,
+    pub effective_discount: Decimal,
+    pub lsu_fills_unbonding: u64,
+    pub redemption_ready_epoch: u64,
+    pub mid_cycle: bool,
+    pub claimable_xrd_balance: Decimal,
+    pub sub_positions: Vec<SubPosition>,
+    pub ladder_weights: Vec<(Decimal, Decimal)>,
+}
+
+// Mirrors the blueprint's `DustPolicy` so it can be passed in manifests
+#[derive(ManifestSbor, Clone, Copy)]
+pub enum DustPolicy {
+    SweepIntoFill,
+    MoveToClaimable,
+}
+
+#[derive(Clone)]
+pub struct Account {
+    public_key: Secp256k1PublicKey,
+    account_address: ComponentAddress,
+}
+
+pub struct TestEnvironment {
+    pub ledger: LedgerSimulator<NoExtension, InMemorySubstateDatabase>,
+    pub admin_account: Account,
+    pub user_account1: Account,
+    pub user_account2: Account,
+    pub package_address: PackageAddress,
+    pub liquify_component: ComponentAddress,
+    pub owner_badge: ResourceAddress,
+    pub liquidity_receipt: ResourceAddress,
+    pub lsu_resource_address: ResourceAddress,
+}
+
+impl TestEnvironment {
+    pub fn instantiate_test() -> Self {
+        let mut ledger = LedgerSimulatorBuilder::new()
+            .without_kernel_trace()
+            .build();
+
+        // Create accounts
+        let (admin_public_key, _admin_private_key, admin_account_address) = ledger.new_allocated_account();
+        let admin_account = Account { public_key: admin_public_key, account_address: admin_account_address };
+
+        let (user_public_key1, _user_private_key1, user_account_address1) = ledger.new_allocated_account();
+        let user_account1 = Account { public_key: user_public_key1, account_address: user_account_address1 };
+
+        let (user_public_key2, _user_private_key2, user_account_address2) = ledger.new_allocated_account();
+        let  user_account2 = Account { public_key: user_public_key2, account_address: user_account_address2 };
+
+        let package_address = ledger.compile_and_publish(this_package!());
+
+        // Instantiate Liquify component
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_function(
+                package_address,
+                "Liquify",
+                "instantiate_liquify",
+                manifest_args!(),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+
+        let liquify_component = receipt.expect_commit(true).new_component_addresses()[0];
+        let owner_badge = receipt.expect_commit(true).new_resource_addresses()[0];
+        let liquidity_receipt = receipt.expect_commit(true).new_resource_addresses()[1];
+
+        // Enable the component
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address,
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component,
+                "set_component_status",
+                manifest_args!(true),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+
+        // Setup LSUs
+        let key = Secp256k1PrivateKey::from_u64(1u64).unwrap().public_key();
+        let validator_address = ledger.get_active_validator_with_key(&key);
+        let validator_info = ledger.get_active_validator_info_by_key(&key);
+        let lsu_resource_address = validator_info.stake_unit_resource;
+
+        // Give user1 LSUs for unstaking
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(user_account_address1, XRD, dec!(5000))
+            .take_all_from_worktop(XRD, "xrd")
+            .call_method_with_name_lookup(validator_address, "stake", |lookup| {
+                (lookup.bucket("xrd"),)
+            })
+            .call_method(
+                user_account_address1,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&user_public_key1)],
+        );
+        receipt.expect_commit_success();
+
+        // Set small order threshold to 1 XRD so small unstakes reach auto_unstake positions
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address,
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component,
+                "set_small_order_threshold",
+                manifest_args!(dec!("1")),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+
+        // Set minimum liquidity to 100 for easier testing
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address,
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component,
+                "set_minimum_liquidity",
+                manifest_args!(dec!("100")),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+
+        Self {
+            ledger,
+            admin_account,
+            user_account1,
+            user_account2,
+            package_address,
+            liquify_component,
+            owner_badge,
+            liquidity_receipt,
+            lsu_resource_address,
+        }
+    }
+
+    pub fn execute_manifest(
+        &mut self,
+        manifest: TransactionManifestV1,
+        account: Account,
+    ) -> TransactionReceipt {
+        let receipt = self.ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&account.public_key)]
+        );
+
+        if receipt.is_commit_success() {
+            common::assert_accounting_invariants(&mut self.ledger, self.liquify_component);
+        }
+
+        receipt
+    }
+}
+
+fn add_liquidity(ledger: &mut TestEnvironment, xrd_amount: Decimal) {
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account2, XRD, xrd_amount)
+        .take_all_from_worktop(XRD, "xrd")
+        .call_method_with_name_lookup(liquify_component, "add_liquidity", |lookup| {(
+            lookup.bucket("xrd"),
+            dec!("0.01"),      // 1% discount
+            false,             // auto_unstake
+            false,             // auto_refill
+            dec!("0"),         // refill_threshold
+            dec!("5"),         // automation_fee
+        )})
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+}
+
+fn unstake(ledger: &mut TestEnvironment, lsu_amount: Decimal) {
+    let user_account1 = ledger.user_account1.account_address;
+    let liquify_component = ledger.liquify_component;
+    let lsu_resource_address = ledger.lsu_resource_address;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account1, lsu_resource_address, lsu_amount)
+        .take_all_from_worktop(lsu_resource_address, "lsu")
+        .call_method_with_name_lookup(liquify_component, "liquify_unstake", |lookup| {
+            (lookup.bucket("lsu"), 10u8)
+        })
+        .call_method(
+            user_account1,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success();
+}
+
+fn get_receipt_detail(ledger: &mut TestEnvironment, receipt_id: u64) -> ReceiptDetailData {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(ledger.liquify_component, "get_receipt_detail", manifest_args!(NonFungibleLocalId::integer(receipt_id)))
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success().output(1)
+}
+
+fn set_dust_threshold(ledger: &mut TestEnvironment, threshold: Decimal) {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_amount(ledger.admin_account.account_address, ledger.owner_badge, 1)
+        .call_method(ledger.liquify_component, "set_dust_threshold", manifest_args!(threshold))
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.admin_account.clone());
+    receipt.expect_commit_success();
+}
+
+fn set_dust_policy(ledger: &mut TestEnvironment, policy: DustPolicy) {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_amount(ledger.admin_account.account_address, ledger.owner_badge, 1)
+        .call_method(ledger.liquify_component, "set_dust_policy", manifest_args!(policy))
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.admin_account.clone());
+    receipt.expect_commit_success();
+}
+
+fn get_buy_list(ledger: &mut TestEnvironment) -> Vec<(u128, NonFungibleGlobalId)> {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(ledger.liquify_component, "get_raw_buy_list_range", manifest_args!(0u64, 10u64))
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success().output(1)
+}
+
+fn withdraw_claimable(ledger: &mut TestEnvironment, receipt_id: u64) -> TransactionReceipt {
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+    let liquidity_receipt = ledger.liquidity_receipt;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_non_fungibles(user_account2, liquidity_receipt, vec![NonFungibleLocalId::integer(receipt_id)])
+        .pop_from_auth_zone("receipt_proof")
+        .call_method_with_name_lookup(liquify_component, "withdraw_claimable", |lookup| {
+            (lookup.proof("receipt_proof"),)
+        })
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    ledger.execute_manifest(manifest, ledger.user_account2.clone())
+}
+
+#[test]
+fn test_withdraw_claimable_dust_residue() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account2 = ledger.user_account2.account_address;
+
+    // Anything under 5 XRD left in a position is dust
+    set_dust_threshold(&mut ledger, dec!(5));
+    add_liquidity(&mut ledger, dec!(100));
+
+    // Leaves a residue of about 3 XRD, which is credited to the receipt's claimable balance
+    unstake(&mut ledger, dec!(98));
+
+    let detail = get_receipt_detail(&mut ledger, 1);
+    let residue = detail.claimable_xrd_balance;
+    assert!(residue > dec!(0) && residue < dec!(5));
+    assert_eq!(detail.xrd_liquidity_available, dec!(0));
+    assert_eq!(residue, dec!(100) - detail.xrd_liquidity_filled);
+
+    let xrd_before = ledger.ledger.get_component_balance(user_account2, XRD);
+    withdraw_claimable(&mut ledger, 1).expect_commit_success();
+    assert_eq!(ledger.ledger.get_component_balance(user_account2, XRD) - xrd_before, residue);
+    assert_eq!(get_receipt_detail(&mut ledger, 1).claimable_xrd_balance, dec!(0));
+
+    // The balance can only be withdrawn once
+    withdraw_claimable(&mut ledger, 1).expect_commit_failure();
+}
+
+#[test]
+fn test_partial_fill_sweeps_dust_into_fill() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account1 = ledger.user_account1.account_address;
+
+    set_dust_threshold(&mut ledger, dec!(5));
+    set_dust_policy(&mut ledger, DustPolicy::SweepIntoFill);
+    add_liquidity(&mut ledger, dec!(100));
+    assert_eq!(get_buy_list(&mut ledger).len(), 1);
+
+    // 98 LSU at a 1% discount is worth 97.02 XRD, the 2.98 XRD left over is swept into the fill
+    let xrd_before = ledger.ledger.get_component_balance(user_account1, XRD);
+    unstake(&mut ledger, dec!(98));
+    let xrd_received = ledger.ledger.get_component_balance(user_account1, XRD) - xrd_before;
+    assert_eq!(xrd_received, dec!(100) * (dec!(1) - dec!("0.0005")));
+
+    let detail = get_receipt_detail(&mut ledger, 1);
+    assert_eq!(detail.xrd_liquidity_filled, dec!(100));
+    assert_eq!(detail.xrd_liquidity_available, dec!(0));
+    assert_eq!(detail.claimable_xrd_balance, dec!(0));
+    assert!(get_buy_list(&mut ledger).is_empty());
+}
+
+#[test]
+fn test_partial_fill_moves_dust_to_claimable() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account1 = ledger.user_account1.account_address;
+
+    set_dust_threshold(&mut ledger, dec!(5));
+    set_dust_policy(&mut ledger, DustPolicy::MoveToClaimable);
+    add_liquidity(&mut ledger, dec!(100));
+    assert_eq!(get_buy_list(&mut ledger).len(), 1);
+
+    // The seller is paid only the discounted value, the residue is set aside for the liquidity provider
+    let xrd_before = ledger.ledger.get_component_balance(user_account1, XRD);
+    unstake(&mut ledger, dec!(98));
+    let xrd_received = ledger.ledger.get_component_balance(user_account1, XRD) - xrd_before;
+
+    let detail = get_receipt_detail(&mut ledger, 1);
+    assert_eq!(xrd_received, detail.xrd_liquidity_filled * (dec!(1) - dec!("0.0005")));
+    assert!(detail.xrd_liquidity_filled < dec!(100));
+    assert_eq!(detail.xrd_liquidity_available, dec!(0));
+    assert_eq!(detail.claimable_xrd_balance, dec!(100) - detail.xrd_liquidity_filled);
+    assert!(get_buy_list(&mut ledger).is_empty());
+}
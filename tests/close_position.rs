@@ -0,0 +1,593 @@
+use scrypto_test::prelude::*;
+
+mod common;
+
+#[derive(ScryptoSbor, Debug, Clone)]
+pub struct SubPosition {
+    pub buy_list_key: u128,
+    pub discount: Decimal,
+    pub xrd_liquidity_available: Decimal,
+}
+
+#[derive(ScryptoSbor, Debug, Clone)]
+pub struct ReceiptDetailData {
+    pub receipt_id: NonFungibleLocalId,
+    pub discount: Decimal,
+    pub auto_unstake: bool,
+    pub auto_refill: bool,
+    pub refill_threshold: Decimal,
+    pub xrd_liquidity_available: Decimal,
+    pub xrd_liquidity_filled: Decimal,
+    pub liquidity_ahead: Decimal,
+    pub fills_to_collect: u64,
+    pub last_added_epoch: u32,
+    pub claimable_xrd: Decimal,
+    pub total_stake_claim_value: Decimal,
+    pub total_lsu_redemption_value: Decimal,
+    pub automation_fee: Decimal,
+    pub discount_overrides: Vec<(ComponentAddress, Decimal)>,
+    pub annualized_rate: Option<Decimal>,
+    pub effective_discount: Decimal,
+    pub lsu_fills_unbonding: u64,
+    pub redemption_ready_epoch: u64,
+    pub mid_cycle: bool,
+    pub claimable_xrd_balance: Decimal,
+    pub sub_positions: Vec<SubPosition>,
+    pub ladder_weights: Vec<(Decimal, Decimal)>,
+}
+
+#[derive(Clone)]
+pub struct Account {
+    public_key: Secp256k1PublicKey,
+    account_address: ComponentAddress,
+}
+
+pub struct TestEnvironment {
+    pub ledger: LedgerSimulator<NoExtension, InMemorySubstateDatabase>,
+    pub admin_account: Account,
+    pub user_account1: Account,
+    pub user_account2: Account,
+    pub package_address: PackageAddress,
+    pub liquify_component: ComponentAddress,
+    pub owner_badge: ResourceAddress,
+    pub liquidity_receipt: ResourceAddress,
+    pub lsu_resource_address: ResourceAddress,
+}
+
+impl TestEnvironment {
+    pub fn instantiate_test() -> Self {
+        let mut ledger = LedgerSimulatorBuilder::new()
+            .without_kernel_trace()
+            .build();
+
+        // Create accounts
+        let (admin_public_key, _admin_private_key, admin_account_address) = ledger.new_allocated_account();
+        let admin_account = Account { public_key: admin_public_key, account_address: admin_account_address };
+
+        let (user_public_key1, _user_private_key1, user_account_address1) = ledger.new_allocated_account();
+        let user_account1 = Account { public_key: user_public_key1, account_address: user_account_address1 };
+
+        let (user_public_key2, _user_private_key2, user_account_address2) = ledger.new_allocated_account();
+        let  user_account2 = Account { public_key: user_public_key2, account_address: user_account_address2 };
+
+        let package_address = ledger.compile_and_publish(this_package!());
+
+        // Instantiate Liquify component
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_function(
+                package_address,
+                "Liquify",
+                "instantiate_liquify",
+                manifest_args!(),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+
+        let liquify_component = receipt.expect_commit(true).new_component_addresses()[0];
+        let owner_badge = receipt.expect_commit(true).new_resource_addresses()[0];
+        let liquidity_receipt = receipt.expect_commit(true).new_resource_addresses()[1];
+
+        // Enable the component
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address,
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component,
+                "set_component_status",
+                manifest_args!(true),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+
+        // Setup LSUs
+        let key = Secp256k1PrivateKey::from_u64(1u64).unwrap().public_key();
+        let validator_address = ledger.get_active_validator_with_key(&key);
+        let validator_info = ledger.get_active_validator_info_by_key(&key);
+        let lsu_resource_address = validator_info.stake_unit_resource;
+
+        // Give user1 LSUs for unstaking
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(user_account_address1, XRD, dec!(5000))
+            .take_all_from_worktop(XRD, "xrd")
+            .call_method_with_name_lookup(validator_address, "stake", |lookup| {
+                (lookup.bucket("xrd"),)
+            })
+            .call_method(
+                user_account_address1,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&user_public_key1)],
+        );
+        receipt.expect_commit_success();
+
+        // Set small order threshold to 1 XRD so small unstakes reach auto_unstake positions
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address,
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component,
+                "set_small_order_threshold",
+                manifest_args!(dec!("1")),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+
+        // Set minimum liquidity to 100 for easier testing
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address,
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component,
+                "set_minimum_liquidity",
+                manifest_args!(dec!("100")),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+
+        Self {
+            ledger,
+            admin_account,
+            user_account1,
+            user_account2,
+            package_address,
+            liquify_component,
+            owner_badge,
+            liquidity_receipt,
+            lsu_resource_address,
+        }
+    }
+
+    pub fn execute_manifest(
+        &mut self,
+        manifest: TransactionManifestV1,
+        account: Account,
+    ) -> TransactionReceipt {
+        let receipt = self.ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&account.public_key)]
+        );
+
+        if receipt.is_commit_success() {
+            common::assert_accounting_invariants(&mut self.ledger, self.liquify_component);
+        }
+
+        receipt
+    }
+}
+
+fn unstake(ledger: &mut TestEnvironment, lsu_amount: Decimal) {
+    let user_account1 = ledger.user_account1.account_address;
+    let liquify_component = ledger.liquify_component;
+    let lsu_resource_address = ledger.lsu_resource_address;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account1, lsu_resource_address, lsu_amount)
+        .take_all_from_worktop(lsu_resource_address, "lsu")
+        .call_method_with_name_lookup(liquify_component, "liquify_unstake", |lookup| {
+            (lookup.bucket("lsu"), 10u8)
+        })
+        .call_method(
+            user_account1,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success();
+}
+
+fn get_receipt_detail(ledger: &mut TestEnvironment, receipt_id: u64) -> ReceiptDetailData {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(ledger.liquify_component, "get_receipt_detail", manifest_args!(NonFungibleLocalId::integer(receipt_id)))
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success().output(1)
+}
+
+fn add_liquidity(ledger: &mut TestEnvironment, xrd_amount: Decimal, automated: bool) {
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account2, XRD, xrd_amount)
+        .take_all_from_worktop(XRD, "xrd")
+        .call_method_with_name_lookup(liquify_component, "add_liquidity", |lookup| {(
+            lookup.bucket("xrd"),
+            dec!("0.01"),      // 1% discount
+            automated,         // auto_unstake
+            automated,         // auto_refill
+            dec!("0"),         // refill_threshold
+            dec!("5"),         // automation_fee
+        )})
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+}
+
+fn set_max_fills_per_cycle(ledger: &mut TestEnvironment, max_fills: u64) {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_amount(ledger.admin_account.account_address, ledger.owner_badge, 1)
+        .call_method(ledger.liquify_component, "set_max_fills_per_cycle", manifest_args!(max_fills))
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.admin_account.clone());
+    receipt.expect_commit_success();
+}
+
+fn set_delivery_account(ledger: &mut TestEnvironment, receipt_id: u64) {
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+    let liquidity_receipt = ledger.liquidity_receipt;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(user_account2, liquidity_receipt, vec![NonFungibleLocalId::integer(receipt_id)])
+        .take_all_from_worktop(liquidity_receipt, "receipt")
+        .call_method_with_name_lookup(liquify_component, "set_delivery_account", |lookup| {
+            (lookup.bucket("receipt"), Some(user_account2))
+        })
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+}
+
+fn deposit_claimable(ledger: &mut TestEnvironment, receipt_id: u64, xrd_amount: Decimal) {
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+    let liquidity_receipt = ledger.liquidity_receipt;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_non_fungibles(user_account2, liquidity_receipt, vec![NonFungibleLocalId::integer(receipt_id)])
+        .pop_from_auth_zone("receipt_proof")
+        .withdraw_from_account(user_account2, XRD, xrd_amount)
+        .take_all_from_worktop(XRD, "xrd")
+        .call_method_with_name_lookup(liquify_component, "deposit_claimable", |lookup| {
+            (lookup.proof("receipt_proof"), lookup.bucket("xrd"))
+        })
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+}
+
+fn withdraw_claimable(ledger: &mut TestEnvironment, receipt_id: u64) {
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+    let liquidity_receipt = ledger.liquidity_receipt;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_non_fungibles(user_account2, liquidity_receipt, vec![NonFungibleLocalId::integer(receipt_id)])
+        .pop_from_auth_zone("receipt_proof")
+        .call_method_with_name_lookup(liquify_component, "withdraw_claimable", |lookup| {
+            (lookup.proof("receipt_proof"),)
+        })
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+}
+
+fn remove_liquidity(ledger: &mut TestEnvironment, receipt_id: u64) {
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+    let liquidity_receipt = ledger.liquidity_receipt;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(user_account2, liquidity_receipt, vec![NonFungibleLocalId::integer(receipt_id)])
+        .take_all_from_worktop(liquidity_receipt, "receipt")
+        .call_method_with_name_lookup(liquify_component, "remove_liquidity", |lookup| {
+            (lookup.bucket("receipt"),)
+        })
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+}
+
+fn collect_fills(ledger: &mut TestEnvironment, receipt_id: u64) {
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+    let liquidity_receipt = ledger.liquidity_receipt;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(user_account2, liquidity_receipt, vec![NonFungibleLocalId::integer(receipt_id)])
+        .take_all_from_worktop(liquidity_receipt, "receipt")
+        .call_method_with_name_lookup(liquify_component, "collect_fills", |lookup| {
+            (lookup.bucket("receipt"), 10u64)
+        })
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+}
+
+fn cycle(ledger: &mut TestEnvironment, receipt_id: u64) {
+    let user_account1 = ledger.user_account1.account_address;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(
+            ledger.liquify_component,
+            "cycle_liquidity",
+            manifest_args!(vec![NonFungibleLocalId::integer(receipt_id)]),
+        )
+        .call_method(
+            user_account1,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success();
+}
+
+fn deliver_fills(ledger: &mut TestEnvironment, receipt_id: u64) {
+    let user_account1 = ledger.user_account1.account_address;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(ledger.liquify_component, "deliver_fills", manifest_args!(vec![NonFungibleLocalId::integer(receipt_id)], 10u64))
+        .call_method(
+            user_account1,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success();
+}
+
+fn close_position(ledger: &mut TestEnvironment, receipt_ids: Vec<u64>) -> TransactionReceipt {
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+    let liquidity_receipt = ledger.liquidity_receipt;
+    let receipt_ids: Vec<NonFungibleLocalId> = receipt_ids.into_iter().map(NonFungibleLocalId::integer).collect();
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(user_account2, liquidity_receipt, receipt_ids)
+        .take_all_from_worktop(liquidity_receipt, "receipt")
+        .call_method_with_name_lookup(liquify_component, "close_position", |lookup| {
+            (lookup.bucket("receipt"),)
+        })
+        .build();
+
+    ledger.execute_manifest(manifest, ledger.user_account2.clone())
+}
+
+fn receipt_exists(ledger: &mut TestEnvironment, receipt_id: u64) -> bool {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(ledger.liquify_component, "get_receipt_detail", manifest_args!(NonFungibleLocalId::integer(receipt_id)))
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.is_commit_success()
+}
+
+#[test]
+fn test_close_settled_position() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account2 = ledger.user_account2.account_address;
+    let liquidity_receipt = ledger.liquidity_receipt;
+
+    add_liquidity(&mut ledger, dec!(100), false);
+    set_delivery_account(&mut ledger, 1);
+
+    // Use up the position and take its fills
+    unstake(&mut ledger, dec!(150));
+    collect_fills(&mut ledger, 1);
+
+    let detail = get_receipt_detail(&mut ledger, 1);
+    assert_eq!(detail.xrd_liquidity_available, dec!(0));
+    assert_eq!(detail.fills_to_collect, 0);
+    assert_eq!(detail.claimable_xrd_balance, dec!(0));
+
+    close_position(&mut ledger, vec![1]).expect_commit_success();
+    assert_eq!(ledger.ledger.get_component_balance(user_account2, liquidity_receipt), dec!(0));
+
+    // The receipt's entry is gone and the delivery account with it, so keepers can no longer serve it
+    assert!(!receipt_exists(&mut ledger, 1));
+    deliver_fills(&mut ledger, 1);
+}
+
+#[test]
+fn test_close_position_rejects_wrong_bucket() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+
+    // Only liquidity receipts can be closed
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account2, XRD, dec!(1))
+        .take_all_from_worktop(XRD, "xrd")
+        .call_method_with_name_lookup(liquify_component, "close_position", |lookup| {
+            (lookup.bucket("xrd"),)
+        })
+        .build();
+    ledger.execute_manifest(manifest, ledger.user_account2.clone()).expect_commit_failure();
+
+    // Both positions are settled but only one can be closed per call
+    add_liquidity(&mut ledger, dec!(100), false);
+    add_liquidity(&mut ledger, dec!(100), false);
+    remove_liquidity(&mut ledger, 1);
+    remove_liquidity(&mut ledger, 2);
+
+    close_position(&mut ledger, vec![1, 2]).expect_commit_failure();
+    close_position(&mut ledger, vec![1]).expect_commit_success();
+    close_position(&mut ledger, vec![2]).expect_commit_success();
+}
+
+#[test]
+fn test_close_position_rejects_available_liquidity() {
+    let mut ledger = TestEnvironment::instantiate_test();
+
+    add_liquidity(&mut ledger, dec!(100), false);
+    close_position(&mut ledger, vec![1]).expect_commit_failure();
+
+    remove_liquidity(&mut ledger, 1);
+    close_position(&mut ledger, vec![1]).expect_commit_success();
+}
+
+#[test]
+fn test_close_position_rejects_uncollected_fills() {
+    let mut ledger = TestEnvironment::instantiate_test();
+
+    add_liquidity(&mut ledger, dec!(100), false);
+    unstake(&mut ledger, dec!(150));
+    assert_eq!(get_receipt_detail(&mut ledger, 1).xrd_liquidity_available, dec!(0));
+    close_position(&mut ledger, vec![1]).expect_commit_failure();
+
+    collect_fills(&mut ledger, 1);
+    close_position(&mut ledger, vec![1]).expect_commit_success();
+}
+
+#[test]
+fn test_close_position_rejects_claimable_balance() {
+    let mut ledger = TestEnvironment::instantiate_test();
+
+    add_liquidity(&mut ledger, dec!(100), false);
+    remove_liquidity(&mut ledger, 1);
+    deposit_claimable(&mut ledger, 1, dec!(5));
+    close_position(&mut ledger, vec![1]).expect_commit_failure();
+
+    withdraw_claimable(&mut ledger, 1);
+    close_position(&mut ledger, vec![1]).expect_commit_success();
+}
+
+#[test]
+fn test_close_position_rejects_partial_cycle() {
+    let mut ledger = TestEnvironment::instantiate_test();
+
+    // Three fills with room to cycle two of them leaves the position mid-cycle
+    set_max_fills_per_cycle(&mut ledger, 2);
+    add_liquidity(&mut ledger, dec!(1000), true);
+    for _ in 0..3 {
+        unstake(&mut ledger, dec!(100));
+    }
+    ledger.ledger.advance_to_round(Round::of(300));
+    cycle(&mut ledger, 1);
+
+    // Settle everything else so only the partial cycle is in the way
+    remove_liquidity(&mut ledger, 1);
+    collect_fills(&mut ledger, 1);
+
+    let detail = get_receipt_detail(&mut ledger, 1);
+    assert!(detail.mid_cycle);
+    assert_eq!(detail.xrd_liquidity_available, dec!(0));
+    assert_eq!(detail.fills_to_collect, 0);
+    assert_eq!(detail.claimable_xrd_balance, dec!(0));
+
+    close_position(&mut ledger, vec![1]).expect_commit_failure();
+}
@@ -0,0 +1,407 @@
+use scrypto_test::prelude::*;
+
+mod common;
+
+#[derive(Clone)]
+pub struct Account {
+    public_key: Secp256k1PublicKey,
+    account_address: ComponentAddress,
+}
+
+pub struct TestEnvironment {
+    pub ledger: LedgerSimulator<NoExtension, InMemorySubstateDatabase>,
+    pub admin_account: Account,
+    pub user_account1: Account,
+    pub user_account2: Account,
+    pub package_address: PackageAddress,
+    pub liquify_component: ComponentAddress,
+    pub owner_badge: ResourceAddress,
+    pub liquidity_receipt: ResourceAddress,
+    pub lsu_resource_address: ResourceAddress,
+    pub claim_nft_address: ResourceAddress,
+    pub validator_address: ComponentAddress,
+}
+
+impl TestEnvironment {
+    pub fn instantiate_test() -> Self {
+        let mut ledger = LedgerSimulatorBuilder::new()
+            .without_kernel_trace()
+            .build();
+
+        // Create accounts
+        let (admin_public_key, _admin_private_key, admin_account_address) = ledger.new_allocated_account();
+        let admin_account = Account { public_key: admin_public_key, account_address: admin_account_address };
+
+        let (user_public_key1, _user_private_key1, user_account_address1) = ledger.new_allocated_account();
+        let user_account1 = Account { public_key: user_public_key1, account_address: user_account_address1 };
+
+        let (user_public_key2, _user_private_key2, user_account_address2) = ledger.new_allocated_account();
+        let  user_account2 = Account { public_key: user_public_key2, account_address: user_account_address2 };
+
+        let package_address = ledger.compile_and_publish(this_package!());
+
+        // Instantiate Liquify component
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_function(
+                package_address,
+                "Liquify",
+                "instantiate_liquify",
+                manifest_args!(),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+
+        let liquify_component = receipt.expect_commit(true).new_component_addresses()[0];
+        let owner_badge = receipt.expect_commit(true).new_resource_addresses()[0];
+        let liquidity_receipt = receipt.expect_commit(true).new_resource_addresses()[1];
+
+        // Enable the component
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address,
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component,
+                "set_component_status",
+                manifest_args!(true),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+
+        // Setup LSUs
+        let key = Secp256k1PrivateKey::from_u64(1u64).unwrap().public_key();
+        let validator_address = ledger.get_active_validator_with_key(&key);
+        let validator_info = ledger.get_active_validator_info_by_key(&key);
+        let lsu_resource_address = validator_info.stake_unit_resource;
+        let claim_nft_address = validator_info.claim_nft;
+
+        // Give user1 LSUs for unstaking
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(user_account_address1, XRD, dec!(5000))
+            .take_all_from_worktop(XRD, "xrd")
+            .call_method_with_name_lookup(validator_address, "stake", |lookup| {
+                (lookup.bucket("xrd"),)
+            })
+            .call_method(
+                user_account_address1,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&user_public_key1)],
+        );
+        receipt.expect_commit_success();
+
+        // Set small order threshold to 1 XRD so small unstakes reach auto_unstake positions
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address,
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component,
+                "set_small_order_threshold",
+                manifest_args!(dec!("1")),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+
+        // Set minimum liquidity to 100 for easier testing
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address,
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component,
+                "set_minimum_liquidity",
+                manifest_args!(dec!("100")),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+
+        Self {
+            ledger,
+            admin_account,
+            user_account1,
+            user_account2,
+            package_address,
+            liquify_component,
+            owner_badge,
+            liquidity_receipt,
+            lsu_resource_address,
+            claim_nft_address,
+            validator_address,
+        }
+    }
+
+    pub fn execute_manifest(
+        &mut self,
+        manifest: TransactionManifestV1,
+        account: Account,
+    ) -> TransactionReceipt {
+        let receipt = self.ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&account.public_key)]
+        );
+
+        if receipt.is_commit_success() {
+            common::assert_accounting_invariants(&mut self.ledger, self.liquify_component);
+        }
+
+        receipt
+    }
+}
+
+// Mirrors the blueprint's `FillFilter` so it can be passed in manifests
+#[derive(ManifestSbor, Clone, Copy)]
+pub enum FillFilter {
+    All,
+    Resource(ResourceAddress),
+    ClaimableNow,
+    LSUOnly,
+}
+
+fn add_liquidity(ledger: &mut TestEnvironment, xrd_amount: Decimal, auto_unstake: bool) {
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account2, XRD, xrd_amount)
+        .take_all_from_worktop(XRD, "xrd")
+        .call_method_with_name_lookup(liquify_component, "add_liquidity", |lookup| {(
+            lookup.bucket("xrd"),
+            dec!("0.01"),      // 1% discount
+            auto_unstake,
+            false,             // auto_refill
+            dec!("0"),         // refill_threshold
+            dec!("5"),         // automation_fee
+        )})
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+}
+
+fn unstake(ledger: &mut TestEnvironment, lsu_amount: Decimal) {
+    let user_account1 = ledger.user_account1.account_address;
+    let liquify_component = ledger.liquify_component;
+    let lsu_resource_address = ledger.lsu_resource_address;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account1, lsu_resource_address, lsu_amount)
+        .take_all_from_worktop(lsu_resource_address, "lsu")
+        .call_method_with_name_lookup(liquify_component, "liquify_unstake", |lookup| {
+            (lookup.bucket("lsu"), 10u8)
+        })
+        .call_method(
+            user_account1,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success();
+}
+
+// Calls a fill collection method with all of user2's liquidity receipts, passing the filter if one is given
+fn collect_with_receipts(ledger: &mut TestEnvironment, method_name: &str, filter: Option<FillFilter>) {
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+    let liquidity_receipt = ledger.liquidity_receipt;
+    let receipt_count = ledger.ledger.get_component_balance(user_account2, liquidity_receipt);
+
+    let builder = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account2, liquidity_receipt, receipt_count)
+        .take_all_from_worktop(liquidity_receipt, "receipts");
+
+    let builder = match filter {
+        Some(filter) => builder.call_method_with_name_lookup(liquify_component, method_name, |lookup| {
+            (lookup.bucket("receipts"), 10u64, filter)
+        }),
+        None => builder.call_method_with_name_lookup(liquify_component, method_name, |lookup| {
+            (lookup.bucket("receipts"), 10u64)
+        }),
+    };
+
+    let manifest = builder
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+}
+
+fn claim_nft_ids(ledger: &mut TestEnvironment, account: ComponentAddress) -> Vec<NonFungibleLocalId> {
+    match ledger.ledger.get_component_vaults(account, ledger.claim_nft_address).first() {
+        Some(vault_id) => ledger.ledger.inspect_non_fungible_vault(*vault_id).unwrap().1.collect(),
+        None => Vec::new(),
+    }
+}
+
+#[test]
+fn test_collect_fills_as_xrd_claims_only_matured_nfts() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account2 = ledger.user_account2.account_address;
+
+    add_liquidity(&mut ledger, dec!(1000), true);
+
+    // One fill matures, the second is still unbonding
+    unstake(&mut ledger, dec!(100));
+    ledger.ledger.advance_to_round(Round::of(300));
+    unstake(&mut ledger, dec!(100));
+
+    let xrd_before = ledger.ledger.get_component_balance(user_account2, XRD);
+    collect_with_receipts(&mut ledger, "collect_fills_as_xrd", None);
+    let xrd_claimed = ledger.ledger.get_component_balance(user_account2, XRD) - xrd_before;
+
+    // Only the matured NFT was claimed, at its full claim amount
+    assert!(xrd_claimed > dec!(0), "The matured fill should have been claimed as XRD");
+    assert!(xrd_claimed < dec!(150), "The immature fill should not have been claimed");
+
+    // Nothing else is claimable until the second NFT matures
+    let xrd_before = ledger.ledger.get_component_balance(user_account2, XRD);
+    collect_with_receipts(&mut ledger, "collect_fills_as_xrd", None);
+    assert_eq!(ledger.ledger.get_component_balance(user_account2, XRD), xrd_before);
+
+    // The immature NFT stayed in the order fill tree and is still collected as a claim NFT
+    collect_with_receipts(&mut ledger, "collect_fills", None);
+    assert_eq!(claim_nft_ids(&mut ledger, user_account2).len(), 1);
+}
+
+#[test]
+fn test_collect_fills_filtered_by_each_filter() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account2 = ledger.user_account2.account_address;
+    let lsu_resource_address = ledger.lsu_resource_address;
+    let claim_nft_address = ledger.claim_nft_address;
+
+    // Receipt 1 keeps LSUs and is used up first, receipt 2 receives unstake NFTs
+    add_liquidity(&mut ledger, dec!(100), false);
+    add_liquidity(&mut ledger, dec!(1000), true);
+    unstake(&mut ledger, dec!(150));
+
+    // ClaimableNow skips LSUs and unstake NFTs that are still unbonding
+    collect_with_receipts(&mut ledger, "collect_fills_filtered", Some(FillFilter::ClaimableNow));
+    assert_eq!(ledger.ledger.get_component_balance(user_account2, lsu_resource_address), dec!(0));
+    assert_eq!(claim_nft_ids(&mut ledger, user_account2).len(), 0);
+
+    // Once matured the NFT is collected, a newer unbonding NFT is not
+    ledger.ledger.advance_to_round(Round::of(300));
+    unstake(&mut ledger, dec!(50));
+    collect_with_receipts(&mut ledger, "collect_fills_filtered", Some(FillFilter::ClaimableNow));
+    assert_eq!(ledger.ledger.get_component_balance(user_account2, lsu_resource_address), dec!(0));
+    assert_eq!(claim_nft_ids(&mut ledger, user_account2).len(), 1);
+
+    // Resource only collects fills of the given resource
+    collect_with_receipts(&mut ledger, "collect_fills_filtered", Some(FillFilter::Resource(claim_nft_address)));
+    assert_eq!(ledger.ledger.get_component_balance(user_account2, lsu_resource_address), dec!(0));
+    assert_eq!(claim_nft_ids(&mut ledger, user_account2).len(), 2);
+
+    // LSUOnly collects the LSU fill left behind by every other filter
+    collect_with_receipts(&mut ledger, "collect_fills_filtered", Some(FillFilter::LSUOnly));
+    assert!(ledger.ledger.get_component_balance(user_account2, lsu_resource_address) > dec!(0));
+
+    // All collects whatever kind of fill is left
+    unstake(&mut ledger, dec!(50));
+    collect_with_receipts(&mut ledger, "collect_fills_filtered", Some(FillFilter::All));
+    assert_eq!(claim_nft_ids(&mut ledger, user_account2).len(), 3);
+}
+
+#[test]
+fn test_collect_fills_returns_one_bucket_per_resource() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+    let liquidity_receipt = ledger.liquidity_receipt;
+    let lsu_resource_address = ledger.lsu_resource_address;
+
+    // Receipt 1 keeps several LSU fills, receipt 2 receives several unstake NFTs
+    add_liquidity(&mut ledger, dec!(100), false);
+    add_liquidity(&mut ledger, dec!(1000), true);
+    unstake(&mut ledger, dec!(50));
+    unstake(&mut ledger, dec!(50));
+    unstake(&mut ledger, dec!(100));
+    unstake(&mut ledger, dec!(100));
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account2, liquidity_receipt, dec!(2))
+        .take_all_from_worktop(liquidity_receipt, "receipts")
+        .call_method_with_name_lookup(liquify_component, "collect_fills", |lookup| {
+            (lookup.bucket("receipts"), 10u64)
+        })
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    let (fill_buckets, _receipts): (Vec<Bucket>, Bucket) = receipt.expect_commit_success().output(3);
+
+    // Fills of the same LSU and of the same claim NFT resource come back merged
+    assert_eq!(fill_buckets.len(), 2, "Expected one LSU bucket and one claim NFT bucket");
+    assert!(ledger.ledger.get_component_balance(user_account2, lsu_resource_address) > dec!(0));
+    assert_eq!(claim_nft_ids(&mut ledger, user_account2).len(), 2);
+}
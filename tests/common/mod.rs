@@ -0,0 +1,48 @@
+use scrypto_test::prelude::*;
+use liquify_scrypto::AccountingAudit;
+
+// Number of buy list entries audited per transaction
+const AUDIT_PAGE_SIZE: u64 = 100;
+
+/// Pages through `audit_accounting` and panics if the vault, `total_xrd_locked`, the liquidity
+/// indexes and the liquidity recorded per receipt don't all agree.
+pub fn assert_accounting_invariants(
+    ledger: &mut LedgerSimulator<NoExtension, InMemorySubstateDatabase>,
+    liquify_component: ComponentAddress,
+) {
+    let mut start_index = 0u64;
+    let mut xrd_liquidity_available_sum = dec!(0);
+    let mut auto_unstake_true_sum = dec!(0);
+    let mut auto_unstake_false_sum = dec!(0);
+
+    loop {
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_method(
+                liquify_component,
+                "audit_accounting",
+                manifest_args!(start_index, AUDIT_PAGE_SIZE),
+            )
+            .build();
+
+        let receipt = ledger.execute_manifest(manifest, vec![]);
+        let audit: AccountingAudit = receipt.expect_commit_success().output(1);
+
+        assert!(audit.discrepancies.is_empty(), "Accounting discrepancies: {:?}", audit.discrepancies);
+
+        xrd_liquidity_available_sum += audit.xrd_liquidity_available_sum;
+        auto_unstake_true_sum += audit.auto_unstake_true_sum;
+        auto_unstake_false_sum += audit.auto_unstake_false_sum;
+
+        match audit.next_index {
+            Some(next_index) => start_index = next_index,
+            None => {
+                assert_eq!(xrd_liquidity_available_sum, audit.total_xrd_locked, "Receipt liquidity doesn't add up to total_xrd_locked");
+                assert_eq!(xrd_liquidity_available_sum, audit.xrd_liquidity_amount, "Receipt liquidity doesn't add up to the XRD liquidity vault");
+                assert_eq!(auto_unstake_true_sum, audit.liquidity_index_auto_unstake_true_total, "Auto unstake liquidity index is out of sync");
+                assert_eq!(auto_unstake_false_sum, audit.liquidity_index_auto_unstake_false_total, "Liquidity index is out of sync");
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,451 @@
+use scrypto_test::prelude::*;
+
+mod common;
+
+#[derive(ScryptoSbor, Debug, Clone)]
+pub struct SubPosition {
+    pub buy_list_key: u128,
+    pub discount: Decimal,
+    pub xrd_liquidity_available: Decimal,
+}
+
+#[derive(ScryptoSbor, Debug, Clone)]
+pub struct ReceiptDetailData {
+    pub receipt_id: NonFungibleLocalId,
+    pub discount: Decimal,
+    pub auto_unstake: bool,
+    pub auto_refill: bool,
+    pub refill_threshold: Decimal,
+    pub xrd_liquidity_available: Decimal,
+    pub xrd_liquidity_filled: Decimal,
+    pub liquidity_ahead: Decimal,
+    pub fills_to_collect: u64,
+    pub last_added_epoch: u32,
+    pub claimable_xrd: Decimal,
+    pub total_stake_claim_value: Decimal,
+    pub total_lsu_redemption_value: Decimal,
+    pub automation_fee: Decimal,
+    pub discount_overrides: Vec<(ComponentAddress, Decimal)>,
+    pub annualized_rate: Option<Decimal>,
+    pub effective_discount: Decimal,
+    pub lsu_fills_unbonding: u64,
+    pub redemption_ready_epoch: u64,
+    pub mid_cycle: bool,
+    pub claimable_xrd_balance: Decimal,
+    pub sub_positions: Vec<SubPosition>,
+    pub ladder_weights: Vec<(Decimal, Decimal)>,
+}
+
+#[derive(Clone)]
+pub struct Account {
+    public_key: Secp256k1PublicKey,
+    account_address: ComponentAddress,
+}
+
+pub struct TestEnvironment {
+    pub ledger: LedgerSimulator<NoExtension, InMemorySubstateDatabase>,
+    pub admin_account: Account,
+    pub user_account1: Account,
+    pub user_account2: Account,
+    pub package_address: PackageAddress,
+    pub liquify_component: ComponentAddress,
+    pub owner_badge: ResourceAddress,
+    pub liquidity_receipt: ResourceAddress,
+    pub lsu_resource_address: ResourceAddress,
+    pub claim_nft_address: ResourceAddress,
+    pub validator_address: ComponentAddress,
+}
+
+impl TestEnvironment {
+    pub fn instantiate_test() -> Self {
+        let mut ledger = LedgerSimulatorBuilder::new()
+            .without_kernel_trace()
+            .build();
+
+        // Create accounts
+        let (admin_public_key, _admin_private_key, admin_account_address) = ledger.new_allocated_account();
+        let admin_account = Account { public_key: admin_public_key, account_address: admin_account_address };
+
+        let (user_public_key1, _user_private_key1, user_account_address1) = ledger.new_allocated_account();
+        let user_account1 = Account { public_key: user_public_key1, account_address: user_account_address1 };
+
+        let (user_public_key2, _user_private_key2, user_account_address2) = ledger.new_allocated_account();
+        let  user_account2 = Account { public_key: user_public_key2, account_address: user_account_address2 };
+
+        let package_address = ledger.compile_and_publish(this_package!());
+
+        // Instantiate Liquify component
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_function(
+                package_address,
+                "Liquify",
+                "instantiate_liquify",
+                manifest_args!(),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+
+        let liquify_component = receipt.expect_commit(true).new_component_addresses()[0];
+        let owner_badge = receipt.expect_commit(true).new_resource_addresses()[0];
+        let liquidity_receipt = receipt.expect_commit(true).new_resource_addresses()[1];
+
+        // Enable the component
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address,
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component,
+                "set_component_status",
+                manifest_args!(true),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+
+        // Setup LSUs
+        let key = Secp256k1PrivateKey::from_u64(1u64).unwrap().public_key();
+        let validator_address = ledger.get_active_validator_with_key(&key);
+        let validator_info = ledger.get_active_validator_info_by_key(&key);
+        let lsu_resource_address = validator_info.stake_unit_resource;
+        let claim_nft_address = validator_info.claim_nft;
+
+        // Give user1 LSUs for unstaking
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(user_account_address1, XRD, dec!(5000))
+            .take_all_from_worktop(XRD, "xrd")
+            .call_method_with_name_lookup(validator_address, "stake", |lookup| {
+                (lookup.bucket("xrd"),)
+            })
+            .call_method(
+                user_account_address1,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&user_public_key1)],
+        );
+        receipt.expect_commit_success();
+
+        // Set small order threshold to 1 XRD so small unstakes reach auto_unstake positions
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address,
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component,
+                "set_small_order_threshold",
+                manifest_args!(dec!("1")),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+
+        // Set minimum liquidity to 100 for easier testing
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address,
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component,
+                "set_minimum_liquidity",
+                manifest_args!(dec!("100")),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+
+        Self {
+            ledger,
+            admin_account,
+            user_account1,
+            user_account2,
+            package_address,
+            liquify_component,
+            owner_badge,
+            liquidity_receipt,
+            lsu_resource_address,
+            claim_nft_address,
+            validator_address,
+        }
+    }
+
+    pub fn execute_manifest(
+        &mut self,
+        manifest: TransactionManifestV1,
+        account: Account,
+    ) -> TransactionReceipt {
+        let receipt = self.ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&account.public_key)]
+        );
+
+        if receipt.is_commit_success() {
+            common::assert_accounting_invariants(&mut self.ledger, self.liquify_component);
+        }
+
+        receipt
+    }
+}
+
+fn add_liquidity(ledger: &mut TestEnvironment, xrd_amount: Decimal) {
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account2, XRD, xrd_amount)
+        .take_all_from_worktop(XRD, "xrd")
+        .call_method_with_name_lookup(liquify_component, "add_liquidity", |lookup| {(
+            lookup.bucket("xrd"),
+            dec!("0.01"),      // 1% discount
+            false,             // auto_unstake
+            false,             // auto_refill
+            dec!("0"),         // refill_threshold
+            dec!("5"),         // automation_fee
+        )})
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+}
+
+fn set_delivery_account(ledger: &mut TestEnvironment, receipt_id: u64) {
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+    let liquidity_receipt = ledger.liquidity_receipt;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(user_account2, liquidity_receipt, vec![NonFungibleLocalId::integer(receipt_id)])
+        .take_all_from_worktop(liquidity_receipt, "receipt")
+        .call_method_with_name_lookup(liquify_component, "set_delivery_account", |lookup| {
+            (lookup.bucket("receipt"), Some(user_account2))
+        })
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+}
+
+fn deposit_claimable(ledger: &mut TestEnvironment, receipt_id: u64, xrd_amount: Decimal) {
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+    let liquidity_receipt = ledger.liquidity_receipt;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_non_fungibles(user_account2, liquidity_receipt, vec![NonFungibleLocalId::integer(receipt_id)])
+        .pop_from_auth_zone("receipt_proof")
+        .withdraw_from_account(user_account2, XRD, xrd_amount)
+        .take_all_from_worktop(XRD, "xrd")
+        .call_method_with_name_lookup(liquify_component, "deposit_claimable", |lookup| {
+            (lookup.proof("receipt_proof"), lookup.bucket("xrd"))
+        })
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+}
+
+fn unstake(ledger: &mut TestEnvironment, lsu_amount: Decimal) {
+    let user_account1 = ledger.user_account1.account_address;
+    let liquify_component = ledger.liquify_component;
+    let lsu_resource_address = ledger.lsu_resource_address;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account1, lsu_resource_address, lsu_amount)
+        .take_all_from_worktop(lsu_resource_address, "lsu")
+        .call_method_with_name_lookup(liquify_component, "liquify_unstake", |lookup| {
+            (lookup.bucket("lsu"), 10u8)
+        })
+        .call_method(
+            user_account1,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success();
+}
+
+// Delivers fills as user1 acting as the keeper and returns the fee earned
+fn deliver_fills(ledger: &mut TestEnvironment, receipt_id: u64) -> Decimal {
+    let user_account1 = ledger.user_account1.account_address;
+    let liquify_component = ledger.liquify_component;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(liquify_component, "deliver_fills", manifest_args!(vec![NonFungibleLocalId::integer(receipt_id)], 10u64))
+        .call_method(
+            user_account1,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let xrd_before = ledger.ledger.get_component_balance(user_account1, XRD);
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success();
+    ledger.ledger.get_component_balance(user_account1, XRD) - xrd_before
+}
+
+fn get_receipt_detail(ledger: &mut TestEnvironment, receipt_id: u64) -> ReceiptDetailData {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(ledger.liquify_component, "get_receipt_detail", manifest_args!(NonFungibleLocalId::integer(receipt_id)))
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success().output(1)
+}
+
+#[test]
+fn test_deliver_fills_of_fully_filled_position() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account2 = ledger.user_account2.account_address;
+    let lsu_resource_address = ledger.lsu_resource_address;
+
+    add_liquidity(&mut ledger, dec!(100));
+    set_delivery_account(&mut ledger, 1);
+    deposit_claimable(&mut ledger, 1, dec!(5));
+
+    // The position is used up, nothing is left in its bid
+    unstake(&mut ledger, dec!(150));
+    assert_eq!(get_receipt_detail(&mut ledger, 1).xrd_liquidity_available, dec!(0));
+
+    let keeper_fee = deliver_fills(&mut ledger, 1);
+
+    assert_eq!(keeper_fee, dec!(1));
+    assert!(ledger.ledger.get_component_balance(user_account2, lsu_resource_address) > dec!(0));
+
+    let detail = get_receipt_detail(&mut ledger, 1);
+    assert_eq!(detail.fills_to_collect, 0);
+    assert_eq!(detail.claimable_xrd_balance, dec!(4));
+}
+
+#[test]
+fn test_deliver_fills_fee_leaves_bid_untouched() {
+    let mut ledger = TestEnvironment::instantiate_test();
+
+    add_liquidity(&mut ledger, dec!(1000));
+    set_delivery_account(&mut ledger, 1);
+    deposit_claimable(&mut ledger, 1, dec!("1.5"));
+
+    unstake(&mut ledger, dec!(100));
+    unstake(&mut ledger, dec!(100));
+    let available_before = get_receipt_detail(&mut ledger, 1).xrd_liquidity_available;
+
+    // The balance covers one fee, so only one of the two fills is delivered
+    let keeper_fee = deliver_fills(&mut ledger, 1);
+    assert_eq!(keeper_fee, dec!(1));
+
+    let detail = get_receipt_detail(&mut ledger, 1);
+    assert_eq!(detail.fills_to_collect, 1);
+    assert_eq!(detail.claimable_xrd_balance, dec!("0.5"));
+    assert_eq!(detail.xrd_liquidity_available, available_before);
+}
+
+#[test]
+fn test_deliver_fills_skips_unfunded_receipt() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account2 = ledger.user_account2.account_address;
+    let lsu_resource_address = ledger.lsu_resource_address;
+
+    add_liquidity(&mut ledger, dec!(1000));
+    set_delivery_account(&mut ledger, 1);
+    unstake(&mut ledger, dec!(100));
+
+    let keeper_fee = deliver_fills(&mut ledger, 1);
+
+    assert_eq!(keeper_fee, dec!(0));
+    assert_eq!(ledger.ledger.get_component_balance(user_account2, lsu_resource_address), dec!(0));
+    assert_eq!(get_receipt_detail(&mut ledger, 1).fills_to_collect, 1);
+}
+
+#[test]
+fn test_deliver_fills_refused_deposit_is_kept() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account2 = ledger.user_account2.account_address;
+    let lsu_resource_address = ledger.lsu_resource_address;
+
+    add_liquidity(&mut ledger, dec!(1000));
+    set_delivery_account(&mut ledger, 1);
+    deposit_claimable(&mut ledger, 1, dec!(5));
+    unstake(&mut ledger, dec!(100));
+
+    // The provider's account stops accepting deposits
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(user_account2, "set_default_deposit_rule", manifest_args!(DefaultDepositRule::Reject))
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+
+    let keeper_fee = deliver_fills(&mut ledger, 1);
+
+    // No fee is charged for a refused fill and the fill stays collectable
+    assert_eq!(keeper_fee, dec!(0));
+    assert_eq!(ledger.ledger.get_component_balance(user_account2, lsu_resource_address), dec!(0));
+
+    let detail = get_receipt_detail(&mut ledger, 1);
+    assert_eq!(detail.fills_to_collect, 1);
+    assert_eq!(detail.claimable_xrd_balance, dec!(5));
+}
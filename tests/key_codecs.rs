@@ -0,0 +1,83 @@
+use liquify_scrypto::client::buy_list_key::{self, BuyListKeyParts};
+use liquify_scrypto::client::order_fill_key::{self, OrderFillKeyParts};
+
+#[test]
+fn test_buy_list_key_round_trip() {
+    let cases = [
+        (0u16, false, 0u64, 0u64),
+        (10, true, 1, 1),
+        (500, false, 123_456, 4_294_967_295),
+        (500, true, u64::MAX, 4_294_967_296),
+        (u16::MAX, true, u64::MAX, buy_list_key::MAX_RECEIPT_ID),
+    ];
+
+    for (discount_basis_points, auto_unstake, position, receipt_id) in cases {
+        let key = buy_list_key::encode(discount_basis_points, auto_unstake, position, receipt_id);
+
+        assert_eq!(
+            buy_list_key::decode(key),
+            BuyListKeyParts {
+                discount_basis_points,
+                position,
+                auto_unstake,
+                receipt_id,
+                legacy_layout: false,
+            }
+        );
+    }
+}
+
+#[test]
+fn test_buy_list_key_decodes_legacy_layout() {
+    // Legacy layout: discount (16 bits) | position (64 bits) | auto_unstake (16 bits) | receipt_id (32 bits)
+    let legacy_key = (25u128 << 112) | (42u128 << 48) | (1u128 << 32) | 7u128;
+
+    assert_eq!(
+        buy_list_key::decode(legacy_key),
+        BuyListKeyParts {
+            discount_basis_points: 25,
+            position: 42,
+            auto_unstake: true,
+            receipt_id: 7,
+            legacy_layout: true,
+        }
+    );
+}
+
+#[test]
+fn test_buy_list_key_keeps_discount_and_position_order() {
+    let better_discount = buy_list_key::encode(10, false, 100, buy_list_key::MAX_RECEIPT_ID);
+    let worse_discount = buy_list_key::encode(20, true, 1, 1);
+    let older_position = buy_list_key::encode(20, true, 1, buy_list_key::MAX_RECEIPT_ID);
+    let newer_position = buy_list_key::encode(20, false, 2, 1);
+    let legacy_key = (20u128 << 112) | (1u128 << 48) | 2u128;
+
+    assert!(better_discount < worse_discount);
+    assert!(older_position < newer_position);
+    assert!(legacy_key < newer_position);
+}
+
+#[test]
+#[should_panic(expected = "Receipt ID does not fit in a buy list key")]
+fn test_buy_list_key_rejects_oversized_receipt_id() {
+    buy_list_key::encode(10, false, 1, buy_list_key::MAX_RECEIPT_ID + 1);
+}
+
+#[test]
+fn test_order_fill_key_round_trip() {
+    let cases = [(0u64, 0u64), (1, 1), (42, 7), (u64::MAX, u64::MAX)];
+
+    for (receipt_id, fill_number) in cases {
+        let key = order_fill_key::encode(receipt_id, fill_number);
+
+        assert_eq!(order_fill_key::decode(key), OrderFillKeyParts { receipt_id, fill_number });
+    }
+}
+
+#[test]
+fn test_order_fill_keys_group_by_receipt() {
+    let last_fill_of_first_receipt = order_fill_key::encode(1, u64::MAX);
+    let first_fill_of_second_receipt = order_fill_key::encode(2, 1);
+
+    assert!(last_fill_of_first_receipt < first_fill_of_second_receipt);
+}
@@ -1,5 +1,7 @@
 use scrypto_test::prelude::*;
 
+mod common;
+
 #[derive(Clone)]
 pub struct Account {
     public_key: Secp256k1PublicKey,
@@ -303,10 +305,16 @@ impl TestEnvironment {
         manifest: TransactionManifestV1, 
         account: Account,
     ) -> TransactionReceipt {
-        self.ledger.execute_manifest(
+        let receipt = self.ledger.execute_manifest(
             manifest, 
             vec![NonFungibleGlobalId::from_public_key(&account.public_key)]
-        )
+        );
+
+        if receipt.is_commit_success() {
+            common::assert_accounting_invariants(&mut self.ledger, self.liquify_component);
+        }
+
+        receipt
     }
 }
 
@@ -0,0 +1,383 @@
+use scrypto_test::prelude::*;
+use liquify_scrypto::client::matching_policy::MatchingPolicy;
+use liquify_scrypto::ReceiptDetailData;
+
+mod common;
+
+// Mirrors the blueprint's `OrderKeyPolicy` so it can be passed in manifests
+#[derive(ManifestSbor, Clone, Copy)]
+pub enum OrderKeyPolicy {
+    Unrestricted,
+    Strict(u64),
+    Fallback(u64),
+}
+
+#[derive(Clone)]
+pub struct Account {
+    public_key: Secp256k1PublicKey,
+    account_address: ComponentAddress,
+} 
+
+pub struct TestEnvironment {
+    pub ledger: LedgerSimulator<NoExtension, InMemorySubstateDatabase>,
+    pub admin_account: Account,
+    pub user_account1: Account,
+    pub user_account2: Account,
+    pub package_address: PackageAddress,
+    pub liquify_component: ComponentAddress,
+    pub owner_badge: ResourceAddress,
+    pub liquidity_receipt: ResourceAddress,
+    pub lsu_resource_address: ResourceAddress,
+}
+
+impl TestEnvironment {
+    pub fn instantiate_test() -> Self {
+        let mut ledger = LedgerSimulatorBuilder::new().without_kernel_trace().build();
+
+        // Create accounts
+        let (admin_public_key, _admin_private_key, admin_account_address) = ledger.new_allocated_account();
+        let admin_account = Account { public_key: admin_public_key, account_address: admin_account_address };
+
+        let (user_public_key1, _user_private_key1, user_account_address1) = ledger.new_allocated_account();
+        let user_account1 = Account { public_key: user_public_key1, account_address: user_account_address1 };
+
+        let (user_public_key2, _user_private_key2, user_account_address2) = ledger.new_allocated_account();
+        let  user_account2 = Account { public_key: user_public_key2, account_address: user_account_address2 };
+
+        let package_address = ledger.compile_and_publish(this_package!());
+
+        // *********** Instantiate Liquify component ***********
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_function(
+                package_address,
+                "Liquify",
+                "instantiate_liquify",
+                manifest_args!(),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        println!("{:?}\n", receipt);
+
+        let liquify_component = receipt.expect_commit(true).new_component_addresses()[0];
+        let owner_badge = receipt.expect_commit(true).new_resource_addresses()[0];
+        let liquidity_receipt = receipt.expect_commit(true).new_resource_addresses()[1];
+
+        // *********** Enable the component (it starts disabled) ***********
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address, 
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component, 
+                "set_component_status", 
+                manifest_args!(true),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+
+        // *********** Setup LSUs ***********
+        let key = Secp256k1PrivateKey::from_u64(1u64).unwrap().public_key();
+        let validator_address = ledger.get_active_validator_with_key(&key);
+        let lsu_resource_address = ledger
+            .get_active_validator_info_by_key(&key)
+            .stake_unit_resource;
+
+        // Give the seller LSUs
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet() 
+            .withdraw_from_account(user_account_address1, XRD, dec!(1000))
+            .take_all_from_worktop(XRD, "xrd")
+            .call_method_with_name_lookup(validator_address, "stake", |lookup| {
+                (lookup.bucket("xrd"),)
+            })
+            .call_method(
+                user_account_address1,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&user_public_key1)],
+        );
+        receipt.expect_commit_success();
+
+        // *********** Set minimum liquidity to 0 for testing ***********
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address, 
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component, 
+                "set_minimum_liquidity", 
+                manifest_args!(dec!("0")),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+
+        // *********** Set small order threshold to 1 XRD (very low) ***********
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address, 
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component, 
+                "set_small_order_threshold", 
+                manifest_args!(dec!("1")),  // Set to 1 XRD instead of default 1000
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+   
+        Self {
+            ledger,
+            admin_account,
+            user_account1,
+            user_account2,
+            package_address,
+            liquify_component,
+            owner_badge,
+            liquidity_receipt,
+            lsu_resource_address,
+        }
+    }
+
+    pub fn execute_manifest(
+        &mut self,
+        manifest: TransactionManifestV1, 
+        account: Account,
+    ) -> TransactionReceipt {
+        let receipt = self.ledger.execute_manifest(
+            manifest, 
+            vec![NonFungibleGlobalId::from_public_key(&account.public_key)]
+        );
+
+        if receipt.is_commit_success() {
+            common::assert_accounting_invariants(&mut self.ledger, self.liquify_component);
+        }
+
+        receipt
+    }
+}
+
+#[test]
+fn test_order_key_policy() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account1 = ledger.user_account1.account_address;
+    let user_account2 = ledger.user_account2.account_address;
+    let admin_account = ledger.admin_account.account_address;
+    let liquify_component = ledger.liquify_component;
+    let owner_badge = ledger.owner_badge;
+    let lsu_resource_address = ledger.lsu_resource_address;
+
+    // Three positions, best discount first in the book
+    for discount in [dec!("0.0010"), dec!("0.0020"), dec!("0.0030")] {
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(user_account2, XRD, dec!(100))
+            .take_all_from_worktop(XRD, "xrd")
+            .call_method_with_name_lookup(liquify_component, "add_liquidity", |lookup| {(
+                lookup.bucket("xrd"),
+                discount,
+                false,             // auto_unstake
+                false,             // auto_refill
+                dec!("0"),         // refill_threshold
+                dec!("5"),         // automation_fee
+            )})
+            .call_method(
+                user_account2,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+        receipt.expect_commit_success();
+    }
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(liquify_component, "get_raw_buy_list_range", manifest_args!(0u64, 10u64))
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    let buy_list: Vec<(u128, NonFungibleGlobalId)> = receipt.expect_commit_success().output(1);
+    let keys: Vec<u128> = buy_list.iter().map(|(key, _)| *key).collect();
+    assert_eq!(keys.len(), 3);
+
+    let set_policy = |ledger: &mut TestEnvironment, policy: OrderKeyPolicy| {
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(admin_account, owner_badge, 1)
+            .call_method(liquify_component, "set_order_key_policy", manifest_args!(policy))
+            .build();
+        let receipt = ledger.execute_manifest(manifest, ledger.admin_account.clone());
+        receipt.expect_commit_success();
+    };
+
+    let unstake = |ledger: &mut TestEnvironment, order_keys: Vec<u128>| {
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(user_account1, lsu_resource_address, dec!(10))
+            .take_all_from_worktop(lsu_resource_address, "lsu")
+            .call_method_with_name_lookup(liquify_component, "liquify_unstake_off_ledger", |lookup| {
+                (lookup.bucket("lsu"), order_keys.clone())
+            })
+            .call_method(
+                user_account1,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        ledger.execute_manifest(manifest, ledger.user_account1.clone())
+    };
+
+    // Unrestricted accepts keys that jump the queue
+    unstake(&mut ledger, vec![keys[2]]).expect_commit_success();
+    println!("✓ Unrestricted policy accepted a deeper discount key");
+
+    // Strict with no tolerance rejects skipped and out of order keys
+    set_policy(&mut ledger, OrderKeyPolicy::Strict(0));
+    unstake(&mut ledger, vec![keys[1]]).expect_commit_failure();
+    unstake(&mut ledger, vec![keys[1], keys[0]]).expect_commit_failure();
+    unstake(&mut ledger, vec![keys[0], keys[1]]).expect_commit_success();
+    println!("✓ Strict policy only accepted keys in book order");
+
+    // A tolerance allows a skipped entry
+    set_policy(&mut ledger, OrderKeyPolicy::Strict(1));
+    unstake(&mut ledger, vec![keys[1]]).expect_commit_success();
+    println!("✓ Strict policy with tolerance accepted one skipped key");
+
+    // Fallback matches in book order instead of reverting
+    set_policy(&mut ledger, OrderKeyPolicy::Fallback(0));
+    let before_xrd = ledger.ledger.get_component_balance(user_account1, XRD);
+    unstake(&mut ledger, vec![keys[2]]).expect_commit_success();
+    let fallback_xrd = ledger.ledger.get_component_balance(user_account1, XRD) - before_xrd;
+
+    set_policy(&mut ledger, OrderKeyPolicy::Unrestricted);
+    let before_xrd = ledger.ledger.get_component_balance(user_account1, XRD);
+    unstake(&mut ledger, vec![keys[2]]).expect_commit_success();
+    let deeper_xrd = ledger.ledger.get_component_balance(user_account1, XRD) - before_xrd;
+
+    assert!(fallback_xrd > deeper_xrd, "Fallback should have matched the better priced position");
+    println!("✓ Fallback policy matched in book order");
+}
+
+#[test]
+fn test_pro_rata_tier_matching() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account1 = ledger.user_account1.account_address;
+    let user_account2 = ledger.user_account2.account_address;
+    let admin_account = ledger.admin_account.account_address;
+    let liquify_component = ledger.liquify_component;
+    let owner_badge = ledger.owner_badge;
+    let lsu_resource_address = ledger.lsu_resource_address;
+
+    // An older small position and a newer large one in the same tier
+    for xrd_amount in [dec!(100), dec!(300)] {
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(user_account2, XRD, xrd_amount)
+            .take_all_from_worktop(XRD, "xrd")
+            .call_method_with_name_lookup(liquify_component, "add_liquidity", |lookup| {(
+                lookup.bucket("xrd"),
+                dec!("0.0010"),    // 0.1% discount
+                false,             // auto_unstake
+                false,             // auto_refill
+                dec!("0"),         // refill_threshold
+                dec!("5"),         // automation_fee
+            )})
+            .call_method(
+                user_account2,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+        receipt.expect_commit_success();
+    }
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_amount(admin_account, owner_badge, 1)
+        .call_method(liquify_component, "set_tier_matching_policy", manifest_args!(dec!("0.0010"), MatchingPolicy::ProRata))
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.admin_account.clone());
+    receipt.expect_commit_success();
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account1, lsu_resource_address, dec!(40))
+        .take_all_from_worktop(lsu_resource_address, "lsu")
+        .call_method_with_name_lookup(liquify_component, "liquify_unstake", |lookup| {
+            (lookup.bucket("lsu"), 10u8)
+        })
+        .call_method(
+            user_account1,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success();
+
+    let mut filled = Vec::new();
+    for id in [1u64, 2u64] {
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_method(liquify_component, "get_receipt_detail", manifest_args!(NonFungibleLocalId::integer(id)))
+            .build();
+        let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+        let detail: ReceiptDetailData = receipt.expect_commit_success().output(1);
+        filled.push(detail.xrd_liquidity_filled);
+    }
+    println!("Older position filled {} XRD, newer position filled {} XRD", filled[0], filled[1]);
+
+    // Under FIFO the older position would have taken the whole unstake
+    assert!(filled[0] > dec!(0) && filled[1] > dec!(0), "Both positions should share the unstake");
+    let difference = filled[1] - filled[0] * dec!(3);
+    assert!(difference.checked_abs().unwrap() < dec!("0.000001"), "Fills should be proportional to available liquidity");
+}
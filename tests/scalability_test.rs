@@ -1,6 +1,18 @@
 use scrypto_test::prelude::*;
+
+mod common;
 use std::time::Instant;
 
+#[derive(ScryptoSbor, Debug, Clone)]
+pub struct AutomationReadyReceipt {
+    pub receipt_id: NonFungibleLocalId,
+    pub discount: Decimal,
+    pub fills_to_collect: u64,
+    pub claimable_xrd: Decimal,
+    pub refill_threshold: Decimal,
+    pub automation_fee: Decimal,
+}
+
 #[derive(Clone)]
 pub struct Account {
     public_key: Secp256k1PublicKey,
@@ -189,10 +201,16 @@ impl TestEnvironment {
         manifest: TransactionManifestV1, 
         account: Account,
     ) -> TransactionReceipt {
-        self.ledger.execute_manifest(
+        let receipt = self.ledger.execute_manifest(
             manifest, 
             vec![NonFungibleGlobalId::from_public_key(&account.public_key)]
-        )
+        );
+
+        if receipt.is_commit_success() {
+            common::assert_accounting_invariants(&mut self.ledger, self.liquify_component);
+        }
+
+        receipt
     }
 }
 
@@ -379,4 +397,171 @@ fn test_unstake_scaling() {
     println!("\nLook for 'Transaction Cost:' in the output above");
     println!("\nTo test scaling, run with different TREE_SIZE values:");
     println!("0, 10, 50, 100, 500, 1000");
-}
\ No newline at end of file
+}
+// Lists the registry in slot order, every position in it is ready when its refill threshold is zero
+fn get_registered_receipts(ledger: &mut TestEnvironment) -> Vec<u64> {
+    let mut registered = Vec::new();
+    let mut start_index = 1u64;
+
+    loop {
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_method(ledger.liquify_component, "get_automation_ready_receipts", manifest_args!(start_index, 100u64))
+            .build();
+        let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+        let page: Vec<AutomationReadyReceipt> = receipt.expect_commit_success().output(1);
+        if page.is_empty() {
+            break;
+        }
+
+        start_index += page.len() as u64;
+        registered.extend(page.into_iter().map(|ready| match ready.receipt_id {
+            NonFungibleLocalId::Integer(id) => id.value(),
+            _ => panic!("Liquidity receipts have integer IDs"),
+        }));
+    }
+
+    registered
+}
+
+fn update_auto_refill_status(ledger: &mut TestEnvironment, receipt_number: u64, auto_refill: bool) -> TransactionReceipt {
+    let user_account4 = ledger.user_account4.account_address;
+    let liquify_component = ledger.liquify_component;
+    let liquidity_receipt = ledger.liquidity_receipt;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(
+            user_account4,
+            liquidity_receipt,
+            vec![NonFungibleLocalId::integer(receipt_number)],
+        )
+        .take_all_from_worktop(liquidity_receipt, "receipt")
+        .call_method_with_name_lookup(liquify_component, "update_auto_refill_status", |lookup| {
+            (lookup.bucket("receipt"), auto_refill)
+        })
+        .call_method(
+            user_account4,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account4.clone());
+    receipt.expect_commit_success();
+    receipt
+}
+
+#[test]
+fn test_automation_registry_scaling() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account4 = ledger.user_account4.account_address;
+    let admin_account = ledger.admin_account.account_address;
+    let liquify_component = ledger.liquify_component;
+    let liquidity_receipt = ledger.liquidity_receipt;
+
+    // PARAMETER - Change this value to test different registry sizes
+    const AUTOMATED_POSITIONS: u64 = 2000;
+    const POSITIONS_PER_TRANSACTION: u64 = 50;
+
+    // A zero refill threshold makes every registered position show up in get_automation_ready_receipts
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_amount(admin_account, ledger.owner_badge, 1)
+        .call_method(liquify_component, "set_minimum_refill_threshold", manifest_args!(dec!("0")))
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.admin_account.clone());
+    receipt.expect_commit_success();
+
+    println!("\n=== AUTOMATION REGISTRY SCALING TEST ===");
+    println!("Registering {} automated positions...", AUTOMATED_POSITIONS);
+
+    for batch in 0..(AUTOMATED_POSITIONS / POSITIONS_PER_TRANSACTION) {
+        ledger.ledger.load_account_from_faucet(user_account4);
+
+        let mut builder = ManifestBuilder::new().lock_fee_from_faucet();
+        for i in 0..POSITIONS_PER_TRANSACTION {
+            let bucket_name = format!("xrd_{}_{}", batch, i);
+            builder = builder
+                .withdraw_from_account(user_account4, XRD, dec!(1))
+                .take_all_from_worktop(XRD, bucket_name.clone())
+                .call_method_with_name_lookup(liquify_component, "add_liquidity", |lookup| {(
+                    lookup.bucket(bucket_name.clone()),
+                    dec!("0.01"),       // 1% discount
+                    true,               // auto_unstake
+                    true,               // auto_refill
+                    dec!("0"),          // refill_threshold
+                    dec!("5"),          // automation_fee
+                )});
+        }
+        let manifest = builder
+            .call_method(
+                user_account4,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+
+        let receipt = ledger.execute_manifest(manifest, ledger.user_account4.clone());
+        receipt.expect_commit_success();
+    }
+
+    println!("✓ Registered {} automated positions\n", AUTOMATED_POSITIONS);
+    assert_eq!(get_registered_receipts(&mut ledger), (1..=AUTOMATED_POSITIONS).collect::<Vec<u64>>());
+
+    // Deregister positions from the front, the middle and the end of the registry. The first removal swaps
+    // the last receipt into slot 1, so the last one here deregisters a receipt that has been moved.
+    let mut deregistration_costs = Vec::new();
+    for receipt_number in [1, AUTOMATED_POSITIONS / 2, AUTOMATED_POSITIONS] {
+        let receipt = update_auto_refill_status(&mut ledger, receipt_number, false);
+        deregistration_costs.push(receipt.fee_summary.total_execution_cost_units_consumed);
+        println!("✓ Disabled automation on receipt #{}", receipt_number);
+    }
+
+    // Removing liquidity from an automated position deregisters it as well
+    let receipt_number = AUTOMATED_POSITIONS / 4;
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(
+            user_account4,
+            liquidity_receipt,
+            vec![NonFungibleLocalId::integer(receipt_number)],
+        )
+        .take_all_from_worktop(liquidity_receipt, "receipt")
+        .call_method_with_name_lookup(liquify_component, "remove_liquidity", |lookup| {
+            (lookup.bucket("receipt"),)
+        })
+        .call_method(
+            user_account4,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account4.clone());
+    receipt.expect_commit_success();
+    println!("✓ Removed liquidity from receipt #{}", receipt_number);
+
+    // Deregistering costs the same wherever the receipt sits in the registry
+    let first_cost = deregistration_costs[0];
+    let last_cost = deregistration_costs[deregistration_costs.len() - 1];
+    println!("Deregistration cost: first {} / last {} execution cost units", first_cost, last_cost);
+    assert!(
+        first_cost.abs_diff(last_cost) * 10 <= first_cost,
+        "Deregistration cost should not depend on the receipt's slot"
+    );
+
+    // Every receipt that was swapped into a freed slot is still registered, exactly once
+    let deregistered = [1, AUTOMATED_POSITIONS / 4, AUTOMATED_POSITIONS / 2, AUTOMATED_POSITIONS];
+    let expected: Vec<u64> = (1..=AUTOMATED_POSITIONS).filter(|id| !deregistered.contains(id)).collect();
+    let mut registered = get_registered_receipts(&mut ledger);
+    assert_eq!(registered.len(), expected.len());
+    registered.sort();
+    assert_eq!(registered, expected);
+
+    // Re-enabling automation on a deregistered receipt reuses the end of the registry
+    update_auto_refill_status(&mut ledger, 1, true);
+    let registered = get_registered_receipts(&mut ledger);
+    assert_eq!(registered.len(), expected.len() + 1);
+    assert_eq!(registered.last(), Some(&1));
+}
@@ -0,0 +1,814 @@
+use scrypto_test::prelude::*;
+
+mod common;
+
+#[derive(ScryptoSbor, Debug, Clone)]
+pub struct SellOrderData {
+    pub lsu_resource: ResourceAddress,
+    pub max_discount: Decimal,
+    pub lsu_remaining: Decimal,
+    pub lsu_sold: Decimal,
+    pub xrd_proceeds: Decimal,
+    pub sell_list_key: u128,
+    pub seller_receipts: Vec<NonFungibleGlobalId>,
+}
+
+#[derive(ScryptoSbor, Debug, Clone)]
+pub struct EffectiveDiscountTier {
+    pub discount: Decimal,
+    pub effective_discount: Decimal,
+    pub xrd_liquidity: Decimal,
+}
+
+#[derive(Clone)]
+pub struct Account {
+    public_key: Secp256k1PublicKey,
+    account_address: ComponentAddress,
+}
+
+pub struct TestEnvironment {
+    pub ledger: LedgerSimulator<NoExtension, InMemorySubstateDatabase>,
+    pub admin_account: Account,
+    pub user_account1: Account,
+    pub user_account2: Account,
+    pub package_address: PackageAddress,
+    pub liquify_component: ComponentAddress,
+    pub owner_badge: ResourceAddress,
+    pub liquidity_receipt: ResourceAddress,
+    pub sell_order_receipt: ResourceAddress,
+    pub lsu_resource_address: ResourceAddress,
+}
+
+impl TestEnvironment {
+    pub fn instantiate_test() -> Self {
+        let mut ledger = LedgerSimulatorBuilder::new()
+            .without_kernel_trace()
+            .build();
+
+        // Create accounts
+        let (admin_public_key, _admin_private_key, admin_account_address) = ledger.new_allocated_account();
+        let admin_account = Account { public_key: admin_public_key, account_address: admin_account_address };
+
+        let (user_public_key1, _user_private_key1, user_account_address1) = ledger.new_allocated_account();
+        let user_account1 = Account { public_key: user_public_key1, account_address: user_account_address1 };
+
+        let (user_public_key2, _user_private_key2, user_account_address2) = ledger.new_allocated_account();
+        let  user_account2 = Account { public_key: user_public_key2, account_address: user_account_address2 };
+
+        let package_address = ledger.compile_and_publish(this_package!());
+
+        // Instantiate Liquify component
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_function(
+                package_address,
+                "Liquify",
+                "instantiate_liquify",
+                manifest_args!(),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+
+        let liquify_component = receipt.expect_commit(true).new_component_addresses()[0];
+        let owner_badge = receipt.expect_commit(true).new_resource_addresses()[0];
+        let liquidity_receipt = receipt.expect_commit(true).new_resource_addresses()[1];
+        let sell_order_receipt = receipt.expect_commit(true).new_resource_addresses()[2];
+
+        // Enable the component
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address,
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component,
+                "set_component_status",
+                manifest_args!(true),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+
+        // Setup LSUs
+        let key = Secp256k1PrivateKey::from_u64(1u64).unwrap().public_key();
+        let validator_address = ledger.get_active_validator_with_key(&key);
+        let lsu_resource_address = ledger
+            .get_active_validator_info_by_key(&key)
+            .stake_unit_resource;
+
+        // Give user1 LSUs to sell
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(user_account_address1, XRD, dec!(5000))
+            .take_all_from_worktop(XRD, "xrd")
+            .call_method_with_name_lookup(validator_address, "stake", |lookup| {
+                (lookup.bucket("xrd"),)
+            })
+            .call_method(
+                user_account_address1,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&user_public_key1)],
+        );
+        receipt.expect_commit_success();
+
+        // Set minimum liquidity to 100 for easier testing
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address,
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component,
+                "set_minimum_liquidity",
+                manifest_args!(dec!("100")),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+
+        Self {
+            ledger,
+            admin_account,
+            user_account1,
+            user_account2,
+            package_address,
+            liquify_component,
+            owner_badge,
+            liquidity_receipt,
+            sell_order_receipt,
+            lsu_resource_address,
+        }
+    }
+
+    pub fn execute_manifest(
+        &mut self,
+        manifest: TransactionManifestV1,
+        account: Account,
+    ) -> TransactionReceipt {
+        let receipt = self.ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&account.public_key)]
+        );
+
+        if receipt.is_commit_success() {
+            common::assert_accounting_invariants(&mut self.ledger, self.liquify_component);
+        }
+
+        receipt
+    }
+}
+
+// Adds a non automated position from the given account
+fn add_liquidity(ledger: &mut TestEnvironment, account: Account, xrd_amount: Decimal, discount: Decimal) {
+    let liquify_component = ledger.liquify_component;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(account.account_address, XRD, xrd_amount)
+        .take_all_from_worktop(XRD, "xrd")
+        .call_method_with_name_lookup(liquify_component, "add_liquidity", |lookup| {(
+            lookup.bucket("xrd"),
+            discount,
+            false,             // auto_unstake
+            false,             // auto_refill
+            dec!("0"),         // refill_threshold
+            dec!("5"),         // automation_fee
+        )})
+        .call_method(
+            account.account_address,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, account);
+    receipt.expect_commit_success();
+}
+
+// Adds an auto_unstake, auto_refill position from user2
+fn add_automated_liquidity(ledger: &mut TestEnvironment, xrd_amount: Decimal, discount: Decimal) {
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account2, XRD, xrd_amount)
+        .take_all_from_worktop(XRD, "xrd")
+        .call_method_with_name_lookup(liquify_component, "add_liquidity", |lookup| {(
+            lookup.bucket("xrd"),
+            discount,
+            true,              // auto_unstake
+            true,              // auto_refill
+            dec!("100"),       // refill_threshold
+            dec!("5"),         // automation_fee
+        )})
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+}
+
+fn set_minimum_refill_threshold(ledger: &mut TestEnvironment, threshold: Decimal) {
+    let admin_account = ledger.admin_account.account_address;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_amount(admin_account, ledger.owner_badge, 1)
+        .call_method(ledger.liquify_component, "set_minimum_refill_threshold", manifest_args!(threshold))
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.admin_account.clone());
+    receipt.expect_commit_success();
+}
+
+fn increase_liquidity(ledger: &mut TestEnvironment, account: Account, receipt_id: u64, xrd_amount: Decimal) {
+    let liquify_component = ledger.liquify_component;
+    let liquidity_receipt = ledger.liquidity_receipt;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(account.account_address, liquidity_receipt, vec![NonFungibleLocalId::integer(receipt_id)])
+        .take_all_from_worktop(liquidity_receipt, "receipt")
+        .withdraw_from_account(account.account_address, XRD, xrd_amount)
+        .take_all_from_worktop(XRD, "xrd")
+        .call_method_with_name_lookup(liquify_component, "increase_liquidity", |lookup| {(
+            lookup.bucket("receipt"),
+            lookup.bucket("xrd"),
+        )})
+        .call_method(
+            account.account_address,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, account);
+    receipt.expect_commit_success();
+}
+
+// Sells LSUs from user1, optionally presenting all of user1's liquidity receipts as the seller badge
+fn create_sell_order(ledger: &mut TestEnvironment, lsu_amount: Decimal, max_discount: Decimal, with_seller_badge: bool) -> TransactionReceipt {
+    let user_account1 = ledger.user_account1.account_address;
+    let liquify_component = ledger.liquify_component;
+    let lsu_resource_address = ledger.lsu_resource_address;
+    let liquidity_receipt = ledger.liquidity_receipt;
+
+    let mut builder = ManifestBuilder::new().lock_fee_from_faucet();
+    if with_seller_badge {
+        let receipt_count = ledger.ledger.get_component_balance(user_account1, liquidity_receipt);
+        builder = builder
+            .create_proof_from_account_of_amount(user_account1, liquidity_receipt, receipt_count)
+            .pop_from_auth_zone("seller_badge");
+    }
+
+    let manifest = builder
+        .withdraw_from_account(user_account1, lsu_resource_address, lsu_amount)
+        .take_all_from_worktop(lsu_resource_address, "lsu")
+        .call_method_with_name_lookup(liquify_component, "create_sell_order", |lookup| {(
+            lookup.bucket("lsu"),
+            max_discount,
+            10u8,
+            if with_seller_badge { Some(lookup.proof("seller_badge")) } else { None },
+        )})
+        .call_method(
+            user_account1,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    ledger.execute_manifest(manifest, ledger.user_account1.clone())
+}
+
+fn get_sell_order(ledger: &mut TestEnvironment, sell_order_id: u64) -> SellOrderData {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(ledger.liquify_component, "get_sell_order", manifest_args!(NonFungibleLocalId::integer(sell_order_id)))
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success().output(1)
+}
+
+fn get_effective_discount_tiers(ledger: &mut TestEnvironment) -> Vec<EffectiveDiscountTier> {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(ledger.liquify_component, "get_effective_discount_tiers", manifest_args!())
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success().output(1)
+}
+
+fn call_with_sell_order_receipt(ledger: &mut TestEnvironment, method_name: &str) -> TransactionReceipt {
+    let user_account1 = ledger.user_account1.account_address;
+    let liquify_component = ledger.liquify_component;
+    let sell_order_receipt = ledger.sell_order_receipt;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account1, sell_order_receipt, dec!(1))
+        .take_all_from_worktop(sell_order_receipt, "sell_order_receipt")
+        .call_method_with_name_lookup(liquify_component, method_name, |lookup| {
+            (lookup.bucket("sell_order_receipt"),)
+        })
+        .call_method(
+            user_account1,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    ledger.execute_manifest(manifest, ledger.user_account1.clone())
+}
+
+#[test]
+fn test_create_sell_order_rests_without_bids() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account1 = ledger.user_account1.account_address;
+
+    // A bid deeper than the seller's limit is not matched
+    add_liquidity(&mut ledger, ledger.user_account2.clone(), dec!(1000), dec!("0.02"));
+
+    let receipt = create_sell_order(&mut ledger, dec!(100), dec!("0.01"), false);
+    receipt.expect_commit_success();
+
+    assert_eq!(ledger.ledger.get_component_balance(user_account1, ledger.sell_order_receipt), dec!(1));
+
+    let order = get_sell_order(&mut ledger, 1);
+    assert_eq!(order.max_discount, dec!("0.01"));
+    assert_eq!(order.lsu_remaining, dec!(100));
+    assert_eq!(order.lsu_sold, dec!(0));
+    assert_eq!(order.xrd_proceeds, dec!(0));
+}
+
+#[test]
+fn test_create_sell_order_fills_immediately() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account1 = ledger.user_account1.account_address;
+
+    add_liquidity(&mut ledger, ledger.user_account2.clone(), dec!(1000), dec!("0.005"));
+
+    let xrd_before = ledger.ledger.get_component_balance(user_account1, XRD);
+    let receipt = create_sell_order(&mut ledger, dec!(100), dec!("0.01"), false);
+    receipt.expect_commit_success();
+
+    // Filled in full, so no order rests and no receipt is minted
+    assert!(ledger.ledger.get_component_balance(user_account1, XRD) > xrd_before);
+    assert_eq!(ledger.ledger.get_component_balance(user_account1, ledger.sell_order_receipt), dec!(0));
+}
+
+#[test]
+fn test_sell_order_matches_on_add_liquidity() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account1 = ledger.user_account1.account_address;
+
+    create_sell_order(&mut ledger, dec!(100), dec!("0.01"), false).expect_commit_success();
+
+    add_liquidity(&mut ledger, ledger.user_account2.clone(), dec!(1000), dec!("0.005"));
+
+    let order = get_sell_order(&mut ledger, 1);
+    assert_eq!(order.lsu_remaining, dec!(0));
+    assert_eq!(order.lsu_sold, dec!(100));
+    assert!(order.xrd_proceeds > dec!(0));
+
+    // Collecting pays out the proceeds and hands back the receipt
+    let xrd_before = ledger.ledger.get_component_balance(user_account1, XRD);
+    call_with_sell_order_receipt(&mut ledger, "collect_sell_order_proceeds").expect_commit_success();
+    let xrd_collected = ledger.ledger.get_component_balance(user_account1, XRD) - xrd_before;
+
+    assert_eq!(xrd_collected, order.xrd_proceeds);
+    assert_eq!(get_sell_order(&mut ledger, 1).xrd_proceeds, dec!(0));
+    assert_eq!(ledger.ledger.get_component_balance(user_account1, ledger.sell_order_receipt), dec!(1));
+}
+
+#[test]
+fn test_sell_order_partial_fill_then_increase_liquidity() {
+    let mut ledger = TestEnvironment::instantiate_test();
+
+    create_sell_order(&mut ledger, dec!(500), dec!("0.01"), false).expect_commit_success();
+
+    // The first bid only covers part of the order
+    add_liquidity(&mut ledger, ledger.user_account2.clone(), dec!(200), dec!("0.01"));
+
+    let order = get_sell_order(&mut ledger, 1);
+    assert!(order.lsu_sold > dec!(0));
+    assert!(order.lsu_remaining > dec!(0));
+    assert_eq!(order.lsu_sold + order.lsu_remaining, dec!(500));
+    let partial_proceeds = order.xrd_proceeds;
+
+    // Topping up the same receipt matches the rest
+    increase_liquidity(&mut ledger, ledger.user_account2.clone(), 1, dec!(1000));
+
+    let order = get_sell_order(&mut ledger, 1);
+    assert_eq!(order.lsu_remaining, dec!(0));
+    assert_eq!(order.lsu_sold, dec!(500));
+    assert!(order.xrd_proceeds > partial_proceeds);
+}
+
+#[test]
+fn test_cancel_sell_order() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account1 = ledger.user_account1.account_address;
+    let lsu_resource_address = ledger.lsu_resource_address;
+
+    create_sell_order(&mut ledger, dec!(500), dec!("0.01"), false).expect_commit_success();
+    add_liquidity(&mut ledger, ledger.user_account2.clone(), dec!(200), dec!("0.01"));
+
+    let order = get_sell_order(&mut ledger, 1);
+    let xrd_before = ledger.ledger.get_component_balance(user_account1, XRD);
+    let lsu_before = ledger.ledger.get_component_balance(user_account1, lsu_resource_address);
+
+    call_with_sell_order_receipt(&mut ledger, "cancel_sell_order").expect_commit_success();
+
+    assert_eq!(ledger.ledger.get_component_balance(user_account1, XRD) - xrd_before, order.xrd_proceeds);
+    assert_eq!(ledger.ledger.get_component_balance(user_account1, lsu_resource_address) - lsu_before, order.lsu_remaining);
+    assert_eq!(ledger.ledger.get_component_balance(user_account1, ledger.sell_order_receipt), dec!(0));
+
+    // A cancelled order no longer rests in the sell list
+    let lsu_before = ledger.ledger.get_component_balance(user_account1, lsu_resource_address);
+    add_liquidity(&mut ledger, ledger.user_account2.clone(), dec!(1000), dec!("0.005"));
+    assert_eq!(ledger.ledger.get_component_balance(user_account1, lsu_resource_address), lsu_before);
+}
+
+#[test]
+fn test_sell_order_skips_sellers_own_receipt() {
+    let mut ledger = TestEnvironment::instantiate_test();
+
+    // The seller provides liquidity themselves, then rests a sell order holding that receipt
+    add_liquidity(&mut ledger, ledger.user_account1.clone(), dec!(200), dec!("0.01"));
+    create_sell_order(&mut ledger, dec!(100), dec!("0.01"), true).expect_commit_success();
+
+    let order = get_sell_order(&mut ledger, 1);
+    assert_eq!(order.lsu_remaining, dec!(100), "The seller's own bid must not fill the order immediately");
+    assert_eq!(order.seller_receipts.len(), 1);
+
+    // Topping up the seller's receipt doesn't match the resting order either
+    increase_liquidity(&mut ledger, ledger.user_account1.clone(), 1, dec!(200));
+    assert_eq!(get_sell_order(&mut ledger, 1).lsu_remaining, dec!(100));
+
+    // Another provider's bid does
+    add_liquidity(&mut ledger, ledger.user_account2.clone(), dec!(1000), dec!("0.01"));
+    assert_eq!(get_sell_order(&mut ledger, 1).lsu_remaining, dec!(0));
+}
+
+#[test]
+fn test_sell_order_respects_discount_override() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+    let liquidity_receipt = ledger.liquidity_receipt;
+
+    let key = Secp256k1PrivateKey::from_u64(1u64).unwrap().public_key();
+    let validator_address = ledger.ledger.get_active_validator_with_key(&key);
+
+    // Listed at 0.5%, but charging 3% for this validator's LSUs
+    add_liquidity(&mut ledger, ledger.user_account2.clone(), dec!(1000), dec!("0.005"));
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(user_account2, liquidity_receipt, vec![NonFungibleLocalId::integer(1)])
+        .take_all_from_worktop(liquidity_receipt, "receipt")
+        .call_method_with_name_lookup(liquify_component, "set_discount_override", |lookup| {(
+            lookup.bucket("receipt"),
+            validator_address,
+            Some(dec!("0.03")),
+        )})
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+
+    // A seller limited to 1% is not filled at the override
+    create_sell_order(&mut ledger, dec!(100), dec!("0.01"), false).expect_commit_success();
+    assert_eq!(get_sell_order(&mut ledger, 1).lsu_remaining, dec!(100));
+
+    // Nor when the position is topped up and matched against the resting order
+    increase_liquidity(&mut ledger, ledger.user_account2.clone(), 1, dec!(100));
+    assert_eq!(get_sell_order(&mut ledger, 1).lsu_remaining, dec!(100));
+
+    // A position without an override within the limit still matches
+    add_liquidity(&mut ledger, ledger.user_account2.clone(), dec!(1000), dec!("0.01"));
+    assert_eq!(get_sell_order(&mut ledger, 1).lsu_remaining, dec!(0));
+}
+
+#[test]
+fn test_unstake_fills_at_discount_override() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account1 = ledger.user_account1.account_address;
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+    let liquidity_receipt = ledger.liquidity_receipt;
+    let lsu_resource_address = ledger.lsu_resource_address;
+
+    let key = Secp256k1PrivateKey::from_u64(1u64).unwrap().public_key();
+    let validator_address = ledger.ledger.get_active_validator_with_key(&key);
+
+    // Listed at 0.5%, but charging 3% for this validator's LSUs
+    add_liquidity(&mut ledger, ledger.user_account2.clone(), dec!(1000), dec!("0.005"));
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(user_account2, liquidity_receipt, vec![NonFungibleLocalId::integer(1)])
+        .take_all_from_worktop(liquidity_receipt, "receipt")
+        .call_method_with_name_lookup(liquify_component, "set_discount_override", |lookup| {(
+            lookup.bucket("receipt"),
+            validator_address,
+            Some(dec!("0.03")),
+        )})
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(validator_address, "get_redemption_value", manifest_args!(dec!(100)))
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    let lsu_value: Decimal = receipt.expect_commit_success().output(1);
+
+    // A direct unstake is priced at the override, not the listed discount
+    let xrd_before = ledger.ledger.get_component_balance(user_account1, XRD);
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account1, lsu_resource_address, dec!(100))
+        .take_all_from_worktop(lsu_resource_address, "lsu")
+        .call_method_with_name_lookup(liquify_component, "liquify_unstake", |lookup| {
+            (lookup.bucket("lsu"), 10u8)
+        })
+        .call_method(
+            user_account1,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success();
+
+    let xrd_received = ledger.ledger.get_component_balance(user_account1, XRD) - xrd_before;
+    let expected_xrd = lsu_value * (dec!(1) - dec!("0.03")) * (dec!(1) - dec!("0.0005"));
+    assert!((xrd_received - expected_xrd).checked_abs().unwrap() < dec!("0.000001"), "Expected {} XRD at the override, received {}", expected_xrd, xrd_received);
+}
+
+#[test]
+fn test_sell_order_respects_annualized_discount() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+
+    // 53% a year over 2016 epochs is about 1.016%, listed at the 1% tier
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account2, XRD, dec!(1000))
+        .take_all_from_worktop(XRD, "xrd")
+        .call_method_with_name_lookup(liquify_component, "add_liquidity_annualized", |lookup| {(
+            lookup.bucket("xrd"),
+            dec!("0.53"),      // annualized rate
+            false,             // auto_unstake
+            false,             // auto_refill
+            dec!("0"),         // refill_threshold
+            dec!("5"),         // automation_fee
+        )})
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+
+    // The tier view reports what the position actually charges
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(liquify_component, "get_effective_discount_tiers", manifest_args!())
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    let tiers: Vec<EffectiveDiscountTier> = receipt.expect_commit_success().output(1);
+    let tier = tiers.iter().find(|tier| tier.discount == dec!("0.01")).unwrap();
+    assert!(tier.effective_discount > dec!("0.01") && tier.effective_discount < dec!("0.01025"));
+    assert_eq!(tier.xrd_liquidity, dec!(1000));
+    assert!(tiers.iter().filter(|tier| tier.discount != dec!("0.01")).all(|tier| tier.effective_discount == tier.discount));
+
+    // A seller limited to the listed tier is not filled at the deeper effective discount
+    create_sell_order(&mut ledger, dec!(100), dec!("0.01"), false).expect_commit_success();
+    assert_eq!(get_sell_order(&mut ledger, 1).lsu_remaining, dec!(100));
+
+    // A seller accepting the next tier is
+    let xrd_before = ledger.ledger.get_component_balance(ledger.user_account1.account_address, XRD);
+    create_sell_order(&mut ledger, dec!(100), dec!("0.0125"), false).expect_commit_success();
+    assert!(ledger.ledger.get_component_balance(ledger.user_account1.account_address, XRD) > xrd_before);
+}
+
+#[test]
+fn test_small_sell_order_skips_auto_unstake_position() {
+    let mut ledger = TestEnvironment::instantiate_test();
+
+    // Worth less than the small order threshold, so a direct unstake wouldn't fill an auto_unstake position
+    create_sell_order(&mut ledger, dec!(100), dec!("0.01"), false).expect_commit_success();
+
+    set_minimum_refill_threshold(&mut ledger, dec!(100));
+    add_automated_liquidity(&mut ledger, dec!(1000), dec!("0.005"));
+    assert_eq!(get_sell_order(&mut ledger, 1).lsu_remaining, dec!(100));
+
+    // A position without auto_unstake still matches it
+    add_liquidity(&mut ledger, ledger.user_account2.clone(), dec!(1000), dec!("0.005"));
+    assert_eq!(get_sell_order(&mut ledger, 1).lsu_remaining, dec!(0));
+}
+
+#[test]
+fn test_sell_order_matches_cycled_liquidity() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account1 = ledger.user_account1.account_address;
+    let liquify_component = ledger.liquify_component;
+    let lsu_resource_address = ledger.lsu_resource_address;
+
+    set_minimum_refill_threshold(&mut ledger, dec!(100));
+    add_automated_liquidity(&mut ledger, dec!(1000), dec!("0.01"));
+
+    // Use the whole position up with a direct unstake
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account1, lsu_resource_address, dec!(1100))
+        .take_all_from_worktop(lsu_resource_address, "lsu")
+        .call_method_with_name_lookup(liquify_component, "liquify_unstake", |lookup| {
+            (lookup.bucket("lsu"), 10u8)
+        })
+        .call_method(
+            user_account1,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success();
+
+    // With the book empty the next order rests
+    create_sell_order(&mut ledger, dec!(1000), dec!("0.01"), false).expect_commit_success();
+    assert_eq!(get_sell_order(&mut ledger, 1).lsu_remaining, dec!(1000));
+
+    // Refilling the position matches it like adding liquidity would
+    ledger.ledger.advance_to_round(Round::of(300));
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(liquify_component, "cycle_liquidity", manifest_args!(vec![NonFungibleLocalId::integer(1)]))
+        .call_method(
+            user_account1,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success();
+
+    let order = get_sell_order(&mut ledger, 1);
+    assert!(order.lsu_sold > dec!(0), "The resting order should be matched against the refill");
+    assert!(order.xrd_proceeds > dec!(0));
+}
+
+#[test]
+fn test_get_effective_discount_tiers() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account2 = ledger.user_account2.account_address;
+    let admin_account = ledger.admin_account.account_address;
+    let liquify_component = ledger.liquify_component;
+
+    // Nothing listed, every tier charges its own discount
+    let tiers = get_effective_discount_tiers(&mut ledger);
+    assert!(!tiers.is_empty());
+    assert!(tiers.iter().all(|tier| tier.effective_discount == tier.discount && tier.xrd_liquidity == dec!(0)));
+
+    add_liquidity(&mut ledger, ledger.user_account2.clone(), dec!(1000), dec!("0.01"));
+    add_liquidity(&mut ledger, ledger.user_account2.clone(), dec!(200), dec!("0.02"));
+
+    // Listed at the 1% tier alongside the flat position
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account2, XRD, dec!(500))
+        .take_all_from_worktop(XRD, "xrd")
+        .call_method_with_name_lookup(liquify_component, "add_liquidity_annualized", |lookup| {(
+            lookup.bucket("xrd"),
+            dec!("0.53"),      // annualized rate
+            false,             // auto_unstake
+            false,             // auto_refill
+            dec!("0"),         // refill_threshold
+            dec!("5"),         // automation_fee
+        )})
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+
+    let tiers = get_effective_discount_tiers(&mut ledger);
+    let tier = tiers.iter().find(|tier| tier.discount == dec!("0.01")).unwrap();
+    assert_eq!(tier.xrd_liquidity, dec!(1500));
+    assert_eq!(tier.effective_discount, dec!("0.53") * Decimal::from(2016u64) / Decimal::from(105_120u64));
+    let tier = tiers.iter().find(|tier| tier.discount == dec!("0.02")).unwrap();
+    assert_eq!(tier.xrd_liquidity, dec!(200));
+    assert_eq!(tier.effective_discount, dec!("0.02"));
+
+    // The annualized rate is converted over the owner-set unbonding period
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_amount(admin_account, ledger.owner_badge, 1)
+        .call_method(liquify_component, "set_unbonding_epochs", manifest_args!(2100u64))
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.admin_account.clone());
+    receipt.expect_commit_success();
+
+    let tiers = get_effective_discount_tiers(&mut ledger);
+    let tier = tiers.iter().find(|tier| tier.discount == dec!("0.01")).unwrap();
+    assert_eq!(tier.effective_discount, dec!("0.53") * Decimal::from(2100u64) / Decimal::from(105_120u64));
+}
+
+#[test]
+fn test_sell_order_matches_ladder_after_every_rung_is_queued() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+
+    // Enough to use up the first rung and part of the second
+    create_sell_order(&mut ledger, dec!(200), dec!("0.01"), false).expect_commit_success();
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account2, XRD, dec!(300))
+        .take_all_from_worktop(XRD, "xrd")
+        .call_method_with_name_lookup(liquify_component, "add_liquidity_ladder", |lookup| {(
+            lookup.bucket("xrd"),
+            vec![(dec!("0.005"), dec!(1)), (dec!("0.0075"), dec!(2))],
+            false,             // auto_unstake
+            false,             // auto_refill
+            dec!("0"),         // refill_threshold
+            dec!("5"),         // automation_fee
+        )})
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+
+    assert_eq!(get_sell_order(&mut ledger, 1).lsu_remaining, dec!(0));
+
+    // Only the second rung is left, and it took over as the receipt's position
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(liquify_component, "get_raw_buy_list_range", manifest_args!(0u64, 10u64))
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    let buy_list: Vec<(u128, NonFungibleGlobalId)> = receipt.expect_commit_success().output(1);
+    assert_eq!(buy_list.len(), 1);
+    assert_eq!(buy_list[0].0 >> 112, 75);
+}
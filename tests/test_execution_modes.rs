@@ -0,0 +1,406 @@
+use scrypto_test::prelude::*;
+
+mod common;
+
+// Mirrors the blueprint's `UnstakeExecutionMode` so it can be passed in manifests
+#[derive(ManifestSbor, Clone, Copy)]
+pub enum UnstakeExecutionMode {
+    PartialAllowed,
+    FillOrKill,
+    MinFillRatio(Decimal),
+}
+
+#[derive(ScryptoSbor, Debug)]
+pub struct OrderFillEvent {
+    pub receipt_id: NonFungibleLocalId,
+    pub lsu_amount: Decimal,
+    pub xrd_amount: Decimal,
+    pub discount: Decimal,
+    pub wash_fill: bool,
+}
+
+#[derive(Clone)]
+pub struct Account {
+    public_key: Secp256k1PublicKey,
+    account_address: ComponentAddress,
+} 
+
+pub struct TestEnvironment {
+    pub ledger: LedgerSimulator<NoExtension, InMemorySubstateDatabase>,
+    pub admin_account: Account,
+    pub user_account1: Account,
+    pub user_account2: Account,
+    pub package_address: PackageAddress,
+    pub liquify_component: ComponentAddress,
+    pub owner_badge: ResourceAddress,
+    pub liquidity_receipt: ResourceAddress,
+    pub lsu_resource_address: ResourceAddress,
+}
+
+impl TestEnvironment {
+    pub fn instantiate_test() -> Self {
+        let mut ledger = LedgerSimulatorBuilder::new()
+            .without_kernel_trace()
+            .build();
+
+        // Create accounts
+        let (admin_public_key, _admin_private_key, admin_account_address) = ledger.new_allocated_account();
+        let admin_account = Account { public_key: admin_public_key, account_address: admin_account_address };
+
+        let (user_public_key1, _user_private_key1, user_account_address1) = ledger.new_allocated_account();
+        let user_account1 = Account { public_key: user_public_key1, account_address: user_account_address1 };
+
+        let (user_public_key2, _user_private_key2, user_account_address2) = ledger.new_allocated_account();
+        let  user_account2 = Account { public_key: user_public_key2, account_address: user_account_address2 };
+
+        let package_address = ledger.compile_and_publish(this_package!());
+
+        // Instantiate Liquify component
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_function(
+                package_address,
+                "Liquify",
+                "instantiate_liquify",
+                manifest_args!(),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+
+        let liquify_component = receipt.expect_commit(true).new_component_addresses()[0];
+        let owner_badge = receipt.expect_commit(true).new_resource_addresses()[0];
+        let liquidity_receipt = receipt.expect_commit(true).new_resource_addresses()[1];
+
+        // Enable the component
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address, 
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component, 
+                "set_component_status", 
+                manifest_args!(true),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+
+        // Setup LSUs
+        let key = Secp256k1PrivateKey::from_u64(1u64).unwrap().public_key();
+        let validator_address = ledger.get_active_validator_with_key(&key);
+        let lsu_resource_address = ledger
+            .get_active_validator_info_by_key(&key)
+            .stake_unit_resource;
+
+        // Give user1 LSUs for unstaking
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet() 
+            .withdraw_from_account(user_account_address1, XRD, dec!(5000))
+            .take_all_from_worktop(XRD, "xrd")
+            .call_method_with_name_lookup(validator_address, "stake", |lookup| {
+                (lookup.bucket("xrd"),)
+            })
+            .call_method(
+                user_account_address1,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&user_public_key1)],
+        );
+        receipt.expect_commit_success();
+
+        // Set minimum liquidity to 100 for easier testing
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address, 
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component, 
+                "set_minimum_liquidity", 
+                manifest_args!(dec!("100")),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+
+        // Set minimum refill threshold to 100
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(
+                admin_account_address, 
+                owner_badge,
+                1,
+            )
+            .call_method(
+                liquify_component, 
+                "set_minimum_refill_threshold", 
+                manifest_args!(dec!("100")),
+            )
+            .call_method(
+                admin_account_address,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&admin_public_key)],
+        );
+        receipt.expect_commit_success();
+       
+        Self {
+            ledger,
+            admin_account,
+            user_account1,
+            user_account2,
+            package_address,
+            liquify_component,
+            owner_badge,
+            liquidity_receipt,
+            lsu_resource_address,
+        }
+    }
+
+    pub fn execute_manifest(
+        &mut self,
+        manifest: TransactionManifestV1, 
+        account: Account,
+    ) -> TransactionReceipt {
+        let receipt = self.ledger.execute_manifest(
+            manifest, 
+            vec![NonFungibleGlobalId::from_public_key(&account.public_key)]
+        );
+
+        if receipt.is_commit_success() {
+            common::assert_accounting_invariants(&mut self.ledger, self.liquify_component);
+        }
+
+        receipt
+    }
+}
+
+
+fn add_liquidity(ledger: &mut TestEnvironment, xrd_amount: Decimal) {
+    let user_account2 = ledger.user_account2.account_address;
+    let liquify_component = ledger.liquify_component;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account2, XRD, xrd_amount)
+        .take_all_from_worktop(XRD, "xrd")
+        .call_method_with_name_lookup(liquify_component, "add_liquidity", |lookup| {(
+            lookup.bucket("xrd"),
+            dec!("0.01"),      // 1% discount
+            false,             // auto_unstake
+            false,             // auto_refill
+            dec!("0"),         // refill_threshold
+            dec!("5"),         // automation_fee
+        )})
+        .call_method(
+            user_account2,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account2.clone());
+    receipt.expect_commit_success();
+}
+
+fn unstake_with_mode(ledger: &mut TestEnvironment, lsu_amount: Decimal, execution_mode: UnstakeExecutionMode) -> TransactionReceipt {
+    let user_account1 = ledger.user_account1.account_address;
+    let liquify_component = ledger.liquify_component;
+    let lsu_resource_address = ledger.lsu_resource_address;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account1, lsu_resource_address, lsu_amount)
+        .take_all_from_worktop(lsu_resource_address, "lsu")
+        .call_method_with_name_lookup(liquify_component, "liquify_unstake_with_mode", |lookup| {(
+            lookup.bucket("lsu"),
+            10u8,
+            execution_mode,
+            None::<ManifestProof>,
+        )})
+        .call_method(
+            user_account1,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    ledger.execute_manifest(manifest, ledger.user_account1.clone())
+}
+
+// Unstakes from user1 while presenting user1's liquidity receipt as the seller badge
+fn unstake_as_seller(ledger: &mut TestEnvironment, lsu_amount: Decimal, execution_mode: UnstakeExecutionMode) -> TransactionReceipt {
+    let user_account1 = ledger.user_account1.account_address;
+    let liquify_component = ledger.liquify_component;
+    let lsu_resource_address = ledger.lsu_resource_address;
+    let liquidity_receipt = ledger.liquidity_receipt;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_amount(user_account1, liquidity_receipt, 1)
+        .pop_from_auth_zone("seller_badge")
+        .withdraw_from_account(user_account1, lsu_resource_address, lsu_amount)
+        .take_all_from_worktop(lsu_resource_address, "lsu")
+        .call_method_with_name_lookup(liquify_component, "liquify_unstake_with_mode", |lookup| {(
+            lookup.bucket("lsu"),
+            10u8,
+            execution_mode,
+            Some(lookup.proof("seller_badge")),
+        )})
+        .call_method(
+            user_account1,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    ledger.execute_manifest(manifest, ledger.user_account1.clone())
+}
+
+#[test]
+fn test_fill_or_kill_reverts_on_partial_fill() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    add_liquidity(&mut ledger, dec!(1000));
+
+    let lsu_before = ledger.ledger.get_component_balance(ledger.user_account1.account_address, ledger.lsu_resource_address);
+
+    // 2000 LSU can't be covered by 1000 XRD of liquidity
+    let receipt = unstake_with_mode(&mut ledger, dec!(2000), UnstakeExecutionMode::FillOrKill);
+    receipt.expect_commit_failure();
+
+    let lsu_after = ledger.ledger.get_component_balance(ledger.user_account1.account_address, ledger.lsu_resource_address);
+    assert_eq!(lsu_before, lsu_after, "Failed fill-or-kill must not move any LSU");
+}
+
+#[test]
+fn test_fill_or_kill_succeeds_on_full_fill() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    add_liquidity(&mut ledger, dec!(1000));
+
+    let receipt = unstake_with_mode(&mut ledger, dec!(500), UnstakeExecutionMode::FillOrKill);
+    receipt.expect_commit_success();
+}
+
+#[test]
+fn test_min_fill_ratio() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    add_liquidity(&mut ledger, dec!(1000));
+
+    // Roughly half of 2000 LSU can be matched
+    let receipt = unstake_with_mode(&mut ledger, dec!(2000), UnstakeExecutionMode::MinFillRatio(dec!("0.9")));
+    receipt.expect_commit_failure();
+
+    let receipt = unstake_with_mode(&mut ledger, dec!(2000), UnstakeExecutionMode::MinFillRatio(dec!("0.25")));
+    receipt.expect_commit_success();
+
+    // Partial fills are still accepted without a mode
+    let receipt = unstake_with_mode(&mut ledger, dec!(100), UnstakeExecutionMode::PartialAllowed);
+    receipt.expect_commit_success();
+}
+
+#[test]
+fn test_self_match_prevention() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account1 = ledger.user_account1.account_address;
+    let admin_account = ledger.admin_account.account_address;
+    let liquify_component = ledger.liquify_component;
+    let owner_badge = ledger.owner_badge;
+
+    // The seller's own position is first in the book
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account1, XRD, dec!(1000))
+        .take_all_from_worktop(XRD, "xrd")
+        .call_method_with_name_lookup(liquify_component, "add_liquidity", |lookup| {(
+            lookup.bucket("xrd"),
+            dec!("0.01"),      // 1% discount
+            false,             // auto_unstake
+            false,             // auto_refill
+            dec!("0"),         // refill_threshold
+            dec!("5"),         // automation_fee
+        )})
+        .call_method(
+            user_account1,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success();
+
+    add_liquidity(&mut ledger, dec!(1000));
+
+    // Only the other provider's liquidity can be matched
+    let receipt = unstake_as_seller(&mut ledger, dec!(500), UnstakeExecutionMode::FillOrKill);
+    receipt.expect_commit_success();
+
+    let receipt = unstake_as_seller(&mut ledger, dec!(900), UnstakeExecutionMode::FillOrKill);
+    receipt.expect_commit_failure();
+
+    // Without the badge the seller's position is matched as usual
+    let receipt = unstake_with_mode(&mut ledger, dec!(900), UnstakeExecutionMode::FillOrKill);
+    receipt.expect_commit_success();
+
+    // With prevention disabled the seller's receipt is matched and flagged instead
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_amount(admin_account, owner_badge, 1)
+        .call_method(liquify_component, "set_self_match_prevention", manifest_args!(false))
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.admin_account.clone());
+    receipt.expect_commit_success();
+
+    let receipt = unstake_as_seller(&mut ledger, dec!(50), UnstakeExecutionMode::FillOrKill);
+    let fill_events: Vec<OrderFillEvent> = receipt
+        .expect_commit_success()
+        .application_events
+        .iter()
+        .filter(|(event_type_identifier, _)| ledger.ledger.event_name(event_type_identifier) == "OrderFillEvent")
+        .map(|(_, event_data)| scrypto_decode(event_data).unwrap())
+        .collect();
+
+    let seller_fills: Vec<&OrderFillEvent> = fill_events
+        .iter()
+        .filter(|event| event.receipt_id == NonFungibleLocalId::integer(1))
+        .collect();
+    assert!(!seller_fills.is_empty(), "The seller's own receipt should be matched");
+    assert!(seller_fills.iter().all(|event| event.wash_fill), "Fills against the seller's receipt should be flagged");
+}
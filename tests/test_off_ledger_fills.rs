@@ -1,4 +1,9 @@
 use scrypto_test::prelude::*;
+use liquify_scrypto::client::buy_list_key;
+use liquify_scrypto::router::{self, route_unstake, BuyListEntry, RouterConfig};
+use liquify_scrypto::ReceiptDetailData;
+
+mod common;
 
 #[derive(Clone)]
 pub struct Account {
@@ -206,10 +211,16 @@ impl TestEnvironment {
         manifest: TransactionManifestV1, 
         account: Account,
     ) -> TransactionReceipt {
-        self.ledger.execute_manifest(
+        let receipt = self.ledger.execute_manifest(
             manifest, 
             vec![NonFungibleGlobalId::from_public_key(&account.public_key)]
-        )
+        );
+
+        if receipt.is_commit_success() {
+            common::assert_accounting_invariants(&mut self.ledger, self.liquify_component);
+        }
+
+        receipt
     }
 }
 
@@ -274,15 +285,15 @@ for i in 0..NUM_LIQUIDITY_POSITIONS {
     let auto_unstake = true;  // All true for off-ledger test
     
     // Calculate the key that will be created
-    let receipt_id = (i + 1) as u32;
+    let receipt_id = (i + 1) as u64;
     let position = (i + 1) as u64;
     
     // Use the CORRECT bit layout matching BuyListKey::new()
-    let auto_unstake_flag = if auto_unstake { 1u128 } else { 0u128 };
+    let flags = 0x80u128 | if auto_unstake { 0x01u128 } else { 0u128 };
     let key = ((discount_basis_points as u128) << 112) |  // Top 16 bits
               ((position as u128) << 48) |                // Next 64 bits  
-              ((auto_unstake_flag as u128) << 32) |       // Next 16 bits
-              (receipt_id as u128);                       // Bottom 32 bits
+              (flags << 40) |                             // Next 8 bits
+              (receipt_id as u128);                       // Bottom 40 bits
     
     expected_keys.push(key);
         
@@ -443,4 +454,242 @@ for i in 0..NUM_LIQUIDITY_POSITIONS {
     println!("✓ Small order correctly skipped auto_unstake=true positions (no XRD received)");
     
     println!("\n✓ All tests passed!");
-}
\ No newline at end of file
+}
+// Snapshots the buy list the way an off-ledger router would
+fn get_buy_list_entries(ledger: &mut TestEnvironment, validator_address: ComponentAddress) -> Vec<BuyListEntry> {
+    let liquify_component = ledger.liquify_component;
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(liquify_component, "get_raw_buy_list_range", manifest_args!(0u64, 100u64))
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    let buy_list: Vec<(u128, NonFungibleGlobalId)> = receipt.expect_commit_success().output(1);
+
+    let mut entries: Vec<BuyListEntry> = Vec::new();
+    for (buy_list_key, global_id) in buy_list {
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_method(liquify_component, "get_receipt_detail", manifest_args!(global_id.local_id().clone()))
+            .build();
+        let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+        let detail: ReceiptDetailData = receipt.expect_commit_success().output(1);
+
+        let discount = detail.discount_overrides.iter()
+            .find(|(validator, _)| *validator == validator_address)
+            .map(|(_, discount)| *discount)
+            .unwrap_or(detail.effective_discount);
+
+        entries.push(BuyListEntry {
+            key: buy_list_key,
+            discount,
+            xrd_liquidity_available: detail.sub_positions.iter()
+                .find(|sub_position| sub_position.buy_list_key == buy_list_key)
+                .map(|sub_position| sub_position.xrd_liquidity_available)
+                .unwrap_or(detail.xrd_liquidity_available),
+        });
+    }
+
+    entries
+}
+
+fn get_redemption_rate(ledger: &mut TestEnvironment, validator_address: ComponentAddress) -> Decimal {
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(validator_address, "get_redemption_value", manifest_args!(dec!(1)))
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success().output(1)
+}
+
+#[test]
+fn test_router_matches_on_ledger_fills() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account1 = ledger.user_account1.account_address;
+    let user_account4 = ledger.user_account4.account_address;
+    let liquify_component = ledger.liquify_component;
+    let lsu_resource_address = ledger.lsu_resource_address;
+
+    let key = Secp256k1PrivateKey::from_u64(1u64).unwrap().public_key();
+    let validator_address = ledger.ledger.get_active_validator_with_key(&key);
+
+    // Create positions at a few different discounts so routing has to follow book order
+    for i in 0..20 {
+        let discount = match i % 3 {
+            0 => dec!("0.0010"),
+            1 => dec!("0.0005"),
+            _ => dec!("0.0020"),
+        };
+
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(user_account4, XRD, dec!(100))
+            .take_all_from_worktop(XRD, "xrd")
+            .call_method_with_name_lookup(liquify_component, "add_liquidity", |lookup| {(
+                lookup.bucket("xrd"),
+                discount,
+                false,             // auto_unstake
+                false,             // auto_refill
+                dec!("0"),         // refill_threshold
+                dec!("5"),         // automation_fee
+            )})
+            .call_method(
+                user_account4,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        
+        let receipt = ledger.execute_manifest(manifest, ledger.user_account4.clone());
+        receipt.expect_commit_success();
+    }
+
+    let entries = get_buy_list_entries(&mut ledger, validator_address);
+    let redemption_rate = get_redemption_rate(&mut ledger, validator_address);
+
+    // Component defaults, with the small order threshold lowered by the test environment
+    let config = RouterConfig {
+        max_keys: 30,
+        platform_fee: dec!("0.0005"),
+        small_order_threshold: dec!(1),
+        dust_threshold: dec!("0.000001"),
+        sweep_dust: false,
+        tier_matching_policies: Vec::new(),
+    };
+
+    let lsu_amount = dec!(250);
+    let route = route_unstake(&entries, lsu_amount, redemption_rate, &config);
+    println!("Router picked {} keys, expecting {} XRD for {} LSU", route.order_keys.len(), route.xrd_expected, route.lsu_matched);
+
+    // The cheapest tier is matched first
+    assert!(route.order_keys.len() > 1);
+    assert!(route.order_keys.windows(2).all(|pair| pair[0] < pair[1]));
+
+    let initial_xrd = ledger.ledger.get_component_balance(user_account1, XRD);
+    let initial_lsu = ledger.ledger.get_component_balance(user_account1, lsu_resource_address);
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account1, lsu_resource_address, lsu_amount)
+        .take_all_from_worktop(lsu_resource_address, "lsu")
+        .call_method_with_name_lookup(liquify_component, "liquify_unstake_off_ledger", |lookup| {
+            (lookup.bucket("lsu"), route.order_keys.clone())
+        })
+        .call_method(
+            user_account1,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success();
+
+    let xrd_received = ledger.ledger.get_component_balance(user_account1, XRD) - initial_xrd;
+    let lsu_spent = initial_lsu - ledger.ledger.get_component_balance(user_account1, lsu_resource_address);
+
+    assert_eq!(xrd_received, route.xrd_expected, "Router output doesn't match the on-ledger fill");
+    assert_eq!(lsu_spent, route.lsu_matched, "Router LSU amount doesn't match the on-ledger fill");
+}
+
+#[test]
+fn test_router_matches_pro_rata_tier() {
+    let mut ledger = TestEnvironment::instantiate_test();
+    let user_account1 = ledger.user_account1.account_address;
+    let user_account4 = ledger.user_account4.account_address;
+    let admin_account = ledger.admin_account.account_address;
+    let liquify_component = ledger.liquify_component;
+    let owner_badge = ledger.owner_badge;
+    let lsu_resource_address = ledger.lsu_resource_address;
+
+    let key = Secp256k1PrivateKey::from_u64(1u64).unwrap().public_key();
+    let validator_address = ledger.ledger.get_active_validator_with_key(&key);
+
+    // Two positions share the pro-rata tier, a third sits behind it
+    for (xrd_amount, discount) in [(dec!(100), dec!("0.0010")), (dec!(300), dec!("0.0010")), (dec!(100), dec!("0.0020"))] {
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(user_account4, XRD, xrd_amount)
+            .take_all_from_worktop(XRD, "xrd")
+            .call_method_with_name_lookup(liquify_component, "add_liquidity", |lookup| {(
+                lookup.bucket("xrd"),
+                discount,
+                false,             // auto_unstake
+                false,             // auto_refill
+                dec!("0"),         // refill_threshold
+                dec!("5"),         // automation_fee
+            )})
+            .call_method(
+                user_account4,
+                "deposit_batch",
+                manifest_args!(ManifestExpression::EntireWorktop),
+            )
+            .build();
+        let receipt = ledger.execute_manifest(manifest, ledger.user_account4.clone());
+        receipt.expect_commit_success();
+    }
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_amount(admin_account, owner_badge, 1)
+        .call_method(liquify_component, "set_tier_matching_policy", manifest_args!(dec!("0.0010"), router::MatchingPolicy::ProRata))
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.admin_account.clone());
+    receipt.expect_commit_success();
+
+    let entries = get_buy_list_entries(&mut ledger, validator_address);
+    let redemption_rate = get_redemption_rate(&mut ledger, validator_address);
+
+    let mut tier_matching_policies = vec![router::MatchingPolicy::Fifo; 201];
+    tier_matching_policies[buy_list_key::tier_index(10)] = router::MatchingPolicy::ProRata;
+    let config = RouterConfig {
+        max_keys: 30,
+        platform_fee: dec!("0.0005"),
+        small_order_threshold: dec!(1),
+        dust_threshold: dec!("0.000001"),
+        sweep_dust: false,
+        tier_matching_policies,
+    };
+
+    // The unstake fits in the first position, but the pro-rata tier spreads it over both
+    let lsu_amount = dec!(50);
+    let fifo_route = route_unstake(&entries, lsu_amount, redemption_rate, &RouterConfig { tier_matching_policies: Vec::new(), ..config.clone() });
+    assert_eq!(fifo_route.order_keys.len(), 1);
+
+    let route = route_unstake(&entries, lsu_amount, redemption_rate, &config);
+    assert_eq!(route.order_keys, vec![entries[0].key, entries[1].key]);
+
+    let initial_xrd = ledger.ledger.get_component_balance(user_account1, XRD);
+    let initial_lsu = ledger.ledger.get_component_balance(user_account1, lsu_resource_address);
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(user_account1, lsu_resource_address, lsu_amount)
+        .take_all_from_worktop(lsu_resource_address, "lsu")
+        .call_method_with_name_lookup(liquify_component, "liquify_unstake_off_ledger", |lookup| {
+            (lookup.bucket("lsu"), route.order_keys.clone())
+        })
+        .call_method(
+            user_account1,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+        .build();
+    let receipt = ledger.execute_manifest(manifest, ledger.user_account1.clone());
+    receipt.expect_commit_success();
+
+    let xrd_received = ledger.ledger.get_component_balance(user_account1, XRD) - initial_xrd;
+    let lsu_spent = initial_lsu - ledger.ledger.get_component_balance(user_account1, lsu_resource_address);
+
+    assert_eq!(xrd_received, route.xrd_expected, "Router output doesn't match the on-ledger fill");
+    assert_eq!(lsu_spent, route.lsu_matched, "Router LSU amount doesn't match the on-ledger fill");
+
+    // The router's split is the one the component made
+    let entries_after = get_buy_list_entries(&mut ledger, validator_address);
+    let filled: Vec<Decimal> = entries.iter().zip(entries_after.iter())
+        .map(|(before, after)| before.xrd_liquidity_available - after.xrd_liquidity_available)
+        .collect();
+    let difference = filled[1] - filled[0] * dec!(3);
+    assert!(difference.checked_abs().unwrap() < dec!("0.000001"), "Fills should be proportional to available liquidity");
+    assert_eq!(filled[2], dec!(0));
+}